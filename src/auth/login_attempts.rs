@@ -0,0 +1,263 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument},
+    Collection, IndexModel,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// What failed logins are counted against. Gateways are counted apart from web
+// logins, so a gateway stuck in a reconnect loop never locks out its owner.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AttemptScope {
+    // Keyed by the email
+    #[serde(rename = "web_account")]
+    WebAccount,
+    #[serde(rename = "web_ip")]
+    WebIp,
+    // Keyed by the gateway client id
+    #[serde(rename = "iot_client")]
+    IotClient,
+    #[serde(rename = "iot_ip")]
+    IotIp,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ThrottlePolicy {
+    // Failures allowed before any wait
+    pub free_attempts: u32,
+    // The wait doubles with every further failure, up to the max
+    pub base_delay_secs: u64,
+    pub max_delay_secs: u64,
+    // Failures after which the key is locked for `lockout_secs`, only waits
+    // are enforced when omitted
+    #[serde(default)]
+    pub lockout_after: Option<u32>,
+    #[serde(default)]
+    pub lockout_secs: u64,
+    // Failures are forgotten after this long without a new one
+    pub reset_after_secs: u64,
+}
+
+impl ThrottlePolicy {
+    pub fn is_lockout(&self, failures: u32) -> bool {
+        self.lockout_after.is_some_and(|lockout_after| failures >= lockout_after)
+    }
+
+    // How long to refuse attempts after `failures` consecutive failures
+    pub fn delay_secs(&self, failures: u32) -> u64 {
+        if self.is_lockout(failures) {
+            return self.lockout_secs;
+        }
+        if failures <= self.free_attempts {
+            return 0;
+        }
+        2u64.checked_pow(failures - self.free_attempts - 1)
+            .and_then(|factor| factor.checked_mul(self.base_delay_secs))
+            .unwrap_or(u64::MAX)
+            .min(self.max_delay_secs)
+    }
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct LoginAttempts {
+    pub scope: AttemptScope,
+    pub key: String,
+    // Consecutive failures, restarted by a lockout
+    pub failures: u32,
+    // Attempts are refused until then
+    pub blocked_until: SystemTime,
+    // Whether the block is a lockout rather than a backoff wait
+    pub locked: bool,
+    pub last_failure_at: SystemTime,
+    // Purged from then on
+    pub expires_at: SystemTime,
+}
+
+pub struct FailureOutcome {
+    pub delay_secs: u64,
+    // Set only by the failure that started the lockout, so that it is
+    // reported once
+    pub locked_out: bool,
+}
+
+pub fn get_login_attempts_coll(mongoc: &mongodb::Client) -> Collection<LoginAttempts> {
+    mongoc.default_database().unwrap().collection("login_attempts")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_login_attempts_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "scope": 1, "key": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "expires_at.secs_since_epoch": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+fn secs_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Seconds left before the key may be tried again, if it is blocked
+pub async fn blocked_for(
+    mongoc: &mongodb::Client,
+    scope: AttemptScope,
+    key: &str,
+) -> mongodb::error::Result<Option<u64>> {
+    let attempts = get_login_attempts_coll(mongoc)
+        .find_one(doc! { "scope": to_bson(&scope)?, "key": key }, None)
+        .await?;
+    Ok(attempts.and_then(|attempts| {
+        attempts
+            .blocked_until
+            .duration_since(SystemTime::now())
+            .ok()
+            .map(|left| left.as_secs().max(1))
+    }))
+}
+
+pub async fn record_failure(
+    mongoc: &mongodb::Client,
+    policy: &ThrottlePolicy,
+    scope: AttemptScope,
+    key: &str,
+) -> mongodb::error::Result<FailureOutcome> {
+    let coll = get_login_attempts_coll(mongoc);
+    let now = SystemTime::now();
+    let filter = doc! { "scope": to_bson(&scope)?, "key": key };
+
+    // Failures older than the reset window no longer count
+    let mut stale = filter.clone();
+    stale.insert("expires_at.secs_since_epoch", doc! { "$lte": secs_since_epoch(now) });
+    coll.delete_one(stale, None).await?;
+
+    let attempts = coll
+        .find_one_and_update(
+            filter.clone(),
+            doc! {
+                "$inc": { "failures": 1 },
+                "$set": { "last_failure_at": to_bson(&now)? },
+                "$setOnInsert": {
+                    "blocked_until": to_bson(&now)?,
+                    "locked": false,
+                    "expires_at": to_bson(&now)?,
+                },
+            },
+            FindOneAndUpdateOptions::builder()
+                .upsert(true)
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await?;
+    let failures = attempts.map(|attempts| attempts.failures).unwrap_or(1);
+
+    let locked_out = policy.is_lockout(failures);
+    let delay_secs = policy.delay_secs(failures);
+    let blocked_until = now + Duration::from_secs(delay_secs);
+    let expires_at = blocked_until + Duration::from_secs(policy.reset_after_secs);
+    let mut update = doc! {
+        "blocked_until": to_bson(&blocked_until)?,
+        "locked": locked_out,
+        "expires_at": to_bson(&expires_at)?,
+    };
+    // The next attempts after a lockout start over with the free attempts
+    if locked_out {
+        update.insert("failures", 0);
+    }
+    coll.update_one(filter, doc! { "$set": update }, None).await?;
+    Ok(FailureOutcome {
+        delay_secs,
+        locked_out,
+    })
+}
+
+// Forgets the failures of the key, after a successful login or to unlock it
+pub async fn clear_failures(
+    mongoc: &mongodb::Client,
+    scope: AttemptScope,
+    key: &str,
+) -> mongodb::error::Result<bool> {
+    let result = get_login_attempts_coll(mongoc)
+        .delete_one(doc! { "scope": to_bson(&scope)?, "key": key }, None)
+        .await?;
+    Ok(result.deleted_count > 0)
+}
+
+pub async fn find_lockouts(mongoc: &mongodb::Client) -> mongodb::error::Result<Vec<LoginAttempts>> {
+    let mut cursor = get_login_attempts_coll(mongoc)
+        .find(
+            doc! {
+                "locked": true,
+                "blocked_until.secs_since_epoch": { "$gt": secs_since_epoch(SystemTime::now()) },
+            },
+            None,
+        )
+        .await?;
+    let mut lockouts = vec![];
+    while cursor.advance().await? {
+        lockouts.push(cursor.deserialize_current()?);
+    }
+    Ok(lockouts)
+}
+
+pub async fn purge_expired_login_attempts(mongoc: &mongodb::Client) -> mongodb::error::Result<u64> {
+    let result = get_login_attempts_coll(mongoc)
+        .delete_many(
+            doc! { "expires_at.secs_since_epoch": { "$lte": secs_since_epoch(SystemTime::now()) } },
+            None,
+        )
+        .await?;
+    Ok(result.deleted_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(lockout_after: Option<u32>) -> ThrottlePolicy {
+        ThrottlePolicy {
+            free_attempts: 3,
+            base_delay_secs: 2,
+            max_delay_secs: 60,
+            lockout_after,
+            lockout_secs: 900,
+            reset_after_secs: 3600,
+        }
+    }
+
+    #[test]
+    fn waits_double_after_the_free_attempts() {
+        let policy = policy(None);
+        assert_eq!(policy.delay_secs(1), 0);
+        assert_eq!(policy.delay_secs(3), 0);
+        assert_eq!(policy.delay_secs(4), 2);
+        assert_eq!(policy.delay_secs(5), 4);
+        assert_eq!(policy.delay_secs(8), 32);
+        assert_eq!(policy.delay_secs(9), 60);
+        assert_eq!(policy.delay_secs(u32::MAX), 60);
+    }
+
+    #[test]
+    fn lockouts_only_happen_when_configured() {
+        assert!(!policy(None).is_lockout(1000));
+
+        let policy = policy(Some(6));
+        assert!(!policy.is_lockout(5));
+        assert_eq!(policy.delay_secs(5), 4);
+        assert!(policy.is_lockout(6));
+        assert_eq!(policy.delay_secs(6), 900);
+    }
+}
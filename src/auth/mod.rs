@@ -1,11 +1,53 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
 use hmac::{Hmac, Mac};
 use jwt::{FromBase64, SignWithKey, VerifyWithKey};
 use mongodb::bson::doc;
+use once_cell::sync::Lazy;
+use ring::rand::{SecureRandom, SystemRandom};
 use serde::{Deserialize, Serialize};
-use sha2::Sha256;
+use sha2::{Digest, Sha256};
 
 use crate::backend_core::models::User;
 
+use self::{
+    security_events::{record_security_event, SecurityEventKind},
+    sessions::{is_iot_session_active, touch_session},
+};
+
+pub mod hashing;
+pub mod login_attempts;
+pub mod password_resets;
+pub mod policy;
+pub mod principal;
+pub mod security_events;
+pub mod sessions;
+pub mod totp;
+pub mod two_factor;
+
+const OPAQUE_TOKEN_LEN: usize = 32;
+
+// Owners of the client ids seen on MQTT, so that ingestion does not look the
+// user up for every message. Entries are dropped when a user joins or leaves.
+static CLIENT_OWNERS: Lazy<Mutex<HashMap<String, String>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+pub fn forget_client(client_id: &str) {
+    CLIENT_OWNERS.lock().unwrap().remove(client_id);
+}
+
+// Gateways publish on `<client_id>/<feature>-metrics`
+pub fn get_client_id_from_topic(topic: &str) -> Option<&str> {
+    let (client_id, metrics) = topic.split_once('/')?;
+    if client_id.is_empty() || !metrics.ends_with("-metrics") {
+        return None;
+    }
+    Some(client_id)
+}
+
 pub fn sign_jwt(key: &str, claim: &impl Serialize) -> Option<String> {
     let key: Hmac<Sha256> = Hmac::new_from_slice(key.as_bytes()).ok()?;
     claim.sign_with_key(&key).ok()
@@ -16,13 +58,48 @@ pub fn decrypt_jwt<T: FromBase64>(key: &str, token_str: &str) -> Option<T> {
     token_str.verify_with_key(&key).ok()
 }
 
+// Random tokens for refresh and password reset, only their hash is stored
+pub(crate) fn generate_opaque_token() -> Option<String> {
+    let mut bytes = [0u8; OPAQUE_TOKEN_LEN];
+    SystemRandom::new().fill(&mut bytes).ok()?;
+    Some(bytes.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+pub(crate) fn hash_opaque_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
 pub fn get_client_id_from_client_token(key: &str, token: String) -> Option<String> {
     let claim = decrypt_jwt::<IotClientClaim>(key, token.as_str())?;
-    Some(claim.client_id)
+    (claim.exp > now_secs()).then_some(claim.client_id)
 }
 
+// Only checks the signature and the expiry, see `get_email_from_web_session`
+// for tokens that may have been revoked.
 pub fn get_email_from_web_token(key: &str, token: String) -> Option<String> {
     let claim = decrypt_jwt::<WebClientClaim>(key, token.as_str())?;
+    (claim.exp > now_secs()).then_some(claim.email)
+}
+
+pub async fn get_email_from_web_session(
+    key: &str,
+    token: String,
+    mongoc: &mongodb::Client,
+) -> Option<String> {
+    let claim = decrypt_jwt::<WebClientClaim>(key, token.as_str())?;
+    if claim.exp <= now_secs() || !touch_session(mongoc, &claim.session_id).await {
+        return None;
+    }
     Some(claim.email)
 }
 
@@ -31,7 +108,7 @@ pub async fn get_client_id_from_web_token(
     token: String,
     mongoc: &mut mongodb::Client,
 ) -> Option<String> {
-    let email = get_email_from_web_token(key, token)?;
+    let email = get_email_from_web_session(key, token, mongoc).await?;
     if let Ok(Some(user_doc)) = mongoc
         .default_database()
         .unwrap()
@@ -45,19 +122,81 @@ pub async fn get_client_id_from_web_token(
     }
 }
 
-pub async fn get_email_from_client_token(
+// Client id of the gateway that sends and receives the data of `email`
+pub async fn get_client_id_from_email(mongoc: &mongodb::Client, email: &str) -> Option<String> {
+    if let Ok(Some(user_doc)) = mongoc
+        .default_database()
+        .unwrap()
+        .collection::<User>("users")
+        .find_one(doc! { "email": email }, None)
+        .await
+    {
+        Some(user_doc.client_id)
+    } else {
+        None
+    }
+}
+
+// Like `get_client_id_from_client_token`, but rejects tokens whose session
+// has been revoked.
+async fn get_client_id_from_iot_session(
     key: &str,
     token: String,
+    mongoc: &mongodb::Client,
+) -> Option<String> {
+    let claim = decrypt_jwt::<IotClientClaim>(key, token.as_str())?;
+    if claim.exp <= now_secs() || !is_iot_session_active(mongoc, &claim.session_id).await {
+        return None;
+    }
+    Some(claim.client_id)
+}
+
+// The token of a gateway is only good for the topics of its own client id
+fn bind_token_to_topic(topic: &str, token_client_id: String) -> Result<String, Option<SecurityEventKind>> {
+    let topic_client_id = get_client_id_from_topic(topic).ok_or(None)?;
+    if token_client_id != topic_client_id {
+        return Err(Some(SecurityEventKind::TopicMismatch {
+            topic: topic.to_string(),
+            topic_client_id: topic_client_id.to_string(),
+            token_client_id,
+        }));
+    }
+    Ok(token_client_id)
+}
+
+// Email of the owner of the gateway that published on `topic`, if the token
+// was issued to the client the topic belongs to.
+pub async fn get_email_from_topic_token(
+    key: &str,
+    topic: &str,
+    token: String,
     mongoc: &mut mongodb::Client,
 ) -> Option<String> {
-    let client_id = get_client_id_from_client_token(key, token)?;
+    get_client_id_from_topic(topic)?;
+    let client_id = get_client_id_from_iot_session(key, token, mongoc).await?;
+    match bind_token_to_topic(topic, client_id) {
+        Ok(client_id) => get_email_from_client_id(client_id, mongoc).await,
+        Err(Some(mismatch)) => {
+            eprintln!("Token of another client was sent on topic '{}'", topic);
+            record_security_event(mongoc, mismatch).await;
+            None
+        }
+        Err(None) => None,
+    }
+}
+
+async fn get_email_from_client_id(client_id: String, mongoc: &mut mongodb::Client) -> Option<String> {
+    if let Some(email) = CLIENT_OWNERS.lock().unwrap().get(&client_id) {
+        return Some(email.clone());
+    }
     if let Ok(Some(user_doc)) = mongoc
         .default_database()
         .unwrap()
         .collection::<User>("users")
-        .find_one(doc! { "client_id": client_id }, None)
+        .find_one(doc! { "client_id": client_id.clone() }, None)
         .await
     {
+        CLIENT_OWNERS.lock().unwrap().insert(client_id, user_doc.email.clone());
         Some(user_doc.email)
     } else {
         None
@@ -68,10 +207,168 @@ pub async fn get_email_from_client_token(
 pub struct IotClientClaim {
     pub client_id: String,
     pub nonce: String,
+    pub session_id: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+impl IotClientClaim {
+    pub fn new(client_id: String, session_id: String, ttl_secs: u64) -> Self {
+        let iat = now_secs();
+        Self {
+            client_id,
+            nonce: uuid::Uuid::now_v7().into(),
+            session_id,
+            iat,
+            exp: iat + ttl_secs,
+        }
+    }
+}
+
+// Signed into the link of the verification mail. The field name differs from
+// `WebClientClaim` so that an access token cannot verify an account.
+#[derive(Serialize, Deserialize)]
+pub struct EmailVerificationClaim {
+    pub verified_email: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+impl EmailVerificationClaim {
+    pub fn new(email: String, ttl_secs: u64) -> Self {
+        let iat = now_secs();
+        Self {
+            verified_email: email,
+            iat,
+            exp: iat + ttl_secs,
+        }
+    }
+}
+
+pub fn get_email_from_verification_token(key: &str, token: &str) -> Option<String> {
+    let claim = decrypt_jwt::<EmailVerificationClaim>(key, token)?;
+    (claim.exp > now_secs()).then_some(claim.verified_email)
+}
+
+// Issued by the password step of a login when the account has two-factor
+// authentication, and only exchanged for access tokens with a valid code
+#[derive(Serialize, Deserialize)]
+pub struct PreAuthClaim {
+    pub pre_auth_email: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+impl PreAuthClaim {
+    pub fn new(email: String, ttl_secs: u64) -> Self {
+        let iat = now_secs();
+        Self {
+            pre_auth_email: email,
+            iat,
+            exp: iat + ttl_secs,
+        }
+    }
+}
+
+pub fn get_email_from_pre_auth_token(key: &str, token: &str) -> Option<String> {
+    let claim = decrypt_jwt::<PreAuthClaim>(key, token)?;
+    (claim.exp > now_secs()).then_some(claim.pre_auth_email)
 }
 
 #[derive(Serialize, Deserialize)]
 pub struct WebClientClaim {
     pub email: String,
     pub nonce: String,
+    pub session_id: String,
+    pub iat: u64,
+    pub exp: u64,
+}
+
+impl WebClientClaim {
+    pub fn new(email: String, session_id: String, ttl_secs: u64) -> Self {
+        let iat = now_secs();
+        Self {
+            email,
+            nonce: uuid::Uuid::now_v7().into(),
+            session_id,
+            iat,
+            exp: iat + ttl_secs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn client_id_is_the_first_topic_level() {
+        assert_eq!(get_client_id_from_topic("abc/fire-alert-metrics"), Some("abc"));
+        assert_eq!(get_client_id_from_topic("abc/remote-control-metrics"), Some("abc"));
+        assert_eq!(get_client_id_from_topic("/fire-alert-metrics"), None);
+        assert_eq!(get_client_id_from_topic("abc/fire-alert"), None);
+        assert_eq!(get_client_id_from_topic("abc"), None);
+    }
+
+    #[test]
+    fn tokens_only_work_on_their_own_topics() {
+        assert_eq!(
+            bind_token_to_topic("abc/fire-alert-metrics", String::from("abc")),
+            Ok(String::from("abc"))
+        );
+        assert_eq!(
+            bind_token_to_topic("abc/remote-control-metrics", String::from("xyz")),
+            Err(Some(SecurityEventKind::TopicMismatch {
+                topic: String::from("abc/remote-control-metrics"),
+                topic_client_id: String::from("abc"),
+                token_client_id: String::from("xyz"),
+            }))
+        );
+        assert_eq!(bind_token_to_topic("abc", String::from("abc")), Err(None));
+    }
+
+    #[test]
+    fn expired_tokens_are_rejected() {
+        let key = "secret";
+        let valid = sign_jwt(key, &IotClientClaim::new("abc".into(), "session".into(), 60)).unwrap();
+        assert_eq!(get_client_id_from_client_token(key, valid), Some(String::from("abc")));
+
+        let mut claim = WebClientClaim::new("a@b.c".into(), "session".into(), 60);
+        claim.exp = claim.iat - 1;
+        let expired = sign_jwt(key, &claim).unwrap();
+        assert_eq!(get_email_from_web_token(key, expired), None);
+    }
+
+    #[test]
+    fn access_tokens_cannot_verify_emails() {
+        let key = "secret";
+        let access = sign_jwt(key, &WebClientClaim::new("a@b.c".into(), "session".into(), 60)).unwrap();
+        assert_eq!(get_email_from_verification_token(key, &access), None);
+
+        let verification = sign_jwt(key, &EmailVerificationClaim::new("a@b.c".into(), 60)).unwrap();
+        assert_eq!(get_email_from_verification_token(key, &verification), Some(String::from("a@b.c")));
+        assert_eq!(get_email_from_web_token(key, verification), None);
+    }
+
+    #[test]
+    fn pre_auth_tokens_are_not_access_tokens() {
+        let key = "secret";
+        let access = sign_jwt(key, &WebClientClaim::new("a@b.c".into(), "session".into(), 60)).unwrap();
+        assert_eq!(get_email_from_pre_auth_token(key, &access), None);
+
+        let pre_auth = sign_jwt(key, &PreAuthClaim::new("a@b.c".into(), 60)).unwrap();
+        assert_eq!(get_email_from_pre_auth_token(key, &pre_auth), Some(String::from("a@b.c")));
+        assert_eq!(get_email_from_web_token(key, pre_auth.clone()), None);
+        assert_eq!(get_email_from_verification_token(key, &pre_auth), None);
+    }
+
+    #[test]
+    fn opaque_tokens_are_random_and_hashed() {
+        let first = generate_opaque_token().unwrap();
+        let second = generate_opaque_token().unwrap();
+        assert_eq!(first.len(), OPAQUE_TOKEN_LEN * 2);
+        assert_ne!(first, second);
+        assert_eq!(hash_opaque_token(&first), hash_opaque_token(&first));
+        assert_ne!(hash_opaque_token(&first), first);
+    }
 }
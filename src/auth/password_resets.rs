@@ -0,0 +1,96 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mongodb::{bson::doc, options::IndexOptions, Collection, IndexModel};
+use serde::{Deserialize, Serialize};
+
+use super::{generate_opaque_token, hash_opaque_token};
+
+#[derive(Serialize, Deserialize)]
+pub struct PasswordReset {
+    pub email: String,
+    pub token_hash: String,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+}
+
+pub fn get_password_reset_coll(mongoc: &mongodb::Client) -> Collection<PasswordReset> {
+    mongoc.default_database().unwrap().collection("password_resets")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_password_reset_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "token_hash": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "email": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+fn secs_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Replaces any pending reset of the user, only the latest link works
+pub async fn create_password_reset(
+    mongoc: &mongodb::Client,
+    email: String,
+    ttl_secs: u64,
+) -> mongodb::error::Result<Option<String>> {
+    let Some(token) = generate_opaque_token() else {
+        return Ok(None);
+    };
+    let reset_coll = get_password_reset_coll(mongoc);
+    reset_coll.delete_many(doc! { "email": email.clone() }, None).await?;
+    let now = SystemTime::now();
+    reset_coll
+        .insert_one(
+            PasswordReset {
+                email,
+                token_hash: hash_opaque_token(&token),
+                created_at: now,
+                expires_at: now + Duration::from_secs(ttl_secs),
+            },
+            None,
+        )
+        .await?;
+    Ok(Some(token))
+}
+
+// Returns the email the token was issued for. The token is deleted, so it
+// cannot be used twice.
+pub async fn consume_password_reset(
+    mongoc: &mongodb::Client,
+    token: &str,
+) -> mongodb::error::Result<Option<String>> {
+    let reset = get_password_reset_coll(mongoc)
+        .find_one_and_delete(
+            doc! {
+                "token_hash": hash_opaque_token(token),
+                "expires_at.secs_since_epoch": { "$gt": secs_since_epoch(SystemTime::now()) },
+            },
+            None,
+        )
+        .await?;
+    Ok(reset.map(|reset| reset.email))
+}
+
+pub async fn purge_expired_password_resets(mongoc: &mongodb::Client) -> mongodb::error::Result<u64> {
+    let result = get_password_reset_coll(mongoc)
+        .delete_many(
+            doc! { "expires_at.secs_since_epoch": { "$lte": secs_since_epoch(SystemTime::now()) } },
+            None,
+        )
+        .await?;
+    Ok(result.deleted_count)
+}
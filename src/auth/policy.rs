@@ -0,0 +1,146 @@
+use aide::axum::{routing::ApiMethodRouter, ApiRouter};
+use axum::{
+    body::{to_bytes, Body},
+    extract::{FromRequestParts, Query, RawPathParams, Request, State},
+    http::StatusCode,
+    middleware::{from_fn_with_state, Next},
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+
+use crate::backend_core::households::models::HouseholdRole;
+
+use super::principal::{auth_error, Principal, SECURITY_SCHEME};
+
+// Bodies are read whole to find the owner of the resource, anything larger is
+// not a request of this API anyway
+const MAX_BODY_BYTES: usize = 1 << 20;
+
+// What a route requires of the logged in user, declared next to the route
+#[derive(Clone, Copy, Debug)]
+pub enum Policy {
+    // Any logged in user, the handler decides the rest
+    Authenticated,
+    // At least this role in the household owning the resource, named by
+    // exactly one `email` path parameter, query parameter or body field.
+    // `Role(HouseholdRole::Owner)` only lets the owner of the resource in.
+    Role(HouseholdRole),
+}
+
+// Finds the owner in the path, the query and a JSON body, which is put back
+// for the handler. Handlers read the owner from any of these, so the request
+// is refused unless exactly one of them names it.
+async fn resource_owner(request: Request) -> Result<(Option<String>, Request), Response> {
+    let (mut parts, body) = request.into_parts();
+    let mut emails = vec![];
+    if let Ok(params) = RawPathParams::from_request_parts(&mut parts, &()).await {
+        emails.extend(
+            params
+                .iter()
+                .filter(|(key, _)| *key == "email")
+                .map(|(_, value)| value.to_string()),
+        );
+    }
+    if let Ok(Query(params)) = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri) {
+        emails.extend(
+            params
+                .into_iter()
+                .filter(|(key, _)| key == "email")
+                .map(|(_, value)| value),
+        );
+    }
+    let Ok(bytes) = to_bytes(body, MAX_BODY_BYTES).await else {
+        return Err(auth_error(StatusCode::PAYLOAD_TOO_LARGE, "Payload too large").into_response());
+    };
+    if let Ok(Value::Object(fields)) = serde_json::from_slice::<Value>(&bytes) {
+        match fields.get("email") {
+            Some(Value::String(email)) => emails.push(email.clone()),
+            Some(_) => return Err(auth_error(StatusCode::BAD_REQUEST, "Invalid email").into_response()),
+            None => {}
+        }
+    }
+    if emails.len() > 1 {
+        return Err(auth_error(StatusCode::BAD_REQUEST, "Ambiguous email").into_response());
+    }
+    Ok((emails.pop(), Request::from_parts(parts, Body::from(bytes))))
+}
+
+async fn enforce(State(policy): State<Policy>, request: Request, next: Next) -> Response {
+    let Some(principal) = request.extensions().get::<Principal>().cloned() else {
+        return auth_error(StatusCode::UNAUTHORIZED, "Unauthorized").into_response();
+    };
+    let request = match policy {
+        Policy::Authenticated => request,
+        Policy::Role(required_role) => {
+            let (owner_name, request) = match resource_owner(request).await {
+                Ok(found) => found,
+                Err(response) => return response,
+            };
+            let Some(owner_name) = owner_name else {
+                return auth_error(StatusCode::BAD_REQUEST, "Missing email").into_response();
+            };
+            if !principal.has_role(&owner_name, required_role) {
+                return auth_error(StatusCode::FORBIDDEN, "Forbidden").into_response();
+            }
+            request
+        }
+    };
+    next.run(request).await
+}
+
+pub trait AuthorizedRouter<S> {
+    // Like `api_route`, but every method of `method_router` is only reached
+    // by requests that satisfy `policy`. Routes with a different policy per
+    // method are declared once per policy.
+    fn authorized_route(self, path: &str, policy: Policy, method_router: ApiMethodRouter<S>) -> Self;
+}
+
+impl<S> AuthorizedRouter<S> for ApiRouter<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    fn authorized_route(self, path: &str, policy: Policy, method_router: ApiMethodRouter<S>) -> Self {
+        self.api_route_with(
+            path,
+            method_router.route_layer(from_fn_with_state(policy, enforce)),
+            |path_item| path_item.security_requirement(SECURITY_SCHEME),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn owner_of(uri: &str, body: &str) -> Result<Option<String>, StatusCode> {
+        let request = Request::builder().uri(uri).body(Body::from(body.to_string())).unwrap();
+        match resource_owner(request).await {
+            Ok((owner, request)) => {
+                // The handler still gets the whole body
+                let bytes = to_bytes(request.into_body(), MAX_BODY_BYTES).await.unwrap();
+                assert_eq!(bytes, body.as_bytes());
+                Ok(owner)
+            }
+            Err(response) => Err(response.status()),
+        }
+    }
+
+    #[tokio::test]
+    async fn the_owner_is_found_in_the_query_or_the_body() {
+        assert_eq!(owner_of("/rules?email=a%40home.com", "").await, Ok(Some(String::from("a@home.com"))));
+        assert_eq!(
+            owner_of("/rules", r#"{"email":"a@home.com","name":"x"}"#).await,
+            Ok(Some(String::from("a@home.com")))
+        );
+        assert_eq!(owner_of("/rules", r#"{"name":"x"}"#).await, Ok(None));
+    }
+
+    #[tokio::test]
+    async fn owners_named_twice_are_refused() {
+        let status = Err(StatusCode::BAD_REQUEST);
+        assert_eq!(owner_of("/rules?email=me%40home.com", r#"{"email":"victim@home.com"}"#).await, status);
+        assert_eq!(owner_of("/rules?email=me%40home.com", r#"{"email":"me@home.com"}"#).await, status);
+        assert_eq!(owner_of("/rules?email=me%40home.com&email=victim%40home.com", "").await, status);
+        assert_eq!(owner_of("/rules", r#"{"email":["victim@home.com"]}"#).await, status);
+    }
+}
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use aide::{
+    gen::GenContext,
+    openapi::{Operation, Response},
+    transform::TransformOperation,
+    OperationInput, OperationOutput,
+};
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{request::Parts, StatusCode},
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::{backend_core::households::models::HouseholdRole, json::Json};
+
+// Name of the OpenAPI security scheme of the `jwt` header
+pub const SECURITY_SCHEME: &str = "jwt";
+
+#[derive(Serialize, JsonSchema)]
+pub struct AuthErrorResponse {
+    message: String,
+}
+
+pub fn auth_error(status_code: StatusCode, message: &str) -> (StatusCode, Json<AuthErrorResponse>) {
+    (
+        status_code,
+        Json(AuthErrorResponse {
+            message: String::from(message),
+        }),
+    )
+}
+
+// The logged in user of a request. Only the auth middleware sets it, from the
+// session of the `jwt` header, so unlike headers it cannot come from the client.
+#[derive(Clone, Debug)]
+pub struct Principal {
+    pub email: String,
+    // Roles in the households of others, keyed by their `owner_name`
+    pub household_roles: HashMap<String, HouseholdRole>,
+}
+
+impl Principal {
+    // Users always own the data under their own email
+    pub fn role_in(&self, owner_name: &str) -> Option<HouseholdRole> {
+        if self.email == owner_name {
+            return Some(HouseholdRole::Owner);
+        }
+        self.household_roles.get(owner_name).copied()
+    }
+
+    pub fn has_role(&self, owner_name: &str, required_role: HouseholdRole) -> bool {
+        self.role_in(owner_name).is_some_and(|role| role >= required_role)
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for Principal {
+    type Rejection = (StatusCode, Json<AuthErrorResponse>);
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or_else(|| auth_error(StatusCode::UNAUTHORIZED, "Unauthorized"))
+    }
+}
+
+impl OperationInput for Principal {
+    fn operation_input(_ctx: &mut GenContext, operation: &mut Operation) {
+        let _ = TransformOperation::new(operation).security_requirement(SECURITY_SCHEME);
+    }
+
+    fn inferred_early_responses(ctx: &mut GenContext, operation: &mut Operation) -> Vec<(Option<u16>, Response)> {
+        Json::<AuthErrorResponse>::operation_response(ctx, operation)
+            .map(|response| vec![(Some(401), response)])
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn principal(email: &str, roles: &[(&str, HouseholdRole)]) -> Principal {
+        Principal {
+            email: email.to_string(),
+            household_roles: roles
+                .iter()
+                .map(|(owner_name, role)| (owner_name.to_string(), *role))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn users_own_their_data() {
+        let principal = principal("a@home.com", &[]);
+        assert!(principal.has_role("a@home.com", HouseholdRole::Owner));
+        assert!(!principal.has_role("b@home.com", HouseholdRole::Viewer));
+    }
+
+    #[test]
+    fn viewers_cannot_send_commands() {
+        let principal = principal("kid@home.com", &[("parent@home.com", HouseholdRole::Viewer)]);
+        assert!(principal.has_role("parent@home.com", HouseholdRole::Viewer));
+        assert!(!principal.has_role("parent@home.com", HouseholdRole::Member));
+    }
+
+    #[test]
+    fn roles_grant_less_privileged_access() {
+        let principal = principal("partner@home.com", &[("parent@home.com", HouseholdRole::Admin)]);
+        assert!(principal.has_role("parent@home.com", HouseholdRole::Member));
+        assert!(principal.has_role("parent@home.com", HouseholdRole::Admin));
+        assert!(!principal.has_role("parent@home.com", HouseholdRole::Owner));
+    }
+
+    #[test]
+    fn roles_do_not_leak_across_households() {
+        let principal = principal("partner@home.com", &[("parent@home.com", HouseholdRole::Admin)]);
+        assert!(!principal.has_role("neighbour@home.com", HouseholdRole::Viewer));
+    }
+}
@@ -0,0 +1,197 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::{doc, from_document, Document},
+    Collection, IndexModel,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::login_attempts::AttemptScope;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug, PartialEq)]
+#[serde(tag = "kind")]
+pub enum SecurityEventKind {
+    // A gateway published with a valid token on the topic of another client
+    #[serde(rename = "topic_mismatch")]
+    TopicMismatch {
+        topic: String,
+        topic_client_id: String,
+        token_client_id: String,
+    },
+    // Too many failed logins, the account or gateway is locked for a while
+    #[serde(rename = "login_lockout")]
+    LoginLockout {
+        scope: AttemptScope,
+        key: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SecurityEvent {
+    #[serde(flatten)]
+    pub kind: SecurityEventKind,
+    pub timestamp: SystemTime,
+}
+
+pub fn get_security_event_coll(mongoc: &mongodb::Client) -> Collection<SecurityEvent> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("security_events")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_security_event_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "kind": 1, "topic_client_id": 1, "timestamp.secs_since_epoch": -1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "kind": 1, "token_client_id": 1, "timestamp.secs_since_epoch": -1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "timestamp.secs_since_epoch": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn record_security_event(mongoc: &mongodb::Client, kind: SecurityEventKind) {
+    let event = SecurityEvent {
+        kind,
+        timestamp: SystemTime::now(),
+    };
+    if let Err(e) = get_security_event_coll(mongoc).insert_one(event, None).await {
+        eprintln!("Failed to record security event: {}", e);
+    }
+}
+
+
+// How often the same event happened within a period
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct SecurityEventCount {
+    #[serde(flatten)]
+    pub kind: SecurityEventKind,
+    pub count: u32,
+    pub last_seen_at: SystemTime,
+}
+
+fn secs_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Events are identical when everything but their time is, so a gateway
+// publishing on the topic of another client is counted rather than listed
+// once per message
+fn count_pipeline(since: SystemTime) -> Vec<Document> {
+    vec![
+        doc! { "$match": { "timestamp.secs_since_epoch": { "$gte": secs_since_epoch(since) } } },
+        doc! { "$sort": { "timestamp.secs_since_epoch": -1, "timestamp.nanos_since_epoch": -1 } },
+        doc! { "$group": {
+            "_id": {
+                "kind": "$kind",
+                "topic": "$topic",
+                "topic_client_id": "$topic_client_id",
+                "token_client_id": "$token_client_id",
+                "scope": "$scope",
+                "key": "$key",
+            },
+            "count": { "$sum": 1 },
+            "last_seen_at": { "$first": "$timestamp" },
+        } },
+        doc! { "$replaceRoot": { "newRoot": {
+            "$mergeObjects": ["$_id", { "count": "$count", "last_seen_at": "$last_seen_at" }],
+        } } },
+        doc! { "$sort": { "count": -1 } },
+    ]
+}
+
+pub async fn count_security_events(
+    mongoc: &mongodb::Client,
+    since: SystemTime,
+) -> mongodb::error::Result<Vec<SecurityEventCount>> {
+    let mut cursor = get_security_event_coll(mongoc)
+        .aggregate(count_pipeline(since), None)
+        .await?;
+    let mut counts = vec![];
+    while cursor.advance().await? {
+        counts.push(from_document(cursor.deserialize_current()?)?);
+    }
+    Ok(counts)
+}
+
+pub async fn purge_security_events(mongoc: &mongodb::Client, keep_days: u64) -> mongodb::error::Result<u64> {
+    let before = SystemTime::now()
+        .checked_sub(Duration::from_secs(keep_days.saturating_mul(24 * 3600)))
+        .map(secs_since_epoch)
+        .unwrap_or_default();
+    let result = get_security_event_coll(mongoc)
+        .delete_many(doc! { "timestamp.secs_since_epoch": { "$lt": before } }, None)
+        .await?;
+    Ok(result.deleted_count)
+}
+
+#[cfg(test)]
+mod tests {
+    use mongodb::bson::{to_document, Bson};
+
+    use super::*;
+
+    fn topic_mismatch() -> SecurityEventKind {
+        SecurityEventKind::TopicMismatch {
+            topic: String::from("abc/fire-alert-metrics"),
+            topic_client_id: String::from("abc"),
+            token_client_id: String::from("xyz"),
+        }
+    }
+
+    #[test]
+    fn events_are_stored_flat_for_the_indexes() {
+        let event = to_document(&SecurityEvent {
+            kind: topic_mismatch(),
+            timestamp: SystemTime::now(),
+        })
+        .unwrap();
+        assert_eq!(event.get_str("kind"), Ok("topic_mismatch"));
+        assert_eq!(event.get_str("topic_client_id"), Ok("abc"));
+        assert_eq!(event.get_str("token_client_id"), Ok("xyz"));
+        assert!(event.get_document("timestamp").unwrap().contains_key("secs_since_epoch"));
+    }
+
+    #[test]
+    fn counts_read_back_from_the_grouped_documents() {
+        let last_seen_at = SystemTime::now();
+        // Fields of other kinds are missing or null after grouping
+        let mut grouped = to_document(&topic_mismatch()).unwrap();
+        grouped.insert("scope", Bson::Null);
+        grouped.insert("count", 3);
+        grouped.insert("last_seen_at", mongodb::bson::to_bson(&last_seen_at).unwrap());
+
+        let count: SecurityEventCount = from_document(grouped).unwrap();
+        assert_eq!(count.kind, topic_mismatch());
+        assert_eq!(count.count, 3);
+        assert_eq!(count.last_seen_at, last_seen_at);
+    }
+
+    #[test]
+    fn counts_group_on_every_field_of_every_kind() {
+        let pipeline = count_pipeline(SystemTime::now());
+        let group_id = pipeline[2].get_document("$group").unwrap().get_document("_id").unwrap();
+        let lockout = SecurityEventKind::LoginLockout {
+            scope: AttemptScope::IotClient,
+            key: String::from("abc"),
+        };
+        for kind in [topic_mismatch(), lockout] {
+            for field in to_document(&kind).unwrap().keys() {
+                assert_eq!(group_id.get_str(field), Ok(format!("${field}").as_str()));
+            }
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::{
+    collections::HashSet,
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument},
+    Collection, IndexModel,
+};
+use once_cell::sync::Lazy;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{generate_opaque_token, hash_opaque_token};
+
+// Gateway sessions known to be active. Tokens are checked on every MQTT
+// message, so the database is only asked once per session. Entries are
+// dropped when the session is revoked.
+static ACTIVE_IOT_SESSIONS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| Mutex::new(HashSet::new()));
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SessionKind {
+    #[serde(rename = "web")]
+    Web,
+    #[serde(rename = "iot")]
+    Iot,
+}
+
+// A login that can be refreshed until it expires or is revoked. Access tokens
+// carry the session id, so deleting the session revokes them too.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Session {
+    pub id: String,
+    pub kind: SessionKind,
+    // The user email for web sessions, the gateway client id for IoT sessions
+    pub subject: String,
+    // Only a hash of the refresh token is kept
+    pub refresh_token_hash: String,
+    #[serde(default)]
+    pub user_agent: Option<String>,
+    #[serde(default)]
+    pub ip: Option<String>,
+    pub created_at: SystemTime,
+    pub refreshed_at: SystemTime,
+    #[serde(default)]
+    pub last_used_at: Option<SystemTime>,
+    pub expires_at: SystemTime,
+}
+
+// Where a login or a refresh came from
+#[derive(Default, Clone)]
+pub struct SessionOrigin {
+    pub user_agent: Option<String>,
+    pub ip: Option<String>,
+}
+
+// The address of the client. `X-Forwarded-For` is only believed when the peer
+// is one of our proxies, and then only up to the first hop that is not: every
+// entry left of it could have been written by the client.
+pub fn client_ip(peer: IpAddr, forwarded_for: Option<&str>, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    let hops = forwarded_for.unwrap_or_default().rsplit(',');
+    for hop in hops {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match hop.trim().parse() {
+            Ok(ip) => client = ip,
+            // Garbage from a trusted proxy, stop at the proxy itself
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+pub fn get_session_coll(mongoc: &mongodb::Client) -> Collection<Session> {
+    mongoc.default_database().unwrap().collection("sessions")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_session_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "refresh_token_hash": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "kind": 1, "subject": 1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "expires_at.secs_since_epoch": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+fn secs_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Returns the new session along with its refresh token, which is never stored
+// in clear.
+pub async fn start_session(
+    mongoc: &mongodb::Client,
+    kind: SessionKind,
+    subject: String,
+    SessionOrigin { user_agent, ip }: SessionOrigin,
+    ttl_secs: u64,
+) -> mongodb::error::Result<Option<(Session, String)>> {
+    let Some(refresh_token) = generate_opaque_token() else {
+        return Ok(None);
+    };
+    let now = SystemTime::now();
+    let session = Session {
+        id: uuid::Uuid::now_v7().into(),
+        kind,
+        subject,
+        refresh_token_hash: hash_opaque_token(&refresh_token),
+        user_agent,
+        ip,
+        created_at: now,
+        refreshed_at: now,
+        last_used_at: Some(now),
+        expires_at: now + Duration::from_secs(ttl_secs),
+    };
+    get_session_coll(mongoc).insert_one(&session, None).await?;
+    Ok(Some((session, refresh_token)))
+}
+
+// Exchanges a refresh token for a new one. Each refresh token can only be used
+// once, a replayed token finds no session.
+pub async fn refresh_session(
+    mongoc: &mongodb::Client,
+    kind: SessionKind,
+    refresh_token: &str,
+    SessionOrigin { user_agent, ip }: SessionOrigin,
+    ttl_secs: u64,
+) -> mongodb::error::Result<Option<(Session, String)>> {
+    let Some(new_refresh_token) = generate_opaque_token() else {
+        return Ok(None);
+    };
+    let now = SystemTime::now();
+    let session = get_session_coll(mongoc)
+        .find_one_and_update(
+            doc! {
+                "kind": to_bson(&kind)?,
+                "refresh_token_hash": hash_opaque_token(refresh_token),
+                "expires_at.secs_since_epoch": { "$gt": secs_since_epoch(now) },
+            },
+            doc! { "$set": {
+                "refresh_token_hash": hash_opaque_token(&new_refresh_token),
+                "user_agent": user_agent,
+                "ip": ip,
+                "refreshed_at": to_bson(&now)?,
+                "last_used_at": to_bson(&now)?,
+                "expires_at": to_bson(&(now + Duration::from_secs(ttl_secs)))?,
+            } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await?;
+    Ok(session.map(|session| (session, new_refresh_token)))
+}
+
+fn active_filter(session_id: &str) -> mongodb::bson::Document {
+    doc! {
+        "id": session_id,
+        "expires_at.secs_since_epoch": { "$gt": secs_since_epoch(SystemTime::now()) },
+    }
+}
+
+pub async fn end_session(mongoc: &mongodb::Client, session_id: &str) -> mongodb::error::Result<bool> {
+    let result = get_session_coll(mongoc)
+        .delete_one(doc! { "id": session_id }, None)
+        .await?;
+    ACTIVE_IOT_SESSIONS.lock().unwrap().remove(session_id);
+    Ok(result.deleted_count > 0)
+}
+
+// Revokes every session of a user or a gateway, except the one in use if any
+pub async fn end_sessions(
+    mongoc: &mongodb::Client,
+    kind: SessionKind,
+    subject: &str,
+    except_session_id: Option<&str>,
+) -> mongodb::error::Result<u64> {
+    let mut filter = doc! { "kind": to_bson(&kind)?, "subject": subject };
+    if let Some(session_id) = except_session_id {
+        filter.insert("id", doc! { "$ne": session_id });
+    }
+    let session_coll = get_session_coll(mongoc);
+    let mut cursor = session_coll.find(filter.clone(), None).await?;
+    let mut session_ids = vec![];
+    while cursor.advance().await? {
+        session_ids.push(cursor.deserialize_current()?.id);
+    }
+    let result = session_coll.delete_many(filter, None).await?;
+    let mut active_iot_sessions = ACTIVE_IOT_SESSIONS.lock().unwrap();
+    for session_id in session_ids {
+        active_iot_sessions.remove(&session_id);
+    }
+    Ok(result.deleted_count)
+}
+
+// Checks a web session and records that it has just been used
+pub async fn touch_session(mongoc: &mongodb::Client, session_id: &str) -> bool {
+    let Ok(now) = to_bson(&SystemTime::now()) else {
+        return false;
+    };
+    matches!(
+        get_session_coll(mongoc)
+            .update_one(active_filter(session_id), doc! { "$set": { "last_used_at": now } }, None)
+            .await,
+        Ok(result) if result.matched_count > 0
+    )
+}
+
+pub async fn is_iot_session_active(mongoc: &mongodb::Client, session_id: &str) -> bool {
+    if ACTIVE_IOT_SESSIONS.lock().unwrap().contains(session_id) {
+        return true;
+    }
+    let active = matches!(
+        get_session_coll(mongoc).find_one(active_filter(session_id), None).await,
+        Ok(Some(_))
+    );
+    if active {
+        ACTIVE_IOT_SESSIONS.lock().unwrap().insert(session_id.to_string());
+    }
+    active
+}
+
+pub async fn find_sessions(
+    mongoc: &mongodb::Client,
+    kind: SessionKind,
+    subject: &str,
+) -> mongodb::error::Result<Vec<Session>> {
+    let mut cursor = get_session_coll(mongoc)
+        .find(
+            doc! {
+                "kind": to_bson(&kind)?,
+                "subject": subject,
+                "expires_at.secs_since_epoch": { "$gt": secs_since_epoch(SystemTime::now()) },
+            },
+            None,
+        )
+        .await?;
+    let mut sessions = vec![];
+    while cursor.advance().await? {
+        sessions.push(cursor.deserialize_current()?);
+    }
+    Ok(sessions)
+}
+
+pub async fn purge_expired_sessions(mongoc: &mongodb::Client) -> mongodb::error::Result<u64> {
+    let result = get_session_coll(mongoc)
+        .delete_many(
+            doc! { "expires_at.secs_since_epoch": { "$lte": secs_since_epoch(SystemTime::now()) } },
+            None,
+        )
+        .await?;
+    Ok(result.deleted_count)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn forwarded_for_is_ignored_without_a_trusted_proxy() {
+        assert_eq!(client_ip(ip("203.0.113.7"), Some("198.51.100.1"), &[]), ip("203.0.113.7"));
+        assert_eq!(
+            client_ip(ip("203.0.113.7"), Some("198.51.100.1"), &[ip("10.0.0.2")]),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn the_right_most_untrusted_hop_is_the_client() {
+        let proxies = [ip("10.0.0.2"), ip("10.0.0.3")];
+        // The client prepended a spoofed address, the proxy appended the real one
+        assert_eq!(
+            client_ip(ip("10.0.0.2"), Some("198.51.100.1, 203.0.113.7"), &proxies),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            client_ip(ip("10.0.0.2"), Some("198.51.100.1, 203.0.113.7, 10.0.0.3"), &proxies),
+            ip("203.0.113.7")
+        );
+        assert_eq!(client_ip(ip("10.0.0.2"), Some("not-an-ip"), &proxies), ip("10.0.0.2"));
+        assert_eq!(client_ip(ip("10.0.0.2"), None, &proxies), ip("10.0.0.2"));
+    }
+}
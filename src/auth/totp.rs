@@ -0,0 +1,203 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use ring::{
+    aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN},
+    hmac,
+    rand::{SecureRandom, SystemRandom},
+};
+use serde::{Deserialize, Serialize};
+
+// RFC 6238 defaults, the only parameters every authenticator app supports
+pub const TOTP_DIGITS: u32 = 6;
+pub const TOTP_PERIOD_SECS: u64 = 30;
+const SECRET_LEN: usize = 20;
+// Codes of the previous and next periods are accepted too, for clock drift
+const ALLOWED_DRIFT_STEPS: u64 = 1;
+const BASE32_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+
+// Lets tests check codes at a fixed time
+pub trait Clock {
+    fn unix_secs(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn unix_secs(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default()
+    }
+}
+
+pub fn generate_secret() -> Option<Vec<u8>> {
+    let mut secret = vec![0u8; SECRET_LEN];
+    SystemRandom::new().fill(&mut secret).ok()?;
+    Some(secret)
+}
+
+pub fn totp_code(secret: &[u8], step: u64) -> u32 {
+    let key = hmac::Key::new(hmac::HMAC_SHA1_FOR_LEGACY_USE_ONLY, secret);
+    let digest = hmac::sign(&key, &step.to_be_bytes());
+    let digest = digest.as_ref();
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let truncated = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]);
+    (truncated & 0x7fff_ffff) % 10u32.pow(TOTP_DIGITS)
+}
+
+// Returns the time step the code belongs to, so that callers can refuse a
+// code that was already used
+pub fn verify_totp(secret: &[u8], code: &str, clock: &impl Clock) -> Option<u64> {
+    let code = code.trim();
+    if code.len() != TOTP_DIGITS as usize || !code.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let current_step = clock.unix_secs() / TOTP_PERIOD_SECS;
+    (current_step.saturating_sub(ALLOWED_DRIFT_STEPS)..=current_step + ALLOWED_DRIFT_STEPS)
+        .find(|step| totp_code(secret, *step) == code)
+}
+
+// Unpadded, as authenticator apps expect it
+pub fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8).div_ceil(5));
+    let mut buffer = 0u16;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u16;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+// Rendered as a QR code by the frontend for the authenticator app to scan
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &[u8]) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        percent_encode(issuer),
+        percent_encode(account),
+        base32_encode(secret),
+        percent_encode(issuer),
+        TOTP_DIGITS,
+        TOTP_PERIOD_SECS,
+    )
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct EncryptedSecret {
+    #[serde(with = "serde_bytes")]
+    pub nonce: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+// The secret is bound to the account, so that a ciphertext copied to another
+// account does not decrypt
+pub fn encrypt_secret(key: &[u8; 32], account: &str, secret: &[u8]) -> Option<EncryptedSecret> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).ok()?);
+    let mut nonce = [0u8; NONCE_LEN];
+    SystemRandom::new().fill(&mut nonce).ok()?;
+    let mut ciphertext = secret.to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(account.as_bytes()),
+        &mut ciphertext,
+    )
+    .ok()?;
+    Some(EncryptedSecret {
+        nonce: nonce.to_vec(),
+        ciphertext,
+    })
+}
+
+pub fn decrypt_secret(key: &[u8; 32], account: &str, encrypted: &EncryptedSecret) -> Option<Vec<u8>> {
+    let key = LessSafeKey::new(UnboundKey::new(&AES_256_GCM, key).ok()?);
+    let nonce = Nonce::try_assume_unique_for_key(&encrypted.nonce).ok()?;
+    let mut in_out = encrypted.ciphertext.clone();
+    let secret = key
+        .open_in_place(nonce, Aad::from(account.as_bytes()), &mut in_out)
+        .ok()?;
+    Some(secret.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn unix_secs(&self) -> u64 {
+            self.0
+        }
+    }
+
+    // Test vectors of RFC 6238, truncated to 6 digits
+    const RFC_SECRET: &[u8] = b"12345678901234567890";
+
+    #[test]
+    fn codes_match_the_rfc() {
+        assert_eq!(totp_code(RFC_SECRET, 59 / TOTP_PERIOD_SECS), 287082);
+        assert_eq!(totp_code(RFC_SECRET, 1111111109 / TOTP_PERIOD_SECS), 81804);
+        assert_eq!(totp_code(RFC_SECRET, 1234567890 / TOTP_PERIOD_SECS), 5924);
+        assert_eq!(totp_code(RFC_SECRET, 2000000000 / TOTP_PERIOD_SECS), 279037);
+    }
+
+    #[test]
+    fn codes_are_accepted_within_one_period_of_drift() {
+        let clock = FixedClock(1111111109);
+        let step = 1111111109 / TOTP_PERIOD_SECS;
+        assert_eq!(verify_totp(RFC_SECRET, "081804", &clock), Some(step));
+        let previous = format!("{:06}", totp_code(RFC_SECRET, step - 1));
+        assert_eq!(verify_totp(RFC_SECRET, &previous, &clock), Some(step - 1));
+        let too_old = format!("{:06}", totp_code(RFC_SECRET, step - 2));
+        assert_eq!(verify_totp(RFC_SECRET, &too_old, &clock), None);
+        assert_eq!(verify_totp(RFC_SECRET, "81804", &clock), None);
+        assert_eq!(verify_totp(RFC_SECRET, "abcdef", &clock), None);
+    }
+
+    #[test]
+    fn base32_matches_the_rfc() {
+        assert_eq!(base32_encode(b""), "");
+        assert_eq!(base32_encode(b"f"), "MY");
+        assert_eq!(base32_encode(b"foob"), "MZXW6YQ");
+        assert_eq!(base32_encode(b"foobar"), "MZXW6YTBOI");
+    }
+
+    #[test]
+    fn provisioning_uri_escapes_the_label() {
+        let uri = provisioning_uri("Tempus alert", "a@home.com", b"foobar");
+        assert_eq!(
+            uri,
+            "otpauth://totp/Tempus%20alert:a%40home.com?secret=MZXW6YTBOI&issuer=Tempus%20alert&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn secrets_only_decrypt_for_their_account() {
+        let key = [7u8; 32];
+        let encrypted = encrypt_secret(&key, "a@home.com", RFC_SECRET).unwrap();
+        assert_ne!(encrypted.ciphertext, RFC_SECRET);
+        assert_eq!(decrypt_secret(&key, "a@home.com", &encrypted).unwrap(), RFC_SECRET);
+        assert!(decrypt_secret(&key, "b@home.com", &encrypted).is_none());
+        assert!(decrypt_secret(&[8u8; 32], "a@home.com", &encrypted).is_none());
+    }
+}
@@ -0,0 +1,250 @@
+use std::time::SystemTime;
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{IndexOptions, ReplaceOptions},
+    Collection, IndexModel,
+};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    hash_opaque_token,
+    totp::{base32_encode, decrypt_secret, encrypt_secret, generate_secret, verify_totp, Clock, EncryptedSecret},
+};
+
+const RECOVERY_CODE_COUNT: usize = 10;
+// Encodes to 8 base32 characters
+const RECOVERY_CODE_BYTES: usize = 5;
+
+#[derive(Serialize, Deserialize)]
+pub struct TwoFactor {
+    pub email: String,
+    pub secret: EncryptedSecret,
+    // Only set once the user proved their app produces valid codes, until
+    // then logging in does not ask for a code
+    pub enabled: bool,
+    // Only hashes are kept, the codes are shown once
+    pub recovery_code_hashes: Vec<String>,
+    // Time step of the last accepted code, so that a code only works once
+    pub last_used_step: Option<i64>,
+    pub created_at: SystemTime,
+}
+
+pub fn get_two_factor_coll(mongoc: &mongodb::Client) -> Collection<TwoFactor> {
+    mongoc.default_database().unwrap().collection("two_factors")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_two_factor_coll(mongoc)
+        .create_indexes(
+            vec![IndexModel::builder()
+                .keys(doc! { "email": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build()],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Codes are compared without dashes, spaces or case
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+fn generate_recovery_codes() -> Option<Vec<String>> {
+    let rng = SystemRandom::new();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let mut bytes = [0u8; RECOVERY_CODE_BYTES];
+            rng.fill(&mut bytes).ok()?;
+            let code = base32_encode(&bytes).to_ascii_lowercase();
+            Some(format!("{}-{}", &code[..4], &code[4..]))
+        })
+        .collect()
+}
+
+fn hash_recovery_codes(codes: &[String]) -> Vec<String> {
+    codes
+        .iter()
+        .map(|code| hash_opaque_token(&normalize_recovery_code(code)))
+        .collect()
+}
+
+pub async fn find_two_factor(mongoc: &mongodb::Client, email: &str) -> mongodb::error::Result<Option<TwoFactor>> {
+    get_two_factor_coll(mongoc)
+        .find_one(doc! { "email": email }, None)
+        .await
+}
+
+pub async fn is_two_factor_enabled(mongoc: &mongodb::Client, email: &str) -> mongodb::error::Result<bool> {
+    Ok(find_two_factor(mongoc, email)
+        .await?
+        .is_some_and(|two_factor| two_factor.enabled))
+}
+
+// Replaces any enrollment that was never confirmed. Returns the plain secret
+// to provision the authenticator app with.
+pub async fn start_enrollment(
+    mongoc: &mongodb::Client,
+    key: &[u8; 32],
+    email: &str,
+) -> mongodb::error::Result<Option<Vec<u8>>> {
+    let Some(secret) = generate_secret() else {
+        return Ok(None);
+    };
+    let Some(encrypted) = encrypt_secret(key, email, &secret) else {
+        return Ok(None);
+    };
+    get_two_factor_coll(mongoc)
+        .replace_one(
+            doc! { "email": email, "enabled": false },
+            TwoFactor {
+                email: email.to_string(),
+                secret: encrypted,
+                enabled: false,
+                recovery_code_hashes: vec![],
+                last_used_step: None,
+                created_at: SystemTime::now(),
+            },
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    Ok(Some(secret))
+}
+
+// Accepts the code if its time step is newer than the last accepted one
+async fn consume_totp(
+    mongoc: &mongodb::Client,
+    key: &[u8; 32],
+    clock: &impl Clock,
+    two_factor: &TwoFactor,
+    code: &str,
+    enabled: bool,
+) -> mongodb::error::Result<bool> {
+    let Some(secret) = decrypt_secret(key, &two_factor.email, &two_factor.secret) else {
+        return Ok(false);
+    };
+    let Some(step) = verify_totp(&secret, code, clock) else {
+        return Ok(false);
+    };
+    let step = step as i64;
+    let result = get_two_factor_coll(mongoc)
+        .update_one(
+            doc! {
+                "email": two_factor.email.clone(),
+                "enabled": enabled,
+                "$or": [
+                    { "last_used_step": null },
+                    { "last_used_step": { "$lt": step } },
+                ],
+            },
+            doc! { "$set": { "last_used_step": step } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count > 0)
+}
+
+// Enables two-factor authentication once the user entered a valid code from
+// their app. Returns the recovery codes, which are never shown again.
+pub async fn confirm_enrollment(
+    mongoc: &mongodb::Client,
+    key: &[u8; 32],
+    clock: &impl Clock,
+    email: &str,
+    code: &str,
+) -> mongodb::error::Result<Option<Vec<String>>> {
+    let Some(two_factor) = find_two_factor(mongoc, email).await? else {
+        return Ok(None);
+    };
+    if two_factor.enabled || !consume_totp(mongoc, key, clock, &two_factor, code, false).await? {
+        return Ok(None);
+    }
+    let Some(recovery_codes) = generate_recovery_codes() else {
+        return Ok(None);
+    };
+    get_two_factor_coll(mongoc)
+        .update_one(
+            doc! { "email": email, "enabled": false },
+            doc! { "$set": {
+                "enabled": true,
+                "recovery_code_hashes": to_bson(&hash_recovery_codes(&recovery_codes))?,
+            } },
+            None,
+        )
+        .await?;
+    Ok(Some(recovery_codes))
+}
+
+// Checks a code of the authenticator app, or else a recovery code which is
+// then used up
+pub async fn verify_second_factor(
+    mongoc: &mongodb::Client,
+    key: &[u8; 32],
+    clock: &impl Clock,
+    email: &str,
+    code: &str,
+) -> mongodb::error::Result<bool> {
+    let Some(two_factor) = find_two_factor(mongoc, email).await?.filter(|two_factor| two_factor.enabled) else {
+        return Ok(false);
+    };
+    if consume_totp(mongoc, key, clock, &two_factor, code, true).await? {
+        return Ok(true);
+    }
+    let code_hash = hash_opaque_token(&normalize_recovery_code(code));
+    let result = get_two_factor_coll(mongoc)
+        .update_one(
+            doc! { "email": email, "enabled": true, "recovery_code_hashes": code_hash.clone() },
+            doc! { "$pull": { "recovery_code_hashes": code_hash } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count > 0)
+}
+
+// Invalidates the previous recovery codes
+pub async fn regenerate_recovery_codes(
+    mongoc: &mongodb::Client,
+    email: &str,
+) -> mongodb::error::Result<Option<Vec<String>>> {
+    let Some(recovery_codes) = generate_recovery_codes() else {
+        return Ok(None);
+    };
+    let result = get_two_factor_coll(mongoc)
+        .update_one(
+            doc! { "email": email, "enabled": true },
+            doc! { "$set": { "recovery_code_hashes": to_bson(&hash_recovery_codes(&recovery_codes))? } },
+            None,
+        )
+        .await?;
+    Ok((result.matched_count > 0).then_some(recovery_codes))
+}
+
+pub async fn disable_two_factor(mongoc: &mongodb::Client, email: &str) -> mongodb::error::Result<bool> {
+    let result = get_two_factor_coll(mongoc)
+        .delete_one(doc! { "email": email }, None)
+        .await?;
+    Ok(result.deleted_count > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recovery_codes_are_unique_and_forgiving() {
+        let codes = generate_recovery_codes().unwrap();
+        assert_eq!(codes.len(), RECOVERY_CODE_COUNT);
+        assert!(codes.iter().all(|code| code.len() == 9 && code.as_bytes()[4] == b'-'));
+        assert_ne!(codes[0], codes[1]);
+
+        let hashes = hash_recovery_codes(&codes);
+        let typed = codes[0].to_ascii_uppercase().replace('-', " ");
+        assert_eq!(hash_opaque_token(&normalize_recovery_code(&typed)), hashes[0]);
+    }
+}
@@ -0,0 +1,166 @@
+use std::time::SystemTime;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::backend_core::{
+    events::FeatureEvent,
+    features::{
+        devices_status_feature::models::ErrorSeverity,
+        fire_alert_feature::models::{FireStatus, SensorDataType},
+        remote_control_feature::models::{BuzzerCommand, LightCommand, RemoteCommand, SceneAction},
+    },
+};
+
+pub const DEFAULT_RULE_COOLDOWN_SECS: u64 = 300;
+
+fn default_cooldown_secs() -> u64 {
+    DEFAULT_RULE_COOLDOWN_SECS
+}
+
+// The kind of event a rule listens to. The tags match `FeatureEvent::kind`.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "event")]
+pub enum RuleTrigger {
+    // Any reading, or only those of one kind of sensor
+    #[serde(rename = "sensor_reading")]
+    SensorReading { sensor_type: Option<SensorDataType> },
+    #[serde(rename = "component_connected")]
+    ComponentConnected,
+    #[serde(rename = "component_disconnected")]
+    ComponentDisconnected,
+    #[serde(rename = "low_battery")]
+    LowBattery,
+    #[serde(rename = "device_error")]
+    DeviceError,
+}
+
+// Every condition of a rule must hold for it to fire. A condition that does
+// not apply to the event, such as `alert` on a disconnect, never holds.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum RuleCondition {
+    // The device is in this room
+    #[serde(rename = "room")]
+    Room { room_name: String },
+    #[serde(rename = "device")]
+    Device {
+        device_id: u32,
+        component: Option<u32>,
+    },
+    #[serde(rename = "alert")]
+    Alert { status: FireStatus },
+    // Compared to the sensor value, or to the battery level
+    #[serde(rename = "value_above")]
+    ValueAbove { value: f32 },
+    #[serde(rename = "value_below")]
+    ValueBelow { value: f32 },
+    #[serde(rename = "min_severity")]
+    MinSeverity { severity: ErrorSeverity },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum RuleAction {
+    #[serde(rename = "command")]
+    Command {
+        device_id: usize,
+        component_id: usize,
+        command: RemoteCommand,
+    },
+    #[serde(rename = "all_lights")]
+    AllLights {
+        room_name: Option<String>,
+        command: LightCommand,
+    },
+    #[serde(rename = "all_buzzers")]
+    AllBuzzers {
+        room_name: Option<String>,
+        command: BuzzerCommand,
+    },
+    #[serde(rename = "scene")]
+    Scene { scene_id: String },
+    // Without a message the event itself is described
+    #[serde(rename = "push")]
+    Push { message: Option<String> },
+    #[serde(rename = "email")]
+    Email {
+        subject: Option<String>,
+        body: Option<String>,
+    },
+}
+
+impl RuleAction {
+    // Remote control actions as they would appear in a scene
+    pub fn to_scene_action(&self) -> Option<SceneAction> {
+        match self {
+            RuleAction::Command {
+                device_id,
+                component_id,
+                command,
+            } => Some(SceneAction::Command {
+                device_id: *device_id,
+                component_id: *component_id,
+                command: *command,
+            }),
+            RuleAction::AllLights { room_name, command } => Some(SceneAction::AllLights {
+                room_name: room_name.clone(),
+                command: *command,
+            }),
+            RuleAction::AllBuzzers { room_name, command } => Some(SceneAction::AllBuzzers {
+                room_name: room_name.clone(),
+                command: *command,
+            }),
+            RuleAction::Scene { .. } | RuleAction::Push { .. } | RuleAction::Email { .. } => None,
+        }
+    }
+}
+
+// A user defined automation, one document per rule in `automation_rules`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Rule {
+    pub id: String,
+    pub owner_name: String,
+    pub name: String,
+    pub enabled: bool,
+    pub trigger: RuleTrigger,
+    #[serde(default)]
+    pub conditions: Vec<RuleCondition>,
+    pub actions: Vec<RuleAction>,
+    // A rule fires at most once per cooldown, so that a sensor which stays
+    // unsafe does not resend the same commands with every reading
+    #[serde(default = "default_cooldown_secs")]
+    pub cooldown_secs: u64,
+    pub last_fired_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum RuleActionResult {
+    #[serde(rename = "command")]
+    Command {
+        device_id: usize,
+        component_id: usize,
+        command: RemoteCommand,
+        status_code: usize,
+        message: String,
+        command_id: Option<String>,
+    },
+    #[serde(rename = "push")]
+    Push { success: bool },
+    #[serde(rename = "email")]
+    Email { success: bool },
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+// One document per time a rule fired in `automation_rule_runs`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct RuleRun {
+    pub rule_id: String,
+    pub owner_name: String,
+    pub event: FeatureEvent,
+    pub fired_at: SystemTime,
+    pub results: Vec<RuleActionResult>,
+}
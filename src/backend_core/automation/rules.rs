@@ -0,0 +1,351 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+
+use super::models::{Rule, RuleAction, RuleCondition, RuleRun, RuleTrigger};
+use crate::backend_core::{
+    events::{DeviceErrorReported, FeatureEvent, LowBatteryDetected, SensorReadingIngested},
+    features::remote_control_feature::{
+        models::{RemoteCommand, Scene},
+        scenes::{expand_scene, get_scene_coll},
+    },
+    models::Room,
+};
+
+pub fn get_rule_coll(mongoc: &mongodb::Client) -> Collection<Rule> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("automation_rules")
+}
+
+pub fn get_rule_run_coll(mongoc: &mongodb::Client) -> Collection<RuleRun> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("automation_rule_runs")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_rule_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "owner_name": 1, "trigger.event": 1, "enabled": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    get_rule_run_coll(mongoc)
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "owner_name": 1, "rule_id": 1, "fired_at.secs_since_epoch": -1 })
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+fn trigger_matches(trigger: &RuleTrigger, event: &FeatureEvent) -> bool {
+    match (trigger, event) {
+        (
+            RuleTrigger::SensorReading { sensor_type },
+            FeatureEvent::SensorReadingIngested(SensorReadingIngested {
+                sensor_type: actual, ..
+            }),
+        ) => sensor_type.is_none_or(|sensor_type| sensor_type == *actual),
+        (RuleTrigger::ComponentConnected, FeatureEvent::ComponentConnected(_))
+        | (RuleTrigger::ComponentDisconnected, FeatureEvent::ComponentDisconnected(_))
+        | (RuleTrigger::LowBattery, FeatureEvent::LowBatteryDetected(_))
+        | (RuleTrigger::DeviceError, FeatureEvent::DeviceErrorReported(_)) => true,
+        _ => false,
+    }
+}
+
+fn event_value(event: &FeatureEvent) -> Option<f32> {
+    match event {
+        FeatureEvent::SensorReadingIngested(SensorReadingIngested { value, .. }) => Some(*value),
+        FeatureEvent::LowBatteryDetected(LowBatteryDetected { battery, .. }) => Some(*battery as f32),
+        _ => None,
+    }
+}
+
+fn condition_holds(condition: &RuleCondition, event: &FeatureEvent, room_name: Option<&str>) -> bool {
+    match condition {
+        RuleCondition::Room { room_name: expected } => room_name == Some(expected.as_str()),
+        RuleCondition::Device {
+            device_id,
+            component,
+        } => {
+            event.device_id() == *device_id
+                && component.is_none_or(|component| event.component() == Some(component))
+        }
+        RuleCondition::Alert { status } => {
+            matches!(event, FeatureEvent::SensorReadingIngested(SensorReadingIngested { alert, .. }) if alert == status)
+        }
+        RuleCondition::ValueAbove { value } => event_value(event).is_some_and(|actual| actual > *value),
+        RuleCondition::ValueBelow { value } => event_value(event).is_some_and(|actual| actual < *value),
+        RuleCondition::MinSeverity { severity } => {
+            matches!(event, FeatureEvent::DeviceErrorReported(DeviceErrorReported { severity: actual, .. }) if actual >= severity)
+        }
+    }
+}
+
+// Whether the rule fires for the event, given the room of the device that
+// raised it. Ignores whether the rule is enabled or cooling down.
+pub fn rule_matches(rule: &Rule, event: &FeatureEvent, room_name: Option<&str>) -> bool {
+    trigger_matches(&rule.trigger, event)
+        && rule
+            .conditions
+            .iter()
+            .all(|condition| condition_holds(condition, event, room_name))
+}
+
+async fn find_room_of_device(
+    mongoc: &mongodb::Client,
+    owner_name: &str,
+    device_id: u32,
+) -> mongodb::error::Result<Option<String>> {
+    let room = mongoc
+        .default_database()
+        .unwrap()
+        .collection::<Room>("rooms")
+        .find_one(doc! { "owner_name": owner_name, "devices": device_id }, None)
+        .await?;
+    Ok(room.map(|room| room.name))
+}
+
+// The enabled rules of the owner of the event that it satisfies. The room of
+// the device is only looked up when a candidate rule depends on it.
+pub async fn find_matching_rules(
+    mongoc: &mongodb::Client,
+    event: &FeatureEvent,
+) -> mongodb::error::Result<Vec<Rule>> {
+    let mut cursor = get_rule_coll(mongoc)
+        .find(
+            doc! {
+                "owner_name": event.owner_name(),
+                "trigger.event": event.kind(),
+                "enabled": true,
+            },
+            None,
+        )
+        .await?;
+    let mut candidates = vec![];
+    while cursor.advance().await? {
+        match cursor.deserialize_current() {
+            Ok(rule) => candidates.push(rule),
+            Err(e) => eprintln!("Error deserializing rule: {}", e),
+        }
+    }
+
+    let needs_room = candidates.iter().any(|rule| {
+        rule.conditions
+            .iter()
+            .any(|condition| matches!(condition, RuleCondition::Room { .. }))
+    });
+    let room_name = if needs_room {
+        find_room_of_device(mongoc, event.owner_name(), event.device_id()).await?
+    } else {
+        None
+    };
+
+    Ok(candidates
+        .into_iter()
+        .filter(|rule| rule_matches(rule, event, room_name.as_deref()))
+        .collect())
+}
+
+// Marks the rule as fired unless it already fired within its cooldown.
+// Returns whether this caller claimed it.
+pub async fn claim_rule(
+    mongoc: &mongodb::Client,
+    rule: &Rule,
+    now: SystemTime,
+) -> mongodb::error::Result<bool> {
+    let now_secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    let result = get_rule_coll(mongoc)
+        .update_one(
+            doc! {
+                "id": rule.id.clone(),
+                "enabled": true,
+                "$or": [
+                    { "last_fired_at": null },
+                    { "last_fired_at.secs_since_epoch": { "$lte": now_secs - rule.cooldown_secs as i64 } },
+                ],
+            },
+            doc! { "$set": { "last_fired_at": to_bson(&now)? } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count == 1)
+}
+
+// Resolves the remote control actions of the rule, including those of the
+// scenes it activates, into individual commands. Also returns the ids of
+// scenes that no longer exist.
+pub async fn expand_rule_commands(
+    mongoc: &mongodb::Client,
+    rule: &Rule,
+) -> mongodb::error::Result<(Vec<(usize, usize, RemoteCommand)>, Vec<String>)> {
+    let mut actions = vec![];
+    let mut missing_scenes = vec![];
+    for action in &rule.actions {
+        if let RuleAction::Scene { scene_id } = action {
+            match get_scene_coll(mongoc)
+                .find_one(doc! { "id": scene_id, "owner_name": rule.owner_name.clone() }, None)
+                .await?
+            {
+                Some(scene) => actions.extend(scene.actions),
+                None => missing_scenes.push(scene_id.clone()),
+            }
+        } else if let Some(action) = action.to_scene_action() {
+            actions.push(action);
+        }
+    }
+
+    let scene = Scene {
+        id: rule.id.clone(),
+        owner_name: rule.owner_name.clone(),
+        name: rule.name.clone(),
+        actions,
+        created_at: rule.created_at,
+    };
+    Ok((expand_scene(mongoc, &scene).await?, missing_scenes))
+}
+
+pub async fn record_run(mongoc: &mongodb::Client, run: RuleRun) -> mongodb::error::Result<()> {
+    get_rule_run_coll(mongoc).insert_one(run, None).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend_core::{
+        automation::models::DEFAULT_RULE_COOLDOWN_SECS,
+        features::{
+            devices_status_feature::models::ErrorSeverity,
+            fire_alert_feature::models::{FireStatus, SensorDataType},
+            remote_control_feature::models::LightCommand,
+        },
+    };
+
+    fn rule(trigger: RuleTrigger, conditions: Vec<RuleCondition>) -> Rule {
+        Rule {
+            id: String::from("rule"),
+            owner_name: String::from("user@example.com"),
+            name: String::from("rule"),
+            enabled: true,
+            trigger,
+            conditions,
+            actions: vec![RuleAction::AllLights {
+                room_name: Some(String::from("kitchen")),
+                command: LightCommand::On,
+            }],
+            cooldown_secs: DEFAULT_RULE_COOLDOWN_SECS,
+            last_fired_at: None,
+            created_at: SystemTime::now(),
+        }
+    }
+
+    fn reading(sensor_type: SensorDataType, value: f32, alert: FireStatus) -> FeatureEvent {
+        FeatureEvent::SensorReadingIngested(SensorReadingIngested {
+            owner_name: String::from("user@example.com"),
+            device_id: 1,
+            component: 2,
+            sensor_type,
+            value,
+            alert,
+        })
+    }
+
+    #[test]
+    fn unsafe_smoke_in_the_kitchen() {
+        let rule = rule(
+            RuleTrigger::SensorReading {
+                sensor_type: Some(SensorDataType::Smoke),
+            },
+            vec![
+                RuleCondition::Room {
+                    room_name: String::from("kitchen"),
+                },
+                RuleCondition::Alert {
+                    status: FireStatus::UNSAFE,
+                },
+            ],
+        );
+
+        let unsafe_smoke = reading(SensorDataType::Smoke, 80.0, FireStatus::UNSAFE);
+        assert!(rule_matches(&rule, &unsafe_smoke, Some("kitchen")));
+        assert!(!rule_matches(&rule, &unsafe_smoke, Some("bedroom")));
+        assert!(!rule_matches(&rule, &unsafe_smoke, None));
+        assert!(!rule_matches(&rule, &reading(SensorDataType::Smoke, 10.0, FireStatus::SAFE), Some("kitchen")));
+        assert!(!rule_matches(&rule, &reading(SensorDataType::CO, 80.0, FireStatus::UNSAFE), Some("kitchen")));
+    }
+
+    #[test]
+    fn value_conditions_compare_readings_and_battery_levels() {
+        let hot = rule(
+            RuleTrigger::SensorReading { sensor_type: None },
+            vec![RuleCondition::ValueAbove { value: 57.0 }],
+        );
+        assert!(rule_matches(&hot, &reading(SensorDataType::Heat, 60.0, FireStatus::SAFE), None));
+        assert!(!rule_matches(&hot, &reading(SensorDataType::Heat, 57.0, FireStatus::SAFE), None));
+
+        let nearly_empty = rule(RuleTrigger::LowBattery, vec![RuleCondition::ValueBelow { value: 5.0 }]);
+        let battery = |battery| FeatureEvent::LowBatteryDetected(LowBatteryDetected {
+            owner_name: String::from("user@example.com"),
+            device_id: 1,
+            battery,
+            threshold: 20,
+        });
+        assert!(rule_matches(&nearly_empty, &battery(3), None));
+        assert!(!rule_matches(&nearly_empty, &battery(15), None));
+    }
+
+    #[test]
+    fn conditions_that_do_not_apply_never_hold() {
+        let error = FeatureEvent::DeviceErrorReported(DeviceErrorReported {
+            owner_name: String::from("user@example.com"),
+            device_id: 1,
+            component: 2,
+            code: Some(101),
+            severity: ErrorSeverity::Warning,
+        });
+        let critical_only = rule(
+            RuleTrigger::DeviceError,
+            vec![RuleCondition::MinSeverity {
+                severity: ErrorSeverity::Critical,
+            }],
+        );
+        assert!(!rule_matches(&critical_only, &error, None));
+
+        let unsafe_only = rule(
+            RuleTrigger::DeviceError,
+            vec![RuleCondition::Alert {
+                status: FireStatus::UNSAFE,
+            }],
+        );
+        assert!(!rule_matches(&unsafe_only, &error, None));
+
+        let on_component = rule(
+            RuleTrigger::DeviceError,
+            vec![RuleCondition::Device {
+                device_id: 1,
+                component: Some(2),
+            }],
+        );
+        assert!(rule_matches(&on_component, &error, None));
+    }
+}
@@ -0,0 +1,117 @@
+use std::marker::PhantomData;
+
+use once_cell::sync::Lazy;
+use tokio::sync::{
+    broadcast::{error::RecvError, Receiver, Sender},
+    Mutex,
+};
+
+use super::events::{BusEvent, FeatureEvent};
+
+pub type Publisher<T> = Sender<T>;
+pub type Subscriber<T> = Receiver<T>;
+
+#[derive(Clone)]
+pub enum UserEventKind {
+    JOIN = 0,
+    CANCEL = 1,
+}
+
+#[derive(Clone)]
+pub struct UserEvent {
+    pub kind: UserEventKind,
+    pub client_id: String,
+}
+
+static USER_CHANNEL: Lazy<Mutex<Sender<UserEvent>>> =
+    Lazy::new(|| Mutex::new(tokio::sync::broadcast::channel::<UserEvent>(100).0));
+
+pub async fn get_user_publisher() -> Publisher<UserEvent> {
+    let channel = USER_CHANNEL.lock().await;
+    channel.clone()
+}
+
+pub async fn get_user_subscriber() -> Subscriber<UserEvent> {
+    let channel = USER_CHANNEL.lock().await;
+    channel.subscribe()
+}
+
+// Publishing is synchronous so that ingestion helpers deep in the features can
+// emit events without holding a lock across an await.
+static FEATURE_CHANNEL: Lazy<Sender<FeatureEvent>> =
+    Lazy::new(|| tokio::sync::broadcast::channel::<FeatureEvent>(1024).0);
+
+// Fire and forget, events are simply dropped while nobody is subscribed.
+pub fn publish_event(event: impl Into<FeatureEvent>) {
+    let _ = FEATURE_CHANNEL.send(event.into());
+}
+
+// Every event of every feature
+pub fn subscribe_events() -> Subscriber<FeatureEvent> {
+    FEATURE_CHANNEL.subscribe()
+}
+
+pub fn subscribe<E: BusEvent>() -> EventSubscriber<E> {
+    EventSubscriber {
+        receiver: subscribe_events(),
+        event: PhantomData,
+    }
+}
+
+// Receives the events of a single type from the feature bus.
+pub struct EventSubscriber<E> {
+    receiver: Subscriber<FeatureEvent>,
+    event: PhantomData<E>,
+}
+
+impl<E: BusEvent> EventSubscriber<E> {
+    // Waits for the next event of this type. Events missed because the
+    // subscriber was too slow are skipped. Returns `None` once the bus is gone.
+    pub async fn recv(&mut self) -> Option<E> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) => {
+                    if let Some(event) = E::from_event(event) {
+                        return Some(event);
+                    }
+                }
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Event subscriber fell behind, skipped {skipped} events")
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend_core::events::{ComponentConnected, ComponentDisconnected};
+
+    #[tokio::test]
+    async fn typed_subscribers_only_receive_their_events() {
+        let owner_name = String::from("typed-subscriber@example.com");
+        let mut disconnects = subscribe::<ComponentDisconnected>();
+
+        publish_event(ComponentConnected {
+            owner_name: owner_name.clone(),
+            device_id: 1,
+            component: 2,
+        });
+        publish_event(ComponentDisconnected {
+            owner_name: owner_name.clone(),
+            device_id: 1,
+            component: 3,
+        });
+
+        // Other tests may publish on the same bus concurrently
+        loop {
+            let event = disconnects.recv().await.unwrap();
+            if event.owner_name == owner_name {
+                assert_eq!(event.component, 3);
+                break;
+            }
+        }
+    }
+}
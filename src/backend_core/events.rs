@@ -0,0 +1,257 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::features::{
+    devices_status_feature::models::ErrorSeverity,
+    fire_alert_feature::models::{FireStatus, IncidentAlert, IncidentKind, SensorDataType},
+    remote_control_feature::models::{CommandStatus, RemoteCommand},
+};
+
+// Events the features publish on the bus in `channels` as they ingest MQTT
+// messages. Other features, automation rules and live streams subscribe to
+// them instead of calling into the publishing feature.
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SensorReadingIngested {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+    pub sensor_type: SensorDataType,
+    pub value: f32,
+    pub alert: FireStatus,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ComponentConnected {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ComponentDisconnected {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct LowBatteryDetected {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub battery: u32,
+    pub threshold: u32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct DeviceErrorReported {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+    pub code: Option<u32>,
+    pub severity: ErrorSeverity,
+}
+
+// An incident was opened or received another unsafe reading
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct IncidentUpdated {
+    pub owner_name: String,
+    pub alert: IncidentAlert,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct IncidentResolved {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+    pub incident_kind: IncidentKind,
+    pub sensor_type: SensorDataType,
+    pub room_name: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CommandAcked {
+    pub owner_name: String,
+    pub command_id: String,
+    pub device_id: usize,
+    pub component_id: usize,
+    pub command: RemoteCommand,
+    pub status: CommandStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum FeatureEvent {
+    #[serde(rename = "sensor_reading")]
+    SensorReadingIngested(SensorReadingIngested),
+    #[serde(rename = "component_connected")]
+    ComponentConnected(ComponentConnected),
+    #[serde(rename = "component_disconnected")]
+    ComponentDisconnected(ComponentDisconnected),
+    #[serde(rename = "low_battery")]
+    LowBatteryDetected(LowBatteryDetected),
+    #[serde(rename = "device_error")]
+    DeviceErrorReported(DeviceErrorReported),
+    #[serde(rename = "incident_updated")]
+    IncidentUpdated(IncidentUpdated),
+    #[serde(rename = "incident_resolved")]
+    IncidentResolved(IncidentResolved),
+    #[serde(rename = "command_acked")]
+    CommandAcked(CommandAcked),
+}
+
+// An event type that can be subscribed to on its own
+pub trait BusEvent: Into<FeatureEvent> + Clone + Send + 'static {
+    fn from_event(event: FeatureEvent) -> Option<Self>;
+}
+
+macro_rules! bus_event {
+    ($($event:ident),* $(,)?) => {
+        $(
+            impl From<$event> for FeatureEvent {
+                fn from(event: $event) -> Self {
+                    FeatureEvent::$event(event)
+                }
+            }
+
+            impl BusEvent for $event {
+                fn from_event(event: FeatureEvent) -> Option<Self> {
+                    match event {
+                        FeatureEvent::$event(event) => Some(event),
+                        _ => None,
+                    }
+                }
+            }
+        )*
+    };
+}
+
+bus_event!(
+    SensorReadingIngested,
+    ComponentConnected,
+    ComponentDisconnected,
+    LowBatteryDetected,
+    DeviceErrorReported,
+    IncidentUpdated,
+    IncidentResolved,
+    CommandAcked,
+);
+
+impl FeatureEvent {
+    // Same as the serde tag, which is also how rule triggers are stored
+    pub fn kind(&self) -> &'static str {
+        match self {
+            FeatureEvent::SensorReadingIngested(_) => "sensor_reading",
+            FeatureEvent::ComponentConnected(_) => "component_connected",
+            FeatureEvent::ComponentDisconnected(_) => "component_disconnected",
+            FeatureEvent::LowBatteryDetected(_) => "low_battery",
+            FeatureEvent::DeviceErrorReported(_) => "device_error",
+            FeatureEvent::IncidentUpdated(_) => "incident_updated",
+            FeatureEvent::IncidentResolved(_) => "incident_resolved",
+            FeatureEvent::CommandAcked(_) => "command_acked",
+        }
+    }
+
+    pub fn owner_name(&self) -> &str {
+        match self {
+            FeatureEvent::SensorReadingIngested(SensorReadingIngested { owner_name, .. })
+            | FeatureEvent::ComponentConnected(ComponentConnected { owner_name, .. })
+            | FeatureEvent::ComponentDisconnected(ComponentDisconnected { owner_name, .. })
+            | FeatureEvent::LowBatteryDetected(LowBatteryDetected { owner_name, .. })
+            | FeatureEvent::DeviceErrorReported(DeviceErrorReported { owner_name, .. })
+            | FeatureEvent::IncidentUpdated(IncidentUpdated { owner_name, .. })
+            | FeatureEvent::IncidentResolved(IncidentResolved { owner_name, .. })
+            | FeatureEvent::CommandAcked(CommandAcked { owner_name, .. }) => owner_name,
+        }
+    }
+
+    pub fn device_id(&self) -> u32 {
+        match self {
+            FeatureEvent::SensorReadingIngested(SensorReadingIngested { device_id, .. })
+            | FeatureEvent::ComponentConnected(ComponentConnected { device_id, .. })
+            | FeatureEvent::ComponentDisconnected(ComponentDisconnected { device_id, .. })
+            | FeatureEvent::LowBatteryDetected(LowBatteryDetected { device_id, .. })
+            | FeatureEvent::DeviceErrorReported(DeviceErrorReported { device_id, .. })
+            | FeatureEvent::IncidentResolved(IncidentResolved { device_id, .. }) => *device_id,
+            FeatureEvent::IncidentUpdated(IncidentUpdated { alert, .. }) => alert.reading.id,
+            FeatureEvent::CommandAcked(CommandAcked { device_id, .. }) => *device_id as u32,
+        }
+    }
+
+    // Battery levels are reported per device, not per component
+    pub fn component(&self) -> Option<u32> {
+        match self {
+            FeatureEvent::SensorReadingIngested(SensorReadingIngested { component, .. })
+            | FeatureEvent::ComponentConnected(ComponentConnected { component, .. })
+            | FeatureEvent::ComponentDisconnected(ComponentDisconnected { component, .. })
+            | FeatureEvent::DeviceErrorReported(DeviceErrorReported { component, .. })
+            | FeatureEvent::IncidentResolved(IncidentResolved { component, .. }) => Some(*component),
+            FeatureEvent::IncidentUpdated(IncidentUpdated { alert, .. }) => Some(alert.reading.component),
+            FeatureEvent::CommandAcked(CommandAcked { component_id, .. }) => Some(*component_id as u32),
+            FeatureEvent::LowBatteryDetected(_) => None,
+        }
+    }
+
+    // Human readable summary used by push and email actions without a message
+    pub fn describe(&self) -> String {
+        match self {
+            FeatureEvent::SensorReadingIngested(SensorReadingIngested {
+                device_id,
+                component,
+                sensor_type,
+                value,
+                alert,
+                ..
+            }) => format!(
+                "{} sensor {component} of device {device_id} read {value} ({})",
+                serde_json::to_string(sensor_type).unwrap_or_default().trim_matches('"'),
+                match alert {
+                    FireStatus::SAFE => "safe",
+                    FireStatus::UNSAFE => "unsafe",
+                },
+            ),
+            FeatureEvent::ComponentConnected(ComponentConnected {
+                device_id,
+                component,
+                ..
+            }) => format!("Component {component} of device {device_id} connected"),
+            FeatureEvent::ComponentDisconnected(ComponentDisconnected {
+                device_id,
+                component,
+                ..
+            }) => format!("Component {component} of device {device_id} disconnected"),
+            FeatureEvent::LowBatteryDetected(LowBatteryDetected {
+                device_id,
+                battery,
+                threshold,
+                ..
+            }) => format!("Battery of device {device_id} is at {battery}%, below {threshold}%"),
+            FeatureEvent::DeviceErrorReported(DeviceErrorReported {
+                device_id,
+                component,
+                code,
+                severity,
+                ..
+            }) => format!(
+                "Component {component} of device {device_id} reported a {} error{}",
+                format!("{severity:?}").to_lowercase(),
+                code.map(|code| format!(" with code {code}")).unwrap_or_default(),
+            ),
+            FeatureEvent::IncidentUpdated(IncidentUpdated { alert, .. }) => format!(
+                "{:?} incident on device {} is {:?}",
+                alert.kind, alert.reading.id, alert.status,
+            ),
+            FeatureEvent::IncidentResolved(IncidentResolved {
+                device_id,
+                incident_kind,
+                ..
+            }) => format!("{incident_kind:?} incident on device {device_id} is resolved"),
+            FeatureEvent::CommandAcked(CommandAcked {
+                command_id,
+                status,
+                ..
+            }) => format!("Command {command_id} is {status:?}"),
+        }
+    }
+}
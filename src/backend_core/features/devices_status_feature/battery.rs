@@ -0,0 +1,176 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::doc,
+    options::{FindOneAndUpdateOptions, ReturnDocument},
+    Collection,
+};
+
+use super::models::{BatteryAlertState, BatterySettings, BatteryStatus, LowBatteryAlert};
+use crate::{
+    backend_core::{channels::publish_event, events::LowBatteryDetected},
+    push_notification::push_notification,
+};
+
+pub const DEFAULT_LOW_BATTERY_THRESHOLD: u32 = 20;
+
+// A trend is only fitted over readings spread across at least an hour, so
+// that a couple of noisy readings do not produce a wild estimate.
+const MIN_FORECAST_SAMPLES: usize = 2;
+const MIN_FORECAST_SPAN_SECS: f64 = 3600.0;
+const SECS_PER_DAY: f64 = 24.0 * 3600.0;
+
+pub async fn get_low_battery_threshold(mongoc: &mongodb::Client, owner_name: String) -> u32 {
+    let settings_coll: Collection<BatterySettings> = mongoc
+        .default_database()
+        .unwrap()
+        .collection("device_battery_settings");
+    settings_coll
+        .find_one(doc! { "owner_name": owner_name }, None)
+        .await
+        .ok()
+        .flatten()
+        .map(|settings| settings.low_battery_threshold)
+        .unwrap_or(DEFAULT_LOW_BATTERY_THRESHOLD)
+}
+
+// Notifies the owner the first time a device drops to or below its low
+// battery threshold. The alert re-arms once a reading is above the threshold
+// again, e.g. after the battery has been swapped.
+pub async fn check_battery_level(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    device_id: u32,
+    status: &BatteryStatus,
+) -> Option<()> {
+    let threshold = get_low_battery_threshold(mongoc, owner_name.clone()).await;
+    let low = status.battery <= threshold;
+
+    let alert_coll: Collection<BatteryAlertState> = mongoc
+        .default_database()
+        .unwrap()
+        .collection("device_battery_alerts");
+    let previous = alert_coll
+        .find_one_and_update(
+            doc! { "owner_name": owner_name.clone(), "device_id": device_id },
+            doc! { "$set": { "low": low } },
+            FindOneAndUpdateOptions::builder()
+                .upsert(true)
+                .return_document(ReturnDocument::Before)
+                .build(),
+        )
+        .await
+        .ok()?;
+
+    if low && !previous.is_some_and(|state| state.low) {
+        publish_event(LowBatteryDetected {
+            owner_name: owner_name.clone(),
+            device_id,
+            battery: status.battery,
+            threshold,
+        });
+        let alert = LowBatteryAlert {
+            device_id,
+            battery: status.battery,
+            threshold,
+            timestamp: status.timestamp,
+        };
+        if let Ok(message) = serde_json::to_string(&alert) {
+            push_notification(owner_name, message, &mut mongoc.clone()).await;
+        }
+    }
+    Some(())
+}
+
+// Fits a least-squares line through the readings and returns the drain in
+// percent per day together with the estimated days until the battery reaches
+// zero. The estimate is `None` when the battery is not draining.
+pub fn forecast_depletion(readings: &[BatteryStatus]) -> Option<(f32, Option<f32>)> {
+    if readings.len() < MIN_FORECAST_SAMPLES {
+        return None;
+    }
+    let points = readings
+        .iter()
+        .map(|reading| {
+            let secs = reading
+                .timestamp
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs_f64();
+            (secs, reading.battery as f64)
+        })
+        .collect::<Vec<_>>();
+    let first = points.iter().map(|(x, _)| *x).fold(f64::INFINITY, f64::min);
+    let last = points.iter().map(|(x, _)| *x).fold(f64::NEG_INFINITY, f64::max);
+    if last - first < MIN_FORECAST_SPAN_SECS {
+        return None;
+    }
+
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| (x - first) / SECS_PER_DAY).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+    let covariance = points
+        .iter()
+        .map(|(x, y)| ((x - first) / SECS_PER_DAY - mean_x) * (y - mean_y))
+        .sum::<f64>();
+    let variance = points
+        .iter()
+        .map(|(x, _)| ((x - first) / SECS_PER_DAY - mean_x).powi(2))
+        .sum::<f64>();
+    if variance == 0.0 {
+        return None;
+    }
+    let slope = covariance / variance;
+    let drain_per_day = -slope;
+    if drain_per_day <= 0.0 {
+        return Some((drain_per_day as f32, None));
+    }
+
+    // Count from now rather than from the last reading, so a device that has
+    // gone quiet does not keep reporting the same number of days left
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64()
+        .max(last);
+    let level_now = mean_y + slope * ((now - first) / SECS_PER_DAY - mean_x);
+    Some((drain_per_day as f32, Some((level_now.max(0.0) / drain_per_day) as f32)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn readings(levels: &[(u64, u32)]) -> Vec<BatteryStatus> {
+        let start = SystemTime::now() - Duration::from_secs(levels.last().unwrap().0 * 3600);
+        levels
+            .iter()
+            .map(|(hours, battery)| BatteryStatus {
+                battery: *battery,
+                timestamp: start + Duration::from_secs(hours * 3600),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn forecasts_days_until_empty_for_a_steady_drain() {
+        // 2% per day, 40% left
+        let (drain, days) = forecast_depletion(&readings(&[(0, 44), (24, 42), (48, 40)])).unwrap();
+        assert!((drain - 2.0).abs() < 0.01);
+        assert!((days.unwrap() - 20.0).abs() < 0.1);
+    }
+
+    #[test]
+    fn does_not_forecast_a_battery_that_is_not_draining() {
+        let (_, days) = forecast_depletion(&readings(&[(0, 80), (24, 80), (48, 81)])).unwrap();
+        assert!(days.is_none());
+    }
+
+    #[test]
+    fn needs_readings_spread_over_time() {
+        assert!(forecast_depletion(&readings(&[(0, 50)])).is_none());
+        assert!(forecast_depletion(&readings(&[(0, 50), (0, 49)])).is_none());
+    }
+}
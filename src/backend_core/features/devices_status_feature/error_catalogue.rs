@@ -0,0 +1,241 @@
+use mongodb::{bson::doc, Collection};
+
+use super::{
+    iot::mqtt_messages::ComponentType,
+    models::{DecodedDeviceError, Device, DeviceError, ErrorSeverity},
+};
+use crate::{
+    backend_core::{channels::publish_event, events::DeviceErrorReported},
+    push_notification::push_notification,
+};
+
+pub struct CatalogueEntry {
+    pub severity: ErrorSeverity,
+    pub description: &'static str,
+    pub remediation: &'static str,
+}
+
+const fn entry(
+    severity: ErrorSeverity,
+    description: &'static str,
+    remediation: &'static str,
+) -> CatalogueEntry {
+    CatalogueEntry {
+        severity,
+        description,
+        remediation,
+    }
+}
+
+// Reported when a component raises an error without a code, or with a code
+// the catalogue does not know for its kind
+const UNKNOWN_ERROR: CatalogueEntry = entry(
+    ErrorSeverity::Warning,
+    "Unrecognised error reported by the component",
+    "Check the device manual for this error and contact support if it persists",
+);
+
+// Codes below 100 mean the same thing on every component, codes from 100 up
+// are specific to the kind of component.
+fn lookup_common(code: u32) -> Option<CatalogueEntry> {
+    Some(match code {
+        1 => entry(
+            ErrorSeverity::Critical,
+            "Self-test failed",
+            "Press the test button; replace the component if the self-test keeps failing",
+        ),
+        2 => entry(
+            ErrorSeverity::Critical,
+            "Sensor element not responding",
+            "Reseat the component on its base and check its wiring",
+        ),
+        3 => entry(
+            ErrorSeverity::Warning,
+            "Calibration drift detected",
+            "Recalibrate the component or schedule a service visit",
+        ),
+        4 => entry(
+            ErrorSeverity::Warning,
+            "Firmware checksum mismatch",
+            "Reflash the gateway firmware",
+        ),
+        5 => entry(
+            ErrorSeverity::Warning,
+            "Tamper detected",
+            "Check that the component is still mounted and has not been covered",
+        ),
+        _ => return None,
+    })
+}
+
+fn lookup_kind(kind: &ComponentType, code: u32) -> Option<CatalogueEntry> {
+    Some(match (kind, code) {
+        (ComponentType::Smoke, 100) => entry(
+            ErrorSeverity::Warning,
+            "Smoke chamber contaminated by dust",
+            "Vacuum the chamber vents; replace the detector if the warning returns",
+        ),
+        (ComponentType::Smoke, 101) => entry(
+            ErrorSeverity::Critical,
+            "Optical chamber obstructed",
+            "Remove any cover or paint from the vents, then run a self-test",
+        ),
+        (ComponentType::Smoke, 102) => entry(
+            ErrorSeverity::Critical,
+            "Smoke detector reached end of life",
+            "Replace the smoke detector",
+        ),
+        (ComponentType::Heat, 100) => entry(
+            ErrorSeverity::Critical,
+            "Thermistor open circuit",
+            "Replace the heat detector",
+        ),
+        (ComponentType::Heat, 101) => entry(
+            ErrorSeverity::Critical,
+            "Thermistor short circuit",
+            "Replace the heat detector",
+        ),
+        (ComponentType::CO, 100) => entry(
+            ErrorSeverity::Critical,
+            "Electrochemical cell expired",
+            "Replace the CO sensor",
+        ),
+        (ComponentType::CO, 101) => entry(
+            ErrorSeverity::Warning,
+            "Electrochemical cell near end of life",
+            "Plan to replace the CO sensor within the next month",
+        ),
+        (ComponentType::LPG, 100) => entry(
+            ErrorSeverity::Critical,
+            "Gas sensor heater failure",
+            "Replace the LPG sensor",
+        ),
+        (ComponentType::LPG, 101) => entry(
+            ErrorSeverity::Info,
+            "Gas sensor warming up",
+            "No action needed, readings stabilise after a few minutes",
+        ),
+        (ComponentType::Fire, 100) => entry(
+            ErrorSeverity::Warning,
+            "Flame sensor lens obstructed",
+            "Clean the lens with a dry cloth",
+        ),
+        (ComponentType::Fire, 101) => entry(
+            ErrorSeverity::Critical,
+            "Infrared flame sensor fault",
+            "Replace the flame sensor",
+        ),
+        (ComponentType::FireButton, 100) => entry(
+            ErrorSeverity::Warning,
+            "Call point stuck in the pressed position",
+            "Reset the call point with its key",
+        ),
+        (ComponentType::GeneralLight | ComponentType::FireLight, 100) => entry(
+            ErrorSeverity::Warning,
+            "LED driver fault",
+            "Replace the light",
+        ),
+        (ComponentType::GeneralBuzzer | ComponentType::FireBuzzer, 100) => entry(
+            ErrorSeverity::Critical,
+            "Sounder circuit open",
+            "Check the buzzer wiring; replace the buzzer if it stays silent on test",
+        ),
+        _ => return None,
+    })
+}
+
+pub fn lookup(kind: Option<&ComponentType>, code: Option<u32>) -> CatalogueEntry {
+    code.and_then(|code| lookup_common(code).or_else(|| lookup_kind(kind?, code)))
+        .unwrap_or(UNKNOWN_ERROR)
+}
+
+pub fn decode_error(error: DeviceError, kind: Option<ComponentType>) -> DecodedDeviceError {
+    let entry = lookup(kind.as_ref(), error.code);
+    DecodedDeviceError {
+        id: error.id,
+        component: error.component,
+        kind,
+        code: error.code,
+        detail: error.detail,
+        timestamp: error.timestamp,
+        severity: entry.severity,
+        description: entry.description.to_string(),
+        remediation: entry.remediation.to_string(),
+    }
+}
+
+pub fn component_kind(device: &Device, component: u32) -> Option<ComponentType> {
+    device
+        .components
+        .iter()
+        .find(|candidate| candidate.id == component)
+        .map(|candidate| candidate.kind.clone())
+}
+
+// Decodes a freshly reported error, hands it to automation rules and pushes it
+// to the owner when critical.
+pub async fn notify_if_critical(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    error: DeviceError,
+) -> Option<()> {
+    let device_coll: Collection<Device> = mongoc.default_database().unwrap().collection("devices");
+    let kind = device_coll
+        .find_one(doc! { "id": error.id, "owner_name": owner_name.clone() }, None)
+        .await
+        .ok()?
+        .and_then(|device| component_kind(&device, error.component));
+
+    let decoded = decode_error(error, kind);
+    publish_event(DeviceErrorReported {
+        owner_name: owner_name.clone(),
+        device_id: decoded.id,
+        component: decoded.component,
+        code: decoded.code,
+        severity: decoded.severity,
+    });
+    if decoded.severity == ErrorSeverity::Critical {
+        let message = serde_json::to_string(&decoded).ok()?;
+        push_notification(owner_name, message, &mut mongoc.clone()).await;
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn common_codes_apply_to_every_kind() {
+        assert_eq!(
+            lookup(Some(&ComponentType::GeneralLight), Some(1)).severity,
+            ErrorSeverity::Critical
+        );
+        assert_eq!(lookup(None, Some(3)).severity, ErrorSeverity::Warning);
+    }
+
+    #[test]
+    fn kind_specific_codes_depend_on_the_component() {
+        assert_eq!(
+            lookup(Some(&ComponentType::LPG), Some(101)).severity,
+            ErrorSeverity::Info
+        );
+        assert_eq!(
+            lookup(Some(&ComponentType::Smoke), Some(101)).severity,
+            ErrorSeverity::Critical
+        );
+    }
+
+    #[test]
+    fn unknown_codes_fall_back_to_a_warning() {
+        for (kind, code) in [
+            (Some(&ComponentType::Heat), Some(999)),
+            (None, Some(100)),
+            (Some(&ComponentType::Smoke), None),
+        ] {
+            let entry = lookup(kind, code);
+            assert_eq!(entry.severity, ErrorSeverity::Warning);
+            assert_eq!(entry.description, UNKNOWN_ERROR.description);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOneAndUpdateOptions, ReturnDocument, UpdateOptions},
+    Collection,
+};
+
+use super::models::{ComponentHeartbeat, ComponentLog, ComponentOfflineAlert, ComponentStatus};
+use crate::{
+    backend_core::{
+        channels::publish_event,
+        events::{ComponentConnected, ComponentDisconnected},
+    },
+    push_notification::push_notification,
+};
+
+fn get_heartbeat_coll(mongoc: &mongodb::Client) -> Collection<ComponentHeartbeat> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("device_heartbeats")
+}
+
+pub async fn log_component_status(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    device_id: u32,
+    component: u32,
+    status: ComponentStatus,
+) -> Option<()> {
+    let component_log_coll: Collection<ComponentLog> = mongoc
+        .default_database()
+        .unwrap()
+        .collection("device_component_logs");
+    component_log_coll
+        .insert_one(
+            ComponentLog {
+                owner_name,
+                device_id,
+                component,
+                timestamp: status.timestamp(),
+                status,
+            },
+            None,
+        )
+        .await
+        .ok()?;
+    Some(())
+}
+
+// Used by explicit connect and disconnect messages, which log their own status.
+pub async fn set_component_online(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    device_id: u32,
+    component: u32,
+    online: bool,
+) -> Option<()> {
+    get_heartbeat_coll(mongoc)
+        .update_one(
+            doc! { "owner_name": owner_name, "device_id": device_id, "component": component },
+            doc! { "$set": { "last_seen": to_bson(&SystemTime::now()).ok()?, "online": online } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await
+        .ok()?;
+    Some(())
+}
+
+// Records traffic from a component, or from every known component of the
+// device when the message is not about a specific one. A component that the
+// watchdog had marked offline gets a synthetic `Connect` back. Returns whether
+// any component came back online.
+pub async fn record_heartbeat(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    device_id: u32,
+    component: Option<u32>,
+) -> Option<bool> {
+    let heartbeat_coll = get_heartbeat_coll(mongoc);
+    let now = SystemTime::now();
+
+    let revived = match component {
+        Some(component) => {
+            let options = FindOneAndUpdateOptions::builder()
+                .upsert(true)
+                .return_document(ReturnDocument::Before)
+                .build();
+            heartbeat_coll
+                .find_one_and_update(
+                    doc! { "owner_name": owner_name.clone(), "device_id": device_id, "component": component },
+                    doc! { "$set": { "last_seen": to_bson(&now).ok()?, "online": true } },
+                    options,
+                )
+                .await
+                .ok()?
+                .filter(|heartbeat| !heartbeat.online)
+                .map(|heartbeat| vec![heartbeat.component])
+                .unwrap_or_default()
+        }
+        None => {
+            let filter = doc! { "owner_name": owner_name.clone(), "device_id": device_id };
+            let mut offline_filter = filter.clone();
+            offline_filter.insert("online", false);
+
+            let mut revived = vec![];
+            let mut cursor = heartbeat_coll.find(offline_filter, None).await.ok()?;
+            while let Ok(true) = cursor.advance().await {
+                if let Ok(heartbeat) = cursor.deserialize_current() {
+                    revived.push(heartbeat.component);
+                }
+            }
+            heartbeat_coll
+                .update_many(
+                    filter,
+                    doc! { "$set": { "last_seen": to_bson(&now).ok()?, "online": true } },
+                    None,
+                )
+                .await
+                .ok()?;
+            revived
+        }
+    };
+
+    let any_revived = !revived.is_empty();
+    for component in revived {
+        log_component_status(
+            mongoc,
+            owner_name.clone(),
+            device_id,
+            component,
+            ComponentStatus::Connect { timestamp: now },
+        )
+        .await?;
+        publish_event(ComponentConnected {
+            owner_name: owner_name.clone(),
+            device_id,
+            component,
+        });
+    }
+    Some(any_revived)
+}
+
+// Marks every component silent for longer than `silence` as offline, appends
+// a synthetic `Disconnect` to its log and notifies the owner. Returns how many
+// components went offline.
+pub async fn disconnect_silent_components(
+    mongoc: &mongodb::Client,
+    silence: Duration,
+) -> mongodb::error::Result<u64> {
+    let heartbeat_coll = get_heartbeat_coll(mongoc);
+    let cutoff = SystemTime::now()
+        .checked_sub(silence)
+        .unwrap_or(UNIX_EPOCH)
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+
+    let mut silent = vec![];
+    let mut cursor = heartbeat_coll
+        .find(
+            doc! { "online": true, "last_seen.secs_since_epoch": { "$lt": cutoff } },
+            None,
+        )
+        .await?;
+    while cursor.advance().await? {
+        match cursor.deserialize_current() {
+            Ok(heartbeat) => silent.push(heartbeat),
+            Err(e) => eprintln!("Error deserializing heartbeat: {}", e),
+        }
+    }
+
+    let mut disconnected = 0;
+    for heartbeat in silent {
+        // Skip components that reported in between the scan and this update
+        let result = heartbeat_coll
+            .update_one(
+                doc! {
+                    "owner_name": heartbeat.owner_name.clone(),
+                    "device_id": heartbeat.device_id,
+                    "component": heartbeat.component,
+                    "online": true,
+                    "last_seen.secs_since_epoch": { "$lt": cutoff },
+                },
+                doc! { "$set": { "online": false } },
+                None,
+            )
+            .await?;
+        if result.modified_count == 0 {
+            continue;
+        }
+        disconnected += 1;
+
+        log_component_status(
+            mongoc,
+            heartbeat.owner_name.clone(),
+            heartbeat.device_id,
+            heartbeat.component,
+            ComponentStatus::Disconnect {
+                timestamp: SystemTime::now(),
+            },
+        )
+        .await;
+
+        publish_event(ComponentDisconnected {
+            owner_name: heartbeat.owner_name.clone(),
+            device_id: heartbeat.device_id,
+            component: heartbeat.component,
+        });
+
+        let alert = ComponentOfflineAlert {
+            device_id: heartbeat.device_id,
+            component: heartbeat.component,
+            last_seen: heartbeat.last_seen,
+        };
+        if let Ok(message) = serde_json::to_string(&alert) {
+            push_notification(heartbeat.owner_name, message, &mut mongoc.clone()).await;
+        }
+    }
+    Ok(disconnected)
+}
@@ -17,13 +17,20 @@ use super::mqtt_messages::{
     ReadDeviceErrorData,
 };
 use crate::{
-    auth::get_email_from_client_token,
+    auth::get_email_from_topic_token,
     backend_core::{
+        events::{ComponentConnected, ComponentDisconnected},
         features::{
             devices_status_feature::{
-                models::{BatteryStatus, Component, ComponentStatus, DeviceError},
+                battery::check_battery_level,
+                error_catalogue::notify_if_critical,
+                heartbeat::{log_component_status, record_heartbeat, set_component_online},
+                models::{
+                    BatteryLog, BatteryStatus, ComponentStatus, DeviceError, DeviceErrorLog,
+                },
                 web::WebDeviceStatusFeature,
             },
+            remote_control_feature::commands::redeliver_commands,
             IotFeature, WebFeature,
         },
         utils::non_primitive_cast,
@@ -39,7 +46,26 @@ pub struct IotDeviceStatusFeature {
     jwt_key: String,
 }
 
-impl IotDeviceStatusFeature {}
+impl IotDeviceStatusFeature {
+    async fn device_exists(&self, owner_name: String, device_id: u32) -> mongodb::error::Result<bool> {
+        let device_coll: Collection<Document> =
+            self.mongoc.default_database().unwrap().collection("devices");
+        Ok(device_coll
+            .find_one(doc! { "id": device_id, "owner_name": owner_name }, None)
+            .await?
+            .is_some())
+    }
+
+    // Replays remote control commands the gateway may have missed while it
+    // was offline
+    async fn redeliver_commands(&self, owner_name: String) {
+        match redeliver_commands(&self.mongoc, self.mqttc.clone(), owner_name.clone()).await {
+            Ok(0) => {}
+            Ok(count) => println!("Redelivered {count} queued commands to user '{owner_name}'"),
+            Err(e) => eprintln!("Failed to redeliver queued commands to user '{}': {}", owner_name, e),
+        }
+    }
+}
 
 #[async_trait]
 impl IotFeature for IotDeviceStatusFeature {
@@ -94,7 +120,7 @@ impl IotFeature for IotDeviceStatusFeature {
     async fn process_next_mqtt_message(&mut self) {
         let mut mongoc = self.get_mongoc();
         let mut mqtt_event_loop = self.mqtt_event_loop.lock().await;
-        if let Ok(Event::Incoming(Incoming::Publish(Publish { payload, .. }))) =
+        if let Ok(Event::Incoming(Incoming::Publish(Publish { topic, payload, .. }))) =
             mqtt_event_loop.poll().await
         {
             if let Some(message) = String::from_utf8(payload.to_vec())
@@ -106,39 +132,76 @@ impl IotFeature for IotDeviceStatusFeature {
                 match message {
                     DeviceStatusMQTTMessage::ReadBattery { token, data } => {
                         if let Some(username) =
-                            get_email_from_client_token(self.jwt_key.as_str(), token, &mut mongoc)
+                            get_email_from_topic_token(self.jwt_key.as_str(), &topic, token, &mut mongoc)
                                 .await
                         {
-                            let device_coll: Collection<Document> =
-                                mongoc.default_database().unwrap().collection("devices");
+                            let battery_log_coll: Collection<BatteryLog> =
+                                mongoc.default_database().unwrap().collection("device_battery_logs");
+                            let mut reconnected = false;
                             for ReadBatteryData { id, value: battery } in data {
-                                if let Err(_) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone() }, doc! { "$push": { "battery_logs": to_bson(&BatteryStatus { battery, timestamp: SystemTime::now() }).unwrap() } }, None).await {
-                                    eprint!("Failed to process read battery data");
+                                match self.device_exists(username.clone(), id).await {
+                                    Ok(true) => {
+                                        reconnected |= record_heartbeat(&mongoc, username.clone(), id, None).await.unwrap_or(false);
+                                        let status = BatteryStatus { battery, timestamp: SystemTime::now() };
+                                        if check_battery_level(&mongoc, username.clone(), id, &status).await.is_none() {
+                                            eprintln!("Failed to check battery level of device '{}'", id);
+                                        }
+                                        if let Err(_) = battery_log_coll.insert_one(BatteryLog { owner_name: username.clone(), device_id: id, status }, None).await {
+                                            eprint!("Failed to process read battery data");
+                                        }
+                                    }
+                                    Ok(false) => {
+                                        eprintln!("Device '{}' did not exist for user '{}'", id, username.clone());
+                                    }
+                                    Err(_) => {
+                                        eprint!("Failed to process read battery data");
+                                    }
                                 }
                             }
+                            if reconnected {
+                                self.redeliver_commands(username).await;
+                            }
                         } else {
                             eprintln!("Invalid token");
                         }
                     }
                     DeviceStatusMQTTMessage::ReadDeviceError { token, data } => {
                         if let Some(username) =
-                            get_email_from_client_token(self.jwt_key.as_str(), token, &mut mongoc)
+                            get_email_from_topic_token(self.jwt_key.as_str(), &topic, token, &mut mongoc)
                                 .await
                         {
-                            let device_coll: Collection<Document> =
-                                mongoc.default_database().unwrap().collection("devices");
-                            for ReadDeviceErrorData { id, component } in data {
-                                if let Err(_) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone() }, doc! { "$push": { "error_logs": to_bson(&DeviceError { id, component, timestamp: SystemTime::now() }).unwrap() } }, None).await {
-                                    eprint!("Failed to process read device error data");
+                            let error_log_coll: Collection<DeviceErrorLog> =
+                                mongoc.default_database().unwrap().collection("device_error_logs");
+                            let mut reconnected = false;
+                            for ReadDeviceErrorData { id, component, code, detail } in data {
+                                match self.device_exists(username.clone(), id).await {
+                                    Ok(true) => {
+                                        reconnected |= record_heartbeat(&mongoc, username.clone(), id, Some(component)).await.unwrap_or(false);
+                                        let error = DeviceError { id, component, timestamp: SystemTime::now(), code, detail };
+                                        if let Err(_) = error_log_coll.insert_one(DeviceErrorLog { owner_name: username.clone(), error: error.clone() }, None).await {
+                                            eprint!("Failed to process read device error data");
+                                        } else if notify_if_critical(&mongoc, username.clone(), error).await.is_none() {
+                                            eprintln!("Failed to decode error of device '{}'", id);
+                                        }
+                                    }
+                                    Ok(false) => {
+                                        eprintln!("Device '{}' did not exist for user '{}'", id, username.clone());
+                                    }
+                                    Err(_) => {
+                                        eprint!("Failed to process read device error data");
+                                    }
                                 }
                             }
+                            if reconnected {
+                                self.redeliver_commands(username).await;
+                            }
                         } else {
                             eprintln!("Invalid token");
                         }
                     }
                     DeviceStatusMQTTMessage::ConnectDevice { token, data } => {
                         if let Some(username) =
-                            get_email_from_client_token(self.jwt_key.as_str(), token, &mut mongoc)
+                            get_email_from_topic_token(self.jwt_key.as_str(), &topic, token, &mut mongoc)
                                 .await
                         {
                             let device_coll: Collection<Document> =
@@ -149,36 +212,40 @@ impl IotFeature for IotDeviceStatusFeature {
                                 kind,
                             } in data
                             {
-                                match device_coll.find_one(doc! { "id": id, "owner_name": username.clone() }, None).await {
-                                    Ok(Some(_)) => {
-                                        if let Ok(None) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone(), "components": { "$elemMatch": { "id": component } } }, doc! { "$push": { "components.$.logs": to_bson(&ComponentStatus::Connect { timestamp: SystemTime::now() }).unwrap() } }, None).await {
-                                            if let Err(_) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone() }, doc! { "$push": { "components": to_bson(&Component { id: component, kind, logs: vec![ComponentStatus::Connect { timestamp: SystemTime::now() }]  }).unwrap() } }, None).await {
+                                match self.device_exists(username.clone(), id).await {
+                                    Ok(exists) => {
+                                        if !exists {
+                                            if let Err(_) = device_coll.insert_one(doc! { "id": id, "owner_name": username.clone(), "components": [] }, None).await {
                                                 eprintln!("Failed to process connect device data");
+                                                continue;
                                             }
                                         }
-                                    }
-                                    Ok(None) => {
-                                        if let Err(_) = device_coll.insert_one(doc! { "id": id, "owner_name": username.clone(), "battery_logs": to_bson(&vec![] as &Vec<BatteryStatus>).unwrap(), "error_logs": to_bson(&vec![] as &Vec<DeviceError>).unwrap(), "components": to_bson(&vec![] as &Vec<Component>).unwrap() }, None).await {
-                                            eprintln!("Failed to process connect device data");
-                                        } 
-                                        if let Ok(None) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone(), "components": { "$elemMatch": { "id": component } } }, doc! { "$push": { "components.$.logs": to_bson(&ComponentStatus::Connect { timestamp: SystemTime::now() }).unwrap() } }, None).await {
-                                            if let Err(_) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone() }, doc! { "$push": { "components": to_bson(&Component { id: component, kind, logs: vec![ComponentStatus::Connect { timestamp: SystemTime::now() }]  }).unwrap() } }, None).await {
+                                        if let Ok(None) = device_coll.find_one(doc! { "id": id, "owner_name": username.clone(), "components.id": component }, None).await {
+                                            if let Err(_) = device_coll.update_one(doc! { "id": id, "owner_name": username.clone() }, doc! { "$push": { "components": { "id": component, "kind": to_bson(&kind).unwrap() } } }, None).await {
                                                 eprintln!("Failed to process connect device data");
+                                                continue;
                                             }
                                         }
+                                        if log_component_status(&mongoc, username.clone(), id, component, ComponentStatus::Connect { timestamp: SystemTime::now() }).await.is_none()
+                                            || set_component_online(&mongoc, username.clone(), id, component, true).await.is_none() {
+                                            eprintln!("Failed to process connect device data");
+                                        } else {
+                                            self.publish_event(ComponentConnected { owner_name: username.clone(), device_id: id, component });
+                                        }
                                     }
                                     Err(_) => {
                                         eprintln!("Unexpected error while finding devices with id {id}");
                                     }
                                 };
                             }
+                            self.redeliver_commands(username).await;
                         } else {
                             eprintln!("Invalid token");
                         }
                     }
                     DeviceStatusMQTTMessage::DisconnectDevice { token, data } => {
                         if let Some(username) =
-                            get_email_from_client_token(self.jwt_key.as_str(), token, &mut mongoc)
+                            get_email_from_topic_token(self.jwt_key.as_str(), &topic, token, &mut mongoc)
                                 .await
                         {
                             let device_coll: Collection<Document> =
@@ -192,10 +259,15 @@ impl IotFeature for IotDeviceStatusFeature {
                                     .await
                                 {
                                     Ok(Some(_)) => {
-                                        if let Ok(None) = device_coll.find_one_and_update(doc! { "id": id, "owner_name": username.clone(), "components": { "$elemMatch": { "id": component } } }, doc! { "$push": { "components.$.logs": to_bson(&ComponentStatus::Disconnect { timestamp: SystemTime::now() }).unwrap() } }, None).await {
-                                        eprintln!("Cannot disconnect a non-existent component");
+                                        if let Ok(None) = device_coll.find_one(doc! { "id": id, "owner_name": username.clone(), "components.id": component }, None).await {
+                                            eprintln!("Cannot disconnect a non-existent component");
+                                        } else if log_component_status(&mongoc, username.clone(), id, component, ComponentStatus::Disconnect { timestamp: SystemTime::now() }).await.is_none()
+                                            || set_component_online(&mongoc, username.clone(), id, component, false).await.is_none() {
+                                            eprintln!("Failed to process disconnect device data");
+                                        } else {
+                                            self.publish_event(ComponentDisconnected { owner_name: username.clone(), device_id: id, component });
+                                        }
                                     }
-                                }
                                     Ok(None) => {
                                         eprintln!(
                                             "Device '{}' did not exist for user '{}'",
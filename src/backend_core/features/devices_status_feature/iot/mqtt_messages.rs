@@ -59,6 +59,11 @@ pub struct ReadBatteryData {
 pub struct ReadDeviceErrorData {
     pub id: u32,
     pub component: u32,
+    // Older firmware only reports that something is wrong, without a code
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -151,6 +156,39 @@ mod deserialize_tests {
             data: vec![ReadDeviceErrorData {
                 id: 0,
                 component: 8,
+                code: None,
+                detail: None,
+            }],
+        };
+
+        assert_eq!(result, expected);
+    }
+
+    #[test]
+    fn deserialize_readdeviceerror_with_code() {
+        let input = r#"{
+            "kind": "1",
+            "payload": {
+                "token": "abcd",
+                "data": [
+                    {
+                        "id": 0,
+                        "component": 8,
+                        "code": 101,
+                        "detail": "chamber reading 0.2% obscuration at rest"
+                    }
+                ]
+            }
+        }"#;
+
+        let result: DeviceStatusMQTTMessage = serde_json::from_str(input).unwrap();
+        let expected = DeviceStatusMQTTMessage::ReadDeviceError {
+            token: Token::from("abcd"),
+            data: vec![ReadDeviceErrorData {
+                id: 0,
+                component: 8,
+                code: Some(101),
+                detail: Some(String::from("chamber reading 0.2% obscuration at rest")),
             }],
         };
 
@@ -278,6 +316,8 @@ mod serialize_tests {
             data: vec![ReadDeviceErrorData {
                 id: 0,
                 component: 8,
+                code: None,
+                detail: None,
             }],
         };
         let result = serde_json::to_string(&input)
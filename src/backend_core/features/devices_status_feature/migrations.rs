@@ -0,0 +1,160 @@
+use mongodb::{
+    bson::{doc, Document},
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+
+use crate::backend_core::migrations::insert_once;
+
+use super::models::{
+    BatteryAlertState, BatteryLog, BatterySettings, ComponentHeartbeat, ComponentLog, Device,
+    DeviceErrorLog,
+};
+
+fn timestamp_index(prefix: Document) -> IndexModel {
+    let mut keys = prefix;
+    keys.insert("timestamp.secs_since_epoch", -1);
+    keys.insert("timestamp.nanos_since_epoch", -1);
+    IndexModel::builder().keys(keys).build()
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    let database = mongoc.default_database().unwrap();
+
+    database
+        .collection::<BatteryLog>("device_battery_logs")
+        .create_index(timestamp_index(doc! { "owner_name": 1, "device_id": 1 }), None)
+        .await?;
+    database
+        .collection::<DeviceErrorLog>("device_error_logs")
+        .create_index(timestamp_index(doc! { "owner_name": 1, "id": 1 }), None)
+        .await?;
+    database
+        .collection::<ComponentLog>("device_component_logs")
+        .create_index(
+            timestamp_index(doc! { "owner_name": 1, "device_id": 1, "component": 1 }),
+            None,
+        )
+        .await?;
+    database
+        .collection::<ComponentHeartbeat>("device_heartbeats")
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "owner_name": 1, "device_id": 1, "component": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "online": 1, "last_seen.secs_since_epoch": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    database
+        .collection::<BatterySettings>("device_battery_settings")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "owner_name": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+    database
+        .collection::<BatteryAlertState>("device_battery_alerts")
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "owner_name": 1, "device_id": 1 })
+                .options(IndexOptions::builder().unique(true).build())
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Moves `battery_logs`, `error_logs` and `components.logs` out of each device
+// document into their own collections, one document per entry. Entries are
+// copied under ids made of the device and their position, so a rerun after a
+// crash before the `$unset` does not copy them twice.
+pub async fn split_log_arrays(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    let database = mongoc.default_database().unwrap();
+    let device_coll: Collection<Device> = database.collection("devices");
+    let battery_log_coll: Collection<Document> = database.collection("device_battery_logs");
+    let error_log_coll: Collection<Document> = database.collection("device_error_logs");
+    let component_log_coll: Collection<Document> = database.collection("device_component_logs");
+
+    let mut cursor = device_coll
+        .find(
+            doc! { "$or": [
+                { "battery_logs": { "$exists": true } },
+                { "error_logs": { "$exists": true } },
+                { "components.logs": { "$exists": true } },
+            ] },
+            None,
+        )
+        .await?;
+    while cursor.advance().await? {
+        let device = match cursor.deserialize_current() {
+            Ok(device) => device,
+            Err(e) => {
+                eprintln!("Skipping malformed device document: {}", e);
+                continue;
+            }
+        };
+        let id_prefix = format!("split:{}:{}", device.owner_name, device.id);
+
+        let battery_logs = device
+            .battery_logs
+            .into_iter()
+            .map(|status| BatteryLog {
+                owner_name: device.owner_name.clone(),
+                device_id: device.id,
+                status,
+            })
+            .enumerate()
+            .map(|(index, entry)| (format!("{id_prefix}:{index}"), entry))
+            .collect::<Vec<_>>();
+        insert_once(&battery_log_coll, battery_logs).await?;
+
+        let error_logs = device
+            .error_logs
+            .into_iter()
+            .map(|error| DeviceErrorLog {
+                owner_name: device.owner_name.clone(),
+                error,
+            })
+            .enumerate()
+            .map(|(index, entry)| (format!("{id_prefix}:{index}"), entry))
+            .collect::<Vec<_>>();
+        insert_once(&error_log_coll, error_logs).await?;
+
+        let component_logs = device
+            .components
+            .into_iter()
+            .flat_map(|component| {
+                let owner_name = device.owner_name.clone();
+                component.logs.into_iter().map(move |status| ComponentLog {
+                    owner_name: owner_name.clone(),
+                    device_id: device.id,
+                    component: component.id,
+                    timestamp: status.timestamp(),
+                    status,
+                })
+            })
+            .enumerate()
+            .map(|(index, entry)| (format!("{id_prefix}:{index}"), entry))
+            .collect::<Vec<_>>();
+        insert_once(&component_log_coll, component_logs).await?;
+
+        device_coll
+            .update_one(
+                doc! { "id": device.id, "owner_name": device.owner_name.clone() },
+                doc! { "$unset": { "battery_logs": "", "error_logs": "", "components.$[].logs": "" } },
+                None,
+            )
+            .await?;
+    }
+    Ok(())
+}
@@ -1,4 +1,8 @@
+pub mod battery;
+pub mod error_catalogue;
+pub mod heartbeat;
 pub mod iot;
+pub mod migrations;
 pub mod models;
 mod notifications;
 pub mod web;
@@ -4,11 +4,15 @@ use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use super::iot::mqtt_messages::ComponentType;
+use crate::backend_core::rollups::Resolution;
 
 #[derive(Serialize, Deserialize, JsonSchema)]
 pub struct Device {
     pub id: u32,
+    // Logs are stored in their own collections and only filled in on responses
+    #[serde(default)]
     pub battery_logs: Vec<BatteryStatus>,
+    #[serde(default)]
     pub error_logs: Vec<DeviceError>,
     pub components: Vec<Component>,
     pub owner_name: String, // username in document of collection User
@@ -18,6 +22,7 @@ pub struct Device {
 pub struct Component {
     pub id: u32,
     pub kind: ComponentType,
+    #[serde(default)]
     pub logs: Vec<ComponentStatus>,
 }
 
@@ -33,9 +38,125 @@ pub struct BatteryStatus {
     pub timestamp: SystemTime,
 }
 
-#[derive(Serialize, Deserialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
 pub struct DeviceError {
     pub id: u32,
     pub component: u32,
     pub timestamp: SystemTime,
+    #[serde(default)]
+    pub code: Option<u32>,
+    #[serde(default)]
+    pub detail: Option<String>,
+}
+
+// One document per battery reading in `device_battery_logs`
+#[derive(Serialize, Deserialize)]
+pub struct BatteryLog {
+    pub owner_name: String,
+    pub device_id: u32,
+    #[serde(flatten)]
+    pub status: BatteryStatus,
+}
+
+// Min/max/avg battery level over a bucket of `device_battery_rollups`
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct BatteryRollup {
+    pub resolution: Resolution,
+    pub timestamp: SystemTime,
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+    pub count: u32,
+}
+
+// One document per reported error in `device_error_logs`
+#[derive(Serialize, Deserialize)]
+pub struct DeviceErrorLog {
+    pub owner_name: String,
+    #[serde(flatten)]
+    pub error: DeviceError,
+}
+
+// One document per connect/disconnect in `device_component_logs`. The
+// timestamp is duplicated out of `status` so that it can be indexed.
+#[derive(Serialize, Deserialize)]
+pub struct ComponentLog {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+    pub status: ComponentStatus,
+    pub timestamp: SystemTime,
+}
+
+impl ComponentStatus {
+    pub fn timestamp(&self) -> SystemTime {
+        match self {
+            ComponentStatus::Connect { timestamp } | ComponentStatus::Disconnect { timestamp } => {
+                *timestamp
+            }
+        }
+    }
+}
+
+// Last time any feature heard from a component, one document per component in
+// `device_heartbeats`
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ComponentHeartbeat {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+    pub last_seen: SystemTime,
+    pub online: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct ComponentOfflineAlert {
+    pub device_id: u32,
+    pub component: u32,
+    pub last_seen: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct BatterySettings {
+    pub owner_name: String,
+    pub low_battery_threshold: u32,
+}
+
+// Whether the owner has already been told that a device is low, one document
+// per device in `device_battery_alerts`
+#[derive(Serialize, Deserialize)]
+pub struct BatteryAlertState {
+    pub owner_name: String,
+    pub device_id: u32,
+    pub low: bool,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct LowBatteryAlert {
+    pub device_id: u32,
+    pub battery: u32,
+    pub threshold: u32,
+    pub timestamp: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
+pub enum ErrorSeverity {
+    Info,
+    Warning,
+    Critical,
+}
+
+// A reported error resolved against the error catalogue for the kind of the
+// component that raised it
+#[derive(Serialize, Deserialize, JsonSchema)]
+pub struct DecodedDeviceError {
+    pub id: u32,
+    pub component: u32,
+    pub kind: Option<ComponentType>,
+    pub code: Option<u32>,
+    pub detail: Option<String>,
+    pub timestamp: SystemTime,
+    pub severity: ErrorSeverity,
+    pub description: String,
+    pub remediation: String,
 }
@@ -0,0 +1,140 @@
+use aide::axum::{routing::{get_with, put_with}, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, options::ReplaceOptions, Collection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::devices_status_feature::{
+        battery::DEFAULT_LOW_BATTERY_THRESHOLD, models::BatterySettings,
+    },
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetBatterySettingsQuery {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct BatterySettingsBody {
+    email: String,
+    low_battery_threshold: u32,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct BatterySettingsResponse {
+    message: String,
+    settings: Option<BatterySettings>,
+}
+
+async fn get_settings_coll() -> Collection<BatterySettings> {
+    get_collection("device_battery_settings").await
+}
+
+async fn get_battery_settings_handler(
+    Query(GetBatterySettingsQuery { email }): Query<GetBatterySettingsQuery>,
+) -> impl IntoApiResponse {
+    match get_settings_coll()
+        .await
+        .find_one(doc! { "owner_name": email.clone() }, None)
+        .await
+    {
+        Ok(settings) => (
+            StatusCode::OK,
+            Json(BatterySettingsResponse {
+                message: String::from("Successfully fetch battery settings"),
+                settings: Some(settings.unwrap_or(BatterySettings {
+                    owner_name: email,
+                    low_battery_threshold: DEFAULT_LOW_BATTERY_THRESHOLD,
+                })),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BatterySettingsResponse {
+                message: String::from("Unexpected error while fetching battery settings"),
+                settings: None,
+            }),
+        ),
+    }
+}
+
+async fn put_battery_settings_handler(
+    Json(BatterySettingsBody {
+        email,
+        low_battery_threshold,
+    }): Json<BatterySettingsBody>,
+) -> impl IntoApiResponse {
+    if low_battery_threshold > 100 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(BatterySettingsResponse {
+                message: String::from("Low battery threshold must be a percentage"),
+                settings: None,
+            }),
+        );
+    }
+
+    let settings = BatterySettings {
+        owner_name: email.clone(),
+        low_battery_threshold,
+    };
+    match get_settings_coll()
+        .await
+        .replace_one(
+            doc! { "owner_name": email },
+            &settings,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await
+    {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(BatterySettingsResponse {
+                message: String::from("Battery settings updated successfully"),
+                settings: Some(settings),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(BatterySettingsResponse {
+                message: String::from("Failed to update battery settings"),
+                settings: None,
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/battery-settings",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_battery_settings_handler, |op| {
+                op.description("Get the low battery notification threshold by user email")
+                    .tag("Devices status")
+                    .response::<200, Json<BatterySettingsResponse>>()
+                    .response::<403, Json<BatterySettingsResponse>>()
+                    .response::<500, Json<BatterySettingsResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/battery-settings",
+            Policy::Role(HouseholdRole::Member),
+            put_with(put_battery_settings_handler, |op| {
+                op.description("Set the battery percentage at or below which the user is notified")
+                    .tag("Devices status")
+                    .response::<200, Json<BatterySettingsResponse>>()
+                    .response::<400, Json<BatterySettingsResponse>>()
+                    .response::<403, Json<BatterySettingsResponse>>()
+                    .response::<500, Json<BatterySettingsResponse>>()
+            }),
+        )
+}
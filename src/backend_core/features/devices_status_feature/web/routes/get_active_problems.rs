@@ -0,0 +1,138 @@
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::bson::{doc, from_document};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::devices_status_feature::{
+        error_catalogue::{component_kind, decode_error},
+        models::{DecodedDeviceError, Device, DeviceErrorLog, ErrorSeverity},
+    },
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+const DEFAULT_PROBLEM_HOURS: u64 = 24;
+// Longer lookbacks are clamped to a year
+const MAX_PROBLEM_HOURS: u64 = 365 * 24;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetActiveProblemsQuery {
+    email: String,
+    // Errors older than this are considered stale
+    hours: Option<u64>,
+    min_severity: Option<ErrorSeverity>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetActiveProblemsResponse {
+    problems: Option<Vec<DecodedDeviceError>>,
+    message: String,
+}
+
+// The latest error of every component within the lookback, most severe first.
+async fn find_active_problems(
+    email: &str,
+    hours: u64,
+    min_severity: ErrorSeverity,
+) -> mongodb::error::Result<Vec<DecodedDeviceError>> {
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        - (hours * 3600) as i64;
+
+    let mut devices = HashMap::new();
+    let mut cursor = get_collection::<Device>("devices")
+        .await
+        .find(doc! { "owner_name": email }, None)
+        .await?;
+    while cursor.advance().await? {
+        let device = cursor.deserialize_current()?;
+        devices.insert(device.id, device);
+    }
+
+    let pipeline = vec![
+        doc! { "$match": { "owner_name": email, "timestamp.secs_since_epoch": { "$gte": since } } },
+        doc! { "$sort": { "timestamp.secs_since_epoch": -1, "timestamp.nanos_since_epoch": -1 } },
+        doc! { "$group": { "_id": { "id": "$id", "component": "$component" }, "latest": { "$first": "$$ROOT" } } },
+        doc! { "$replaceRoot": { "newRoot": "$latest" } },
+    ];
+    let mut cursor = get_collection::<DeviceErrorLog>("device_error_logs")
+        .await
+        .aggregate(pipeline, None)
+        .await?;
+    let mut problems = vec![];
+    while cursor.advance().await? {
+        let DeviceErrorLog { error, .. } = from_document(cursor.deserialize_current()?)?;
+        let kind = devices
+            .get(&error.id)
+            .and_then(|device| component_kind(device, error.component));
+        let decoded = decode_error(error, kind);
+        if decoded.severity >= min_severity {
+            problems.push(decoded);
+        }
+    }
+    problems.sort_by(|a, b| {
+        b.severity
+            .cmp(&a.severity)
+            .then_with(|| b.timestamp.cmp(&a.timestamp))
+    });
+    Ok(problems)
+}
+
+async fn handler(
+    Query(GetActiveProblemsQuery {
+        email,
+        hours,
+        min_severity,
+    }): Query<GetActiveProblemsQuery>,
+) -> impl IntoApiResponse {
+    match find_active_problems(
+        &email,
+        hours.unwrap_or(DEFAULT_PROBLEM_HOURS).min(MAX_PROBLEM_HOURS),
+        min_severity.unwrap_or(ErrorSeverity::Info),
+    )
+    .await
+    {
+        Ok(problems) => (
+            StatusCode::OK,
+            Json(GetActiveProblemsResponse {
+                message: String::from("Successfully fetch active problems"),
+                problems: Some(problems),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetActiveProblemsResponse {
+                message: String::from("Unexpected error while fetching active problems"),
+                problems: None,
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new().authorized_route(
+        "/active-problems",
+        Policy::Role(HouseholdRole::Viewer),
+        get_with(handler, |op| {
+            op.description("Get the latest decoded error of every component of a user, most severe first")
+                .tag("Devices status")
+                .response::<200, Json<GetActiveProblemsResponse>>()
+                .response::<403, Json<GetActiveProblemsResponse>>()
+                .response::<500, Json<GetActiveProblemsResponse>>()
+        }),
+    )
+}
@@ -1,7 +1,7 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
 };
 use mongodb::{bson::doc, options::FindOptions, Collection};
 use schemars::JsonSchema;
@@ -14,6 +14,7 @@ use crate::{
     },
     json::Json,
 };
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetAllDevicesQuery {
@@ -39,22 +40,8 @@ pub struct ResponseDevice {
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetAllDevicesQuery { email }): Query<GetAllDevicesQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetAllDeviceResponse {
-                message: String::from("Forbidden"),
-                devices: None,
-            }),
-        );
-    }
     let device_coll: Collection<Device> = {
         let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
         mongoc.default_database().unwrap().collection("devices")
@@ -118,8 +105,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/devices",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get all devices for a given user by email")
                 .tag("Devices status")
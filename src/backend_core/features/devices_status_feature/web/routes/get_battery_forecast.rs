@@ -0,0 +1,133 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, options::FindOptions};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::devices_status_feature::{
+        battery::forecast_depletion,
+        models::{BatteryLog, Device},
+    },
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+const DEFAULT_FORECAST_DAYS: u64 = 14;
+// Longer lookbacks are clamped, readings are not kept that long anyway
+const MAX_FORECAST_DAYS: u64 = 365;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetBatteryForecastQuery {
+    email: String,
+    // How many days of battery readings the trend is fitted over
+    days: Option<u64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct BatteryForecast {
+    device_id: u32,
+    battery: Option<u32>,
+    drain_per_day: Option<f32>,
+    days_until_empty: Option<f32>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetBatteryForecastResponse {
+    forecasts: Option<Vec<BatteryForecast>>,
+    message: String,
+}
+
+async fn forecast_device(
+    email: &str,
+    device_id: u32,
+    since: i64,
+) -> mongodb::error::Result<BatteryForecast> {
+    let find_options = FindOptions::builder()
+        .sort(doc! { "timestamp.secs_since_epoch": 1, "timestamp.nanos_since_epoch": 1 })
+        .build();
+    let mut cursor = get_collection::<BatteryLog>("device_battery_logs")
+        .await
+        .find(
+            doc! {
+                "owner_name": email,
+                "device_id": device_id,
+                "timestamp.secs_since_epoch": { "$gte": since },
+            },
+            find_options,
+        )
+        .await?;
+    let mut readings = vec![];
+    while cursor.advance().await? {
+        readings.push(cursor.deserialize_current()?.status);
+    }
+
+    let forecast = forecast_depletion(&readings);
+    Ok(BatteryForecast {
+        device_id,
+        battery: readings.last().map(|reading| reading.battery),
+        drain_per_day: forecast.map(|(drain, _)| drain),
+        days_until_empty: forecast.and_then(|(_, days)| days),
+    })
+}
+
+async fn forecast_devices(email: &str, days: u64) -> mongodb::error::Result<Vec<BatteryForecast>> {
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+        - (days * 24 * 3600) as i64;
+
+    let mut cursor = get_collection::<Device>("devices")
+        .await
+        .find(doc! { "owner_name": email }, None)
+        .await?;
+    let mut forecasts = vec![];
+    while cursor.advance().await? {
+        let device = cursor.deserialize_current()?;
+        forecasts.push(forecast_device(email, device.id, since).await?);
+    }
+    Ok(forecasts)
+}
+
+async fn handler(
+    Query(GetBatteryForecastQuery { email, days }): Query<GetBatteryForecastQuery>,
+) -> impl IntoApiResponse {
+    match forecast_devices(&email, days.unwrap_or(DEFAULT_FORECAST_DAYS).min(MAX_FORECAST_DAYS)).await {
+        Ok(forecasts) => (
+            StatusCode::OK,
+            Json(GetBatteryForecastResponse {
+                message: String::from("Successfully forecast battery depletion"),
+                forecasts: Some(forecasts),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetBatteryForecastResponse {
+                message: String::from("Unexpected error while forecasting battery depletion"),
+                forecasts: None,
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new().authorized_route(
+        "/battery-forecast",
+        Policy::Role(HouseholdRole::Viewer),
+        get_with(handler, |op| {
+            op.description("Estimate the days until empty of each device of a user from the trend of its recent battery readings")
+                .tag("Devices status")
+                .response::<200, Json<GetBatteryForecastResponse>>()
+                .response::<403, Json<GetBatteryForecastResponse>>()
+                .response::<500, Json<GetBatteryForecastResponse>>()
+        }),
+    )
+}
@@ -1,19 +1,40 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
+};
+use mongodb::{
+    bson::{doc, Document},
+    options::FindOptions,
+    Collection,
 };
-use mongodb::{bson::doc, Collection};
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    backend_core::{
+        features::devices_status_feature::{
+            error_catalogue::{component_kind, decode_error},
+            models::{
+                BatteryLog, BatteryRollup, ComponentLog, DecodedDeviceError, Device, DeviceErrorLog,
+            },
+        },
+        rollups::Resolution,
+    },
+    json::Json,
+};
 
-use crate::{backend_core::features::devices_status_feature::models::Device, json::Json};
+use super::{get_collection, MONGOC};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
-use super::MONGOC;
+// Only the most recent entries of each log are embedded in the device
+const MAX_LOGS_PER_KIND: i64 = 100;
 
 #[derive(Serialize, JsonSchema)]
 pub struct GetDeviceByIdResponse {
     device: Option<Device>,
+    battery_rollups: Option<Vec<BatteryRollup>>,
+    decoded_errors: Option<Vec<DecodedDeviceError>>,
     message: String,
 }
 
@@ -21,25 +42,75 @@ pub struct GetDeviceByIdResponse {
 pub struct GetDeviceByIdQuery {
     device_id: u32,
     email: String,
+    resolution: Option<Resolution>,
+}
+
+async fn find_recent_logs<T: DeserializeOwned + Unpin + Send + Sync>(
+    coll_name: &str,
+    filter: Document,
+) -> mongodb::error::Result<Vec<T>> {
+    let log_coll: Collection<T> = get_collection(coll_name).await;
+
+    let find_options = FindOptions::builder()
+        .sort(doc! { "timestamp.secs_since_epoch": -1, "timestamp.nanos_since_epoch": -1 })
+        .limit(MAX_LOGS_PER_KIND)
+        .build();
+
+    let mut cursor = log_coll.find(filter, find_options).await?;
+    let mut logs = vec![];
+    while cursor.advance().await? {
+        logs.push(cursor.deserialize_current()?);
+    }
+    logs.reverse();
+    Ok(logs)
+}
+
+// With a rolled-up resolution the raw battery logs are replaced by rollups.
+async fn attach_logs(
+    mut device: Device,
+    resolution: Resolution,
+) -> mongodb::error::Result<(Device, Option<Vec<BatteryRollup>>)> {
+    let filter = doc! { "owner_name": device.owner_name.clone(), "device_id": device.id };
+
+    let mut battery_rollups = None;
+    if resolution == Resolution::Raw {
+        device.battery_logs = find_recent_logs::<BatteryLog>("device_battery_logs", filter.clone())
+            .await?
+            .into_iter()
+            .map(|log| log.status)
+            .collect();
+    } else {
+        let mut filter = filter.clone();
+        filter.insert("resolution", resolution.as_str());
+        battery_rollups = Some(find_recent_logs("device_battery_rollups", filter).await?);
+    }
+    device.error_logs = find_recent_logs::<DeviceErrorLog>(
+        "device_error_logs",
+        doc! { "owner_name": device.owner_name.clone(), "id": device.id },
+    )
+    .await?
+    .into_iter()
+    .map(|log| log.error)
+    .collect();
+    for component in device.components.iter_mut() {
+        let mut filter = filter.clone();
+        filter.insert("component", component.id);
+        component.logs = find_recent_logs::<ComponentLog>("device_component_logs", filter)
+            .await?
+            .into_iter()
+            .map(|log| log.status)
+            .collect();
+    }
+    Ok((device, battery_rollups))
 }
 
 async fn handler(
-    headers: HeaderMap,
-    Query(GetDeviceByIdQuery { device_id, email }): Query<GetDeviceByIdQuery>,
+    Query(GetDeviceByIdQuery {
+        device_id,
+        email,
+        resolution,
+    }): Query<GetDeviceByIdQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetDeviceByIdResponse {
-                message: String::from("Forbidden"),
-                device: None,
-            }),
-        );
-    }
     let device_coll: Collection<Device> = {
         let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
         mongoc.default_database().unwrap().collection("devices")
@@ -49,17 +120,40 @@ async fn handler(
         .find_one(doc! { "id": device_id, "owner_name": email.clone() }, None)
         .await
     {
-        Ok(Some(device)) => (
-            StatusCode::OK,
-            Json(GetDeviceByIdResponse {
-                device: Some(device),
-                message: format!("Successfully fetch device '{device_id}'"),
-            }),
-        ),
+        Ok(Some(device)) => match attach_logs(device, resolution.unwrap_or_default()).await {
+            Ok((device, battery_rollups)) => (
+                StatusCode::OK,
+                Json(GetDeviceByIdResponse {
+                    decoded_errors: Some(
+                        device
+                            .error_logs
+                            .iter()
+                            .map(|error| {
+                                decode_error(error.clone(), component_kind(&device, error.component))
+                            })
+                            .collect(),
+                    ),
+                    device: Some(device),
+                    battery_rollups,
+                    message: format!("Successfully fetch device '{device_id}'"),
+                }),
+            ),
+            Err(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(GetDeviceByIdResponse {
+                    device: None,
+                    battery_rollups: None,
+                    decoded_errors: None,
+                    message: format!("Unexpected error while fetching logs of device '{device_id}'"),
+                }),
+            ),
+        },
         Ok(None) => (
             StatusCode::OK,
             Json(GetDeviceByIdResponse {
                 device: None,
+                battery_rollups: None,
+                decoded_errors: None,
                 message: format!(
                     "No device with id '{}' for user '{}'",
                     device_id,
@@ -71,6 +165,8 @@ async fn handler(
             StatusCode::INTERNAL_SERVER_ERROR,
             Json(GetDeviceByIdResponse {
                 device: None,
+                battery_rollups: None,
+                decoded_errors: None,
                 message: format!("Unexpected error while fetching device with id '{device_id}'"),
             }),
         ),
@@ -78,8 +174,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/devices/:device_id",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get devices by id for a given user by email")
                 .tag("Devices status")
@@ -1,15 +1,25 @@
 use std::sync::Arc;
 
 use aide::axum::ApiRouter;
+use mongodb::Collection;
 use tokio::sync::Mutex;
 
 use super::WebDeviceStatusFeature;
 
 pub static mut MONGOC: Option<Arc<Mutex<mongodb::Client>>> = None;
 
+mod battery_settings;
+mod get_active_problems;
 mod get_all_devices;
+mod get_battery_forecast;
 mod get_device_by_id;
 
+#[allow(static_mut_refs)]
+pub async fn get_collection<T>(name: &str) -> Collection<T> {
+    let mongoc = unsafe { MONGOC.as_ref().unwrap().lock() }.await;
+    mongoc.default_database().unwrap().collection(name)
+}
+
 pub fn create_router(web: &mut WebDeviceStatusFeature) -> ApiRouter {
     unsafe {
         MONGOC = Some(Arc::new(Mutex::new(web.mongoc.clone())));
@@ -18,4 +28,7 @@ pub fn create_router(web: &mut WebDeviceStatusFeature) -> ApiRouter {
     ApiRouter::new()
         .nest("/", get_all_devices::routes())
         .nest("/", get_device_by_id::routes())
+        .nest("/", battery_settings::routes())
+        .nest("/", get_battery_forecast::routes())
+        .nest("/", get_active_problems::routes())
 }
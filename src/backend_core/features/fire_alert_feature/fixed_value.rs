@@ -1 +1,31 @@
-pub const MAX_AMOUNT_DOCUMENT_PER_REQUEST:i64 = 100;
\ No newline at end of file
+use super::models::SensorDataType;
+
+pub const MAX_AMOUNT_DOCUMENT_PER_REQUEST:i64 = 100;
+
+// Built-in thresholds used when a user has not configured their own.
+// Smoke, CO and LPG are in ppm, heat is in degree Celsius.
+pub const DEFAULT_SMOKE_THRESHOLD: f32 = 300.0;
+pub const DEFAULT_CO_THRESHOLD: f32 = 50.0;
+pub const DEFAULT_HEAT_THRESHOLD: f32 = 57.0;
+pub const DEFAULT_LPG_THRESHOLD: f32 = 2000.0;
+
+// Rate-of-rise heat detectors conventionally trip at 15 degree Fahrenheit per minute
+pub const DEFAULT_RATE_OF_RISE_PER_MINUTE: f32 = 8.3;
+pub const DEFAULT_RATE_OF_RISE_WINDOW_SECS: u64 = 120;
+pub const DEFAULT_CORRELATION_WINDOW_SECS: u64 = 300;
+// Readings are kept in memory for the length of the windows, so they are capped
+pub const MAX_ANALYSIS_WINDOW_SECS: u64 = 3600;
+
+pub fn default_threshold(sensor_type: SensorDataType) -> Option<f32> {
+    match sensor_type {
+        SensorDataType::Smoke => Some(DEFAULT_SMOKE_THRESHOLD),
+        SensorDataType::CO => Some(DEFAULT_CO_THRESHOLD),
+        SensorDataType::Heat => Some(DEFAULT_HEAT_THRESHOLD),
+        SensorDataType::LPG => Some(DEFAULT_LPG_THRESHOLD),
+        // Flame sensor scales differ too much between vendors to guess a sane default
+        SensorDataType::Fire
+        | SensorDataType::FireButton
+        | SensorDataType::FireLight
+        | SensorDataType::FireBuzzer => None,
+    }
+}
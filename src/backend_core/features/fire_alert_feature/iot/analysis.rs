@@ -0,0 +1,175 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, SystemTime},
+};
+
+use crate::backend_core::features::fire_alert_feature::models::SensorDataType;
+
+// A slope is only trusted once the window holds enough samples spread over
+// enough time, otherwise a single noisy reading would trip the alarm.
+const MIN_RATE_SAMPLES: usize = 3;
+const MIN_RATE_SPAN: Duration = Duration::from_secs(30);
+
+#[derive(Default)]
+pub struct FireAnalyzer {
+    heat_windows: HashMap<(String, u32, u32), VecDeque<(SystemTime, f32)>>,
+    room_alerts: HashMap<(String, String), HashMap<SensorDataType, SystemTime>>,
+}
+
+impl FireAnalyzer {
+    // Adds a heat reading to the component's sliding window and returns the
+    // least-squares temperature slope in degree Celsius per minute.
+    pub fn record_heat(
+        &mut self,
+        owner_name: &str,
+        device_id: u32,
+        component: u32,
+        timestamp: SystemTime,
+        value: f32,
+        window: Duration,
+    ) -> Option<f32> {
+        let samples = self
+            .heat_windows
+            .entry((owner_name.to_string(), device_id, component))
+            .or_default();
+        samples.push_back((timestamp, value));
+        while samples
+            .front()
+            .is_some_and(|(oldest, _)| timestamp.duration_since(*oldest).unwrap_or_default() > window)
+        {
+            samples.pop_front();
+        }
+
+        let (first, _) = *samples.front()?;
+        if samples.len() < MIN_RATE_SAMPLES
+            || timestamp.duration_since(first).unwrap_or_default() < MIN_RATE_SPAN
+        {
+            return None;
+        }
+
+        let points = samples
+            .iter()
+            .map(|(at, value)| {
+                let minutes = at.duration_since(first).unwrap_or_default().as_secs_f64() / 60.0;
+                (minutes, *value as f64)
+            })
+            .collect::<Vec<_>>();
+        let count = points.len() as f64;
+        let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+        let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+        let covariance = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum::<f64>();
+        let variance = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum::<f64>();
+        if variance == 0.0 {
+            return None;
+        }
+        Some((covariance / variance) as f32)
+    }
+
+    // Remembers an UNSAFE smoke, heat or CO reading in a room and reports
+    // whether smoke together with heat or CO was seen within the window.
+    pub fn record_room_alert(
+        &mut self,
+        owner_name: &str,
+        room_name: &str,
+        sensor_type: SensorDataType,
+        timestamp: SystemTime,
+        window: Duration,
+    ) -> bool {
+        if !matches!(
+            sensor_type,
+            SensorDataType::Smoke | SensorDataType::Heat | SensorDataType::CO
+        ) {
+            return false;
+        }
+
+        let alerts = self
+            .room_alerts
+            .entry((owner_name.to_string(), room_name.to_string()))
+            .or_default();
+        alerts.insert(sensor_type, timestamp);
+        alerts.retain(|_, at| timestamp.duration_since(*at).unwrap_or_default() <= window);
+
+        alerts.contains_key(&SensorDataType::Smoke)
+            && (alerts.contains_key(&SensorDataType::Heat) || alerts.contains_key(&SensorDataType::CO))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use super::FireAnalyzer;
+    use crate::backend_core::features::fire_alert_feature::models::SensorDataType;
+
+    const OWNER: &str = "user@example.com";
+    const WINDOW: Duration = Duration::from_secs(120);
+
+    #[test]
+    fn rate_of_rise_needs_enough_samples() {
+        let mut analyzer = FireAnalyzer::default();
+        let start = SystemTime::UNIX_EPOCH;
+
+        assert_eq!(analyzer.record_heat(OWNER, 1, 1, start, 20.0, WINDOW), None);
+        assert_eq!(
+            analyzer.record_heat(OWNER, 1, 1, start + Duration::from_secs(60), 30.0, WINDOW),
+            None
+        );
+        assert_eq!(
+            analyzer.record_heat(OWNER, 1, 1, start + Duration::from_secs(120), 40.0, WINDOW),
+            Some(10.0)
+        );
+    }
+
+    #[test]
+    fn rate_of_rise_forgets_readings_outside_the_window() {
+        let mut analyzer = FireAnalyzer::default();
+        let start = SystemTime::UNIX_EPOCH;
+
+        analyzer.record_heat(OWNER, 1, 1, start, 80.0, WINDOW);
+        for minute in 5..8 {
+            let rate = analyzer.record_heat(
+                OWNER,
+                1,
+                1,
+                start + Duration::from_secs(minute * 60),
+                20.0 + minute as f32,
+                WINDOW,
+            );
+            if minute == 7 {
+                assert_eq!(rate, Some(1.0));
+            }
+        }
+    }
+
+    #[test]
+    fn lone_smoke_is_not_a_confirmed_fire() {
+        let mut analyzer = FireAnalyzer::default();
+        let start = SystemTime::UNIX_EPOCH;
+
+        assert!(!analyzer.record_room_alert(OWNER, "Kitchen", SensorDataType::Smoke, start, WINDOW));
+        assert!(!analyzer.record_room_alert(
+            OWNER,
+            "Bedroom",
+            SensorDataType::Heat,
+            start + Duration::from_secs(10),
+            WINDOW
+        ));
+        assert!(!analyzer.record_room_alert(
+            OWNER,
+            "Kitchen",
+            SensorDataType::Heat,
+            start + Duration::from_secs(600),
+            WINDOW
+        ));
+        assert!(analyzer.record_room_alert(
+            OWNER,
+            "Kitchen",
+            SensorDataType::Smoke,
+            start + Duration::from_secs(660),
+            WINDOW
+        ));
+    }
+}
@@ -1,17 +1,29 @@
 use axum::async_trait;
-use mongodb::bson::{doc, to_bson};
+use mongodb::{bson::doc, Collection};
 use rumqttc::{Event, Incoming, Publish};
-use std::{any::Any, sync::{Arc, Weak}, time::SystemTime};
+use std::{any::Any, collections::{hash_map::Entry, HashMap, HashSet}, sync::{Arc, Weak}, time::{Duration, SystemTime}};
 use tokio::sync::Mutex;
 
-use super::mqtt_messages::FireMQTTMessage;
+use super::{
+    analysis::FireAnalyzer,
+    incidents::{find_room_of_device, new_incident, raise_incident, resolve_incident, track_incident},
+    mqtt_messages::FireMQTTMessage,
+    thresholds::{combine_verdicts, evaluate_reading, resolve_threshold},
+};
 use crate::{
-    auth::get_email_from_client_token,
+    auth::get_email_from_topic_token,
     backend_core::{
+        events::SensorReadingIngested,
         features::{
-            fire_alert_feature::{models::{FireStatus, SensorDataType, SensorLogData}, web::WebFireFeature}, IotFeature, WebFeature,
+            devices_status_feature::heartbeat::record_heartbeat,
+            fire_alert_feature::{
+                fixed_value::{DEFAULT_CORRELATION_WINDOW_SECS, DEFAULT_RATE_OF_RISE_PER_MINUTE, DEFAULT_RATE_OF_RISE_WINDOW_SECS, MAX_ANALYSIS_WINDOW_SECS},
+                models::{AlertThreshold, AnalysisSettings, FireStatus, IncidentKind, SensorDataType, SensorLogData, SensorLogEntry},
+                web::WebFireFeature,
+            },
+            IotFeature, WebFeature,
         }, utils::non_primitive_cast,
-    }, push_notification::push_notification,
+    },
 };
 
 #[derive(Clone)]
@@ -21,6 +33,7 @@ pub struct IotFireFeature {
     mongoc: mongodb::Client,
     web_instance: Option<Weak<WebFireFeature>>,
     jwt_key: String,
+    analyzer: Arc<Mutex<FireAnalyzer>>,
 }
 
 impl IotFireFeature {
@@ -30,36 +43,131 @@ impl IotFireFeature {
         sensor_data: &[SensorLogData],
         sensor_type: &SensorDataType,
     ) -> Option<()> {
-        let field_name = match sensor_type {
-            SensorDataType::Fire => "fire_logs",
-            SensorDataType::Smoke => "smoke_logs",
-            SensorDataType::CO => "co_logs",
-            SensorDataType::Heat => "heat_logs",
-            SensorDataType::FireButton => "button_logs",
-            SensorDataType::FireLight => "light_logs",
-            SensorDataType::FireBuzzer => "buzzer_logs",
-            SensorDataType::LPG => "lpg_logs",
-        };
-
-        let fire_log_coll = self
+        if sensor_data.is_empty() {
+            return Some(());
+        }
+
+        let sensor_log_coll: Collection<SensorLogEntry> = self
             .mongoc
-            .clone()
             .default_database()
             .unwrap()
-            .collection("fire_alerts");
-        let mut session = self.mongoc.clone().start_session(None).await.ok()?;
-        session.start_transaction(None).await.ok()?;
+            .collection("fire_sensor_logs");
 
-        if let Ok(None) = fire_log_coll
-            .find_one(doc! { "owner_name": owner_name.clone() }, None)
+        sensor_log_coll
+            .insert_many(
+                sensor_data.iter().map(|data| SensorLogEntry {
+                    owner_name: owner_name.clone(),
+                    sensor_type: *sensor_type,
+                    data: data.clone(),
+                }),
+                None,
+            )
+            .await
+            .ok()?;
+
+        Some(())
+    }
+
+    async fn load_thresholds(&self, owner_name: String) -> Vec<AlertThreshold> {
+        let threshold_coll: Collection<AlertThreshold> = self
+            .mongoc
+            .default_database()
+            .unwrap()
+            .collection("fire_alert_thresholds");
+
+        let mut thresholds = vec![];
+        if let Ok(mut cursor) = threshold_coll
+            .find(doc! { "owner_name": owner_name }, None)
             .await
         {
-            fire_log_coll.insert_one(doc! { "owner_name": owner_name.clone(), "fire_logs": [], "smoke_logs": [], "co_logs": [], "heat_logs": [], "button_logs": [] }, None).await.ok()?;
+            while let Ok(true) = cursor.advance().await {
+                if let Ok(threshold) = cursor.deserialize_current() {
+                    thresholds.push(threshold);
+                }
+            }
         }
+        thresholds
+    }
 
-        fire_log_coll.find_one_and_update(doc! { "owner_name": owner_name.clone() }, doc! { "$push": { field_name: { "$each": sensor_data.iter().map(|data| to_bson(data).unwrap()).collect::<Vec<_>>() }}}, None).await.ok()?;
+    async fn load_analysis_settings(&self, owner_name: String) -> AnalysisSettings {
+        let settings_coll: Collection<AnalysisSettings> = self
+            .mongoc
+            .default_database()
+            .unwrap()
+            .collection("fire_alert_analysis_settings");
 
-        Some(())
+        settings_coll
+            .find_one(doc! { "owner_name": owner_name.clone() }, None)
+            .await
+            .ok()
+            .flatten()
+            .unwrap_or(AnalysisSettings {
+                owner_name,
+                rate_of_rise_per_minute: DEFAULT_RATE_OF_RISE_PER_MINUTE,
+                rate_of_rise_window_secs: DEFAULT_RATE_OF_RISE_WINDOW_SECS,
+                correlation_window_secs: DEFAULT_CORRELATION_WINDOW_SECS,
+            })
+    }
+
+    // Raises rate-of-rise alarms from the heat trend of each component and
+    // confirmed fire alarms when smoke and heat or CO alert in the same room.
+    async fn analyze_readings(
+        &self,
+        mongoc: &mut mongodb::Client,
+        owner_name: String,
+        settings: &AnalysisSettings,
+        sensor_type: SensorDataType,
+        readings: &[SensorLogData],
+        rooms: &HashMap<u32, Option<String>>,
+    ) {
+        for reading in readings {
+            let room_name = rooms.get(&reading.id).cloned().flatten();
+
+            if sensor_type == SensorDataType::Heat {
+                let rate = self.analyzer.lock().await.record_heat(
+                    &owner_name,
+                    reading.id,
+                    reading.component,
+                    reading.timestamp,
+                    reading.value,
+                    Duration::from_secs(settings.rate_of_rise_window_secs.min(MAX_ANALYSIS_WINDOW_SECS)),
+                );
+                if let Some(rate) = rate {
+                    let incident = new_incident(
+                        IncidentKind::RateOfRise,
+                        owner_name.clone(),
+                        sensor_type,
+                        room_name.clone(),
+                        reading,
+                    );
+                    if rate >= settings.rate_of_rise_per_minute {
+                        raise_incident(mongoc, incident, reading).await;
+                    } else {
+                        resolve_incident(mongoc, &incident, reading.timestamp).await;
+                    }
+                }
+            }
+
+            if let (FireStatus::UNSAFE, Some(room_name)) = (reading.alert, room_name) {
+                let confirmed = self.analyzer.lock().await.record_room_alert(
+                    &owner_name,
+                    &room_name,
+                    sensor_type,
+                    reading.timestamp,
+                    Duration::from_secs(settings.correlation_window_secs.min(MAX_ANALYSIS_WINDOW_SECS)),
+                );
+                if confirmed {
+                    let incident = new_incident(
+                        IncidentKind::ConfirmedFire,
+                        owner_name.clone(),
+                        sensor_type,
+                        Some(room_name),
+                        reading,
+                    );
+                    raise_incident(mongoc, incident, reading).await;
+                }
+            }
+        }
     }
 }
 
@@ -77,6 +185,7 @@ impl IotFeature for IotFireFeature {
             mongoc: mongoc.clone(),
             web_instance: None,
             jwt_key,
+            analyzer: Arc::new(Mutex::new(FireAnalyzer::default())),
         })
     }
 
@@ -113,7 +222,7 @@ impl IotFeature for IotFireFeature {
     async fn process_next_mqtt_message(&mut self) {
         let mut mongoc = self.get_mongoc();
         let mut mqtt_event_loop = self.mqtt_event_loop.lock().await;
-        if let Ok(Event::Incoming(Incoming::Publish(Publish { payload, .. }))) =
+        if let Ok(Event::Incoming(Incoming::Publish(Publish { topic, payload, .. }))) =
             mqtt_event_loop.poll().await
         {
             if let Ok(raw_json) = String::from_utf8(payload.to_vec()) {
@@ -142,8 +251,11 @@ impl IotFeature for IotFireFeature {
                             lpg
                         } => {
                             if let Some(email) =
-                                get_email_from_client_token(&self.jwt_key, token.clone(), &mut mongoc).await
+                                get_email_from_topic_token(&self.jwt_key, &topic, token.clone(), &mut mongoc).await
                             {
+                                let thresholds = self.load_thresholds(email.clone()).await;
+                                let settings = self.load_analysis_settings(email.clone()).await;
+                                let mut rooms = HashMap::new();
                                 let sensor_data = vec![
                                     (SensorDataType::Fire, fire_data),
                                     (SensorDataType::Smoke, smoke),
@@ -155,15 +267,29 @@ impl IotFeature for IotFireFeature {
                                     (SensorDataType::LPG, lpg),
                                 ];
 
+                                let components = sensor_data
+                                    .iter()
+                                    .flat_map(|(_, data)| data.iter().map(|sensor| (sensor.id, sensor.component)))
+                                    .collect::<HashSet<_>>();
+                                for (device_id, component) in components {
+                                    record_heartbeat(&mongoc, email.clone(), device_id, Some(component)).await;
+                                }
+
                                 for (sensor_type, data) in sensor_data {
                                     let sensor_logs = data
                                         .into_iter()
-                                        .map(|sensor| SensorLogData {
-                                            id: sensor.id,
-                                            component: sensor.component,
-                                            value: sensor.value,
-                                            alert: sensor.alert,
-                                            timestamp: SystemTime::now(),
+                                        .map(|sensor| {
+                                            let server_alert = resolve_threshold(&thresholds, sensor_type, sensor.component)
+                                                .map(|threshold| evaluate_reading(sensor.value, threshold));
+                                            SensorLogData {
+                                                id: sensor.id,
+                                                component: sensor.component,
+                                                value: sensor.value,
+                                                alert: combine_verdicts(sensor.alert, server_alert),
+                                                device_alert: Some(sensor.alert),
+                                                server_alert,
+                                                timestamp: SystemTime::now(),
+                                            }
                                         })
                                         .collect::<Vec<_>>();
 
@@ -185,16 +311,29 @@ impl IotFeature for IotFireFeature {
                                         }
                                     }
 
-                                    let alert_data = sensor_logs
-                                        .into_iter()
-                                        .filter(| SensorLogData { alert, .. } | *alert == FireStatus::UNSAFE)
-                                        .collect::<Vec<_>>();
-                                    
-                                    push_notification(
-                                        email.clone(), 
-                                        serde_json::to_string(&alert_data).unwrap(), 
-                                        &mut mongoc,
-                                    ).await;
+                                    for reading in &sensor_logs {
+                                        if let Entry::Vacant(entry) = rooms.entry(reading.id) {
+                                            entry.insert(find_room_of_device(&mongoc, email.clone(), reading.id).await);
+                                        }
+                                    }
+
+                                    for reading in &sensor_logs {
+                                        let room_name = rooms.get(&reading.id).cloned().flatten();
+                                        track_incident(&mut mongoc, email.clone(), sensor_type, room_name, reading).await;
+                                    }
+
+                                    self.analyze_readings(&mut mongoc, email.clone(), &settings, sensor_type, &sensor_logs, &rooms).await;
+
+                                    for reading in &sensor_logs {
+                                        self.publish_event(SensorReadingIngested {
+                                            owner_name: email.clone(),
+                                            device_id: reading.id,
+                                            component: reading.component,
+                                            sensor_type,
+                                            value: reading.value,
+                                            alert: reading.alert,
+                                        });
+                                    }
                                 }
                             } else {
                                 eprintln!("Invalid token");
@@ -0,0 +1,218 @@
+use std::time::SystemTime;
+
+use mongodb::{
+    bson::{doc, to_bson, Document},
+    Collection,
+};
+
+use crate::{
+    backend_core::{
+        channels::publish_event,
+        events::{IncidentResolved, IncidentUpdated},
+        features::fire_alert_feature::models::{
+            FireStatus, Incident, IncidentAlert, IncidentEvent, IncidentKind, IncidentStatus,
+            SensorDataType, SensorLogData,
+        },
+    },
+    push_notification::push_notification,
+};
+
+pub async fn find_room_of_device(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    device_id: u32,
+) -> Option<String> {
+    let room_coll: Collection<Document> = mongoc.default_database().unwrap().collection("rooms");
+    let room = room_coll
+        .find_one(doc! { "owner_name": owner_name, "devices": device_id }, None)
+        .await
+        .ok()??;
+    room.get_str("name").ok().map(String::from)
+}
+
+fn get_incident_coll(mongoc: &mongodb::Client) -> Collection<Incident> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("fire_incidents")
+}
+
+// Threshold incidents are tracked per sensor, rate-of-rise incidents per heat
+// component and confirmed fires per room.
+fn active_incident_filter(incident: &Incident) -> Option<Document> {
+    let mut filter = doc! {
+        "owner_name": incident.owner_name.clone(),
+        "kind": to_bson(&incident.kind).ok()?,
+        "status": { "$ne": to_bson(&IncidentStatus::Resolved).ok()? },
+    };
+    match incident.kind {
+        IncidentKind::Threshold => {
+            filter.insert("device_id", incident.device_id);
+            filter.insert("component", incident.component);
+            filter.insert("sensor_type", to_bson(&incident.sensor_type).ok()?);
+        }
+        IncidentKind::RateOfRise => {
+            filter.insert("device_id", incident.device_id);
+            filter.insert("component", incident.component);
+        }
+        IncidentKind::ConfirmedFire => {
+            filter.insert("room_name", incident.room_name.clone());
+        }
+    }
+    Some(filter)
+}
+
+fn incident_alert(incident: &Incident, reading: &SensorLogData) -> IncidentAlert {
+    IncidentAlert {
+        incident_id: incident.id.clone(),
+        kind: incident.kind,
+        status: incident.status,
+        sensor_type: incident.sensor_type,
+        room_name: incident.room_name.clone(),
+        reading: reading.clone(),
+    }
+}
+
+async fn notify_incident(
+    mongoc: &mut mongodb::Client,
+    incident: &Incident,
+    reading: &SensorLogData,
+) -> Option<()> {
+    push_notification(
+        incident.owner_name.clone(),
+        serde_json::to_string(&incident_alert(incident, reading)).ok()?,
+        mongoc,
+    )
+    .await
+}
+
+fn publish_incident_update(incident: &Incident, reading: &SensorLogData) {
+    publish_event(IncidentUpdated {
+        owner_name: incident.owner_name.clone(),
+        alert: incident_alert(incident, reading),
+    });
+}
+
+pub fn new_incident(
+    kind: IncidentKind,
+    owner_name: String,
+    sensor_type: SensorDataType,
+    room_name: Option<String>,
+    reading: &SensorLogData,
+) -> Incident {
+    Incident {
+        id: uuid::Uuid::now_v7().into(),
+        kind,
+        owner_name,
+        device_id: reading.id,
+        component: reading.component,
+        sensor_type,
+        room_name,
+        status: IncidentStatus::Open,
+        opened_at: reading.timestamp,
+        last_reading_at: reading.timestamp,
+        silenced_until: None,
+        reading_count: 1,
+        peak_value: reading.value,
+        timeline: vec![IncidentEvent::Opened {
+            timestamp: reading.timestamp,
+            value: reading.value,
+        }],
+    }
+}
+
+// Opens the incident, or folds the reading into the matching unresolved one.
+// Repeated pushes are suppressed while that incident is silenced.
+pub async fn raise_incident(
+    mongoc: &mut mongodb::Client,
+    incident: Incident,
+    reading: &SensorLogData,
+) -> Option<()> {
+    let incident_coll = get_incident_coll(mongoc);
+    let active_incident = incident_coll
+        .find_one(active_incident_filter(&incident)?, None)
+        .await
+        .ok()?;
+
+    match active_incident {
+        None => {
+            incident_coll.insert_one(&incident, None).await.ok()?;
+            publish_incident_update(&incident, reading);
+            notify_incident(mongoc, &incident, reading).await
+        }
+        Some(active_incident) => {
+            incident_coll
+                .update_one(
+                    doc! { "id": active_incident.id.clone() },
+                    doc! {
+                        "$inc": { "reading_count": 1 },
+                        "$max": { "peak_value": reading.value },
+                        "$set": { "last_reading_at": to_bson(&reading.timestamp).ok()? },
+                    },
+                    None,
+                )
+                .await
+                .ok()?;
+            publish_incident_update(&active_incident, reading);
+
+            if active_incident
+                .silenced_until
+                .is_some_and(|until| until > SystemTime::now())
+            {
+                return Some(());
+            }
+            notify_incident(mongoc, &active_incident, reading).await
+        }
+    }
+}
+
+pub async fn resolve_incident(
+    mongoc: &mongodb::Client,
+    incident: &Incident,
+    timestamp: SystemTime,
+) -> Option<()> {
+    let result = get_incident_coll(mongoc)
+        .update_many(
+            active_incident_filter(incident)?,
+            doc! {
+                "$set": { "status": to_bson(&IncidentStatus::Resolved).ok()? },
+                "$push": { "timeline": to_bson(&IncidentEvent::Resolved { timestamp, by: None }).ok()? },
+            },
+            None,
+        )
+        .await
+        .ok()?;
+    if result.modified_count > 0 {
+        publish_event(IncidentResolved {
+            owner_name: incident.owner_name.clone(),
+            device_id: incident.device_id,
+            component: incident.component,
+            incident_kind: incident.kind,
+            sensor_type: incident.sensor_type,
+            room_name: incident.room_name.clone(),
+        });
+    }
+    Some(())
+}
+
+// Consecutive UNSAFE readings of a component are grouped into one incident,
+// which is resolved by the first SAFE reading that follows them.
+pub async fn track_incident(
+    mongoc: &mut mongodb::Client,
+    owner_name: String,
+    sensor_type: SensorDataType,
+    room_name: Option<String>,
+    reading: &SensorLogData,
+) -> Option<()> {
+    let incident = new_incident(
+        IncidentKind::Threshold,
+        owner_name,
+        sensor_type,
+        room_name,
+        reading,
+    );
+    match reading.alert {
+        FireStatus::UNSAFE => raise_incident(mongoc, incident, reading).await,
+        FireStatus::SAFE => resolve_incident(mongoc, &incident, reading.timestamp).await,
+    }
+}
@@ -1,4 +1,7 @@
+pub mod analysis;
 pub mod feature;
+pub mod incidents;
 pub mod mqtt_messages;
+pub mod thresholds;
 
 pub use feature::*;
@@ -232,7 +232,7 @@ mod deserialize_tests {
                 SensorData {
                     id: 1,
                     component: 6,
-                    value: 20,
+                    value: 20.0,
                     alert: FireStatus::SAFE,
                 }
             ],
@@ -391,7 +391,7 @@ mod serialize_tests {
                     {
                         "id": 0,
                         "component": 8,
-                        "value": 460,
+                        "value": 460.0,
                         "alert": 0
                     }
                 ],
@@ -399,31 +399,31 @@ mod serialize_tests {
                     {
                         "id": 0,
                         "component": 0,
-                        "value": 120,
+                        "value": 120.0,
                         "alert": 0
                     },
                     {
                         "id": 0,
                         "component": 1,
-                        "value": 240,
+                        "value": 240.0,
                         "alert": 0
                     },
                     {
                         "id": 1,
                         "component": 0,
-                        "value": 120,
+                        "value": 120.0,
                         "alert": 0
                     },
                     {
                         "id": 2,
                         "component": 0,
-                        "value": 120,
+                        "value": 120.0,
                         "alert": 0
                     },
                     {
                         "id": 0,
                         "component": 0,
-                        "value": 120,
+                        "value": 120.0,
                         "alert": 0
                     }
                 ],
@@ -431,13 +431,13 @@ mod serialize_tests {
                     {
                         "id": 0,
                         "component": 4,
-                        "value": 460,
+                        "value": 460.0,
                         "alert": 0
                     },
                     {
                         "id": 1,
                         "component": 4,
-                        "value": 460,
+                        "value": 460.0,
                         "alert": 0
                     }
                 ],
@@ -445,13 +445,13 @@ mod serialize_tests {
                     {
                         "id": 2,
                         "component": 2,
-                        "value": 460,
+                        "value": 460.0,
                         "alert": 0
                     },
                     {
                         "id": 3,
                         "component": 2,
-                        "value": 460,
+                        "value": 460.0,
                         "alert": 0
                     }
                 ],
@@ -459,7 +459,7 @@ mod serialize_tests {
                     {
                         "id": 1,
                         "component": 10,
-                        "value": 1,
+                        "value": 1.0,
                         "alert": 0
                     }
                 ],
@@ -469,7 +469,7 @@ mod serialize_tests {
                     {
                         "id": 1,
                         "component": 6,
-                        "value": 20,
+                        "value": 20.0,
                         "alert": 0,
                     }
                 ],
@@ -508,7 +508,7 @@ mod serialize_tests {
                     {
                         "id": 0,
                         "component": 8,
-                        "value": 460,
+                        "value": 460.0,
                         "alert": 1
                     }
                 ],
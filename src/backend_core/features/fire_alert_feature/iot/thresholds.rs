@@ -0,0 +1,104 @@
+use crate::backend_core::features::fire_alert_feature::{
+    fixed_value::default_threshold,
+    models::{AlertThreshold, FireStatus, SensorDataType},
+};
+
+// A component specific threshold wins over the user's per-type threshold,
+// which in turn wins over the built-in default.
+pub fn resolve_threshold(
+    thresholds: &[AlertThreshold],
+    sensor_type: SensorDataType,
+    component: u32,
+) -> Option<f32> {
+    if !sensor_type.is_measurement() {
+        return None;
+    }
+
+    thresholds
+        .iter()
+        .find(|t| t.sensor_type == sensor_type && t.component == Some(component))
+        .or_else(|| {
+            thresholds
+                .iter()
+                .find(|t| t.sensor_type == sensor_type && t.component.is_none())
+        })
+        .map(|t| t.value)
+        .or_else(|| default_threshold(sensor_type))
+}
+
+pub fn evaluate_reading(value: f32, threshold: f32) -> FireStatus {
+    if value >= threshold {
+        FireStatus::UNSAFE
+    } else {
+        FireStatus::SAFE
+    }
+}
+
+pub fn combine_verdicts(device_alert: FireStatus, server_alert: Option<FireStatus>) -> FireStatus {
+    if device_alert == FireStatus::UNSAFE || server_alert == Some(FireStatus::UNSAFE) {
+        FireStatus::UNSAFE
+    } else {
+        FireStatus::SAFE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{combine_verdicts, evaluate_reading, resolve_threshold};
+    use crate::backend_core::features::fire_alert_feature::{
+        fixed_value::DEFAULT_HEAT_THRESHOLD,
+        models::{AlertThreshold, FireStatus, SensorDataType},
+    };
+
+    fn threshold(sensor_type: SensorDataType, component: Option<u32>, value: f32) -> AlertThreshold {
+        AlertThreshold {
+            owner_name: String::from("user@example.com"),
+            sensor_type,
+            component,
+            value,
+        }
+    }
+
+    #[test]
+    fn component_threshold_overrides_type_threshold() {
+        let thresholds = vec![
+            threshold(SensorDataType::Smoke, None, 100.0),
+            threshold(SensorDataType::Smoke, Some(3), 400.0),
+        ];
+
+        assert_eq!(resolve_threshold(&thresholds, SensorDataType::Smoke, 3), Some(400.0));
+        assert_eq!(resolve_threshold(&thresholds, SensorDataType::Smoke, 4), Some(100.0));
+    }
+
+    #[test]
+    fn falls_back_to_default_threshold() {
+        assert_eq!(
+            resolve_threshold(&[], SensorDataType::Heat, 1),
+            Some(DEFAULT_HEAT_THRESHOLD)
+        );
+        assert_eq!(resolve_threshold(&[], SensorDataType::Fire, 1), None);
+        assert_eq!(
+            resolve_threshold(
+                &[threshold(SensorDataType::FireButton, None, 1.0)],
+                SensorDataType::FireButton,
+                1
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn server_verdict_can_only_raise_the_alert() {
+        assert_eq!(evaluate_reading(57.0, 57.0), FireStatus::UNSAFE);
+        assert_eq!(evaluate_reading(20.0, 57.0), FireStatus::SAFE);
+        assert_eq!(
+            combine_verdicts(FireStatus::SAFE, Some(FireStatus::UNSAFE)),
+            FireStatus::UNSAFE
+        );
+        assert_eq!(
+            combine_verdicts(FireStatus::UNSAFE, Some(FireStatus::SAFE)),
+            FireStatus::UNSAFE
+        );
+        assert_eq!(combine_verdicts(FireStatus::SAFE, None), FireStatus::SAFE);
+    }
+}
@@ -0,0 +1,91 @@
+use mongodb::{
+    bson::{doc, Document},
+    Collection, IndexModel,
+};
+
+use crate::backend_core::migrations::insert_once;
+
+use super::models::{FireLog, SensorDataType, SensorLogEntry};
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    let sensor_log_coll: Collection<SensorLogEntry> = mongoc
+        .default_database()
+        .unwrap()
+        .collection("fire_sensor_logs");
+
+    sensor_log_coll
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! {
+                        "owner_name": 1,
+                        "sensor_type": 1,
+                        "timestamp.secs_since_epoch": -1,
+                        "timestamp.nanos_since_epoch": -1,
+                    })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! {
+                        "owner_name": 1,
+                        "component": 1,
+                        "timestamp.secs_since_epoch": -1,
+                        "timestamp.nanos_since_epoch": -1,
+                    })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Moves the per-owner log arrays of `fire_alerts` into one document per
+// reading in `fire_sensor_logs`, dropping each owner document once copied.
+// Readings are copied under ids made of the owner and their position, so a
+// rerun after a crash before the drop does not copy them twice.
+pub async fn split_log_arrays(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    let database = mongoc.default_database().unwrap();
+    let fire_log_coll: Collection<FireLog> = database.collection("fire_alerts");
+    let sensor_log_coll: Collection<Document> = database.collection("fire_sensor_logs");
+
+    let mut cursor = fire_log_coll.find(Document::new(), None).await?;
+    while cursor.advance().await? {
+        let fire_log = match cursor.deserialize_current() {
+            Ok(fire_log) => fire_log,
+            Err(e) => {
+                eprintln!("Skipping malformed fire log document: {}", e);
+                continue;
+            }
+        };
+
+        let owner_name = fire_log.owner_name.clone();
+        let entries = [
+            (SensorDataType::Fire, fire_log.fire_logs),
+            (SensorDataType::Smoke, fire_log.smoke_logs),
+            (SensorDataType::CO, fire_log.co_logs),
+            (SensorDataType::Heat, fire_log.heat_logs),
+            (SensorDataType::FireButton, fire_log.button_logs),
+            (SensorDataType::FireLight, fire_log.light_logs),
+            (SensorDataType::FireBuzzer, fire_log.buzzer_logs),
+            (SensorDataType::LPG, fire_log.lpg_logs),
+        ]
+        .into_iter()
+        .flat_map(|(sensor_type, logs)| {
+            let owner_name = owner_name.clone();
+            logs.into_iter().map(move |data| SensorLogEntry {
+                owner_name: owner_name.clone(),
+                sensor_type,
+                data,
+            })
+        })
+        .enumerate()
+        .map(|(index, entry)| (format!("split:{owner_name}:{index}"), entry))
+        .collect::<Vec<_>>();
+
+        insert_once(&sensor_log_coll, entries).await?;
+        fire_log_coll
+            .delete_one(doc! { "owner_name": owner_name }, None)
+            .await?;
+    }
+    Ok(())
+}
@@ -1,5 +1,6 @@
 pub mod fixed_value;
 mod iot;
+pub mod migrations;
 pub mod models;
 mod notifications;
 mod web;
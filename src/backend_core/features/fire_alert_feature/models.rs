@@ -3,26 +3,54 @@ use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 use std::time::SystemTime;
 
-#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+use crate::backend_core::rollups::Resolution;
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
 pub struct SensorLogData {
     pub id: u32,
     pub component: u32,
     pub value: f32,
     pub alert: FireStatus,
+    #[serde(default)]
+    pub device_alert: Option<FireStatus>,
+    #[serde(default)]
+    pub server_alert: Option<FireStatus>,
     pub timestamp: SystemTime,
 }
 
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub enum SensorDataType {
+    #[serde(rename = "fire")]
     Fire,
+    #[serde(rename = "smoke")]
     Smoke,
+    #[serde(rename = "co")]
     CO,
+    #[serde(rename = "heat")]
     Heat,
+    #[serde(rename = "fire-button")]
     FireButton,
+    #[serde(rename = "fire-light")]
     FireLight,
+    #[serde(rename = "fire-buzzer")]
     FireBuzzer,
+    #[serde(rename = "lpg")]
     LPG,
 }
 
+impl SensorDataType {
+    pub fn is_measurement(&self) -> bool {
+        matches!(
+            self,
+            SensorDataType::Fire
+                | SensorDataType::Smoke
+                | SensorDataType::CO
+                | SensorDataType::Heat
+                | SensorDataType::LPG
+        )
+    }
+}
+
 #[derive(Serialize_repr, Deserialize_repr, PartialEq, Eq, JsonSchema, Clone, Copy, Debug)]
 #[repr(u8)]
 pub enum FireStatus {
@@ -30,16 +58,49 @@ pub enum FireStatus {
     UNSAFE = 1,
 }
 
+// One document per reading in the `fire_sensor_logs` collection.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SensorLogEntry {
+    pub owner_name: String,
+    pub sensor_type: SensorDataType,
+    #[serde(flatten)]
+    pub data: SensorLogData,
+}
+
+// Min/max/avg of one sensor over a bucket of `fire_sensor_rollups`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct SensorRollup {
+    pub sensor_type: SensorDataType,
+    pub id: u32,
+    pub component: u32,
+    pub resolution: Resolution,
+    pub timestamp: SystemTime,
+    pub min: f32,
+    pub max: f32,
+    pub avg: f32,
+    pub count: u32,
+}
+
+// Legacy layout of the `fire_alerts` collection, kept only so that the
+// migration to `fire_sensor_logs` can read it.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct FireLog {
     pub owner_name: String,
+    #[serde(default)]
     pub fire_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub smoke_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub co_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub heat_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub button_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub light_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub buzzer_logs: Vec<SensorLogData>,
+    #[serde(default)]
     pub lpg_logs: Vec<SensorLogData>,
 }
 
@@ -50,3 +111,78 @@ pub struct Pagination {
     pub offset: Option<u32>,
     pub limit: Option<i64>,
 }
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AlertThreshold {
+    pub owner_name: String,
+    pub sensor_type: SensorDataType,
+    pub component: Option<u32>,
+    pub value: f32,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum IncidentStatus {
+    #[serde(rename = "open")]
+    Open,
+    #[serde(rename = "acknowledged")]
+    Acknowledged,
+    #[serde(rename = "resolved")]
+    Resolved,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum IncidentKind {
+    #[default]
+    #[serde(rename = "threshold")]
+    Threshold,
+    #[serde(rename = "rate-of-rise")]
+    RateOfRise,
+    #[serde(rename = "confirmed-fire")]
+    ConfirmedFire,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum IncidentEvent {
+    Opened { timestamp: SystemTime, value: f32 },
+    Acknowledged { timestamp: SystemTime, by: String },
+    Silenced { timestamp: SystemTime, by: String, until: SystemTime },
+    Resolved { timestamp: SystemTime, by: Option<String> },
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Incident {
+    pub id: String,
+    #[serde(default)]
+    pub kind: IncidentKind,
+    pub owner_name: String,
+    pub device_id: u32,
+    pub component: u32,
+    pub sensor_type: SensorDataType,
+    pub room_name: Option<String>,
+    pub status: IncidentStatus,
+    pub opened_at: SystemTime,
+    pub last_reading_at: SystemTime,
+    pub silenced_until: Option<SystemTime>,
+    pub reading_count: u32,
+    pub peak_value: f32,
+    pub timeline: Vec<IncidentEvent>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct IncidentAlert {
+    pub incident_id: String,
+    pub kind: IncidentKind,
+    pub status: IncidentStatus,
+    pub sensor_type: SensorDataType,
+    pub room_name: Option<String>,
+    pub reading: SensorLogData,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct AnalysisSettings {
+    pub owner_name: String,
+    pub rate_of_rise_per_minute: f32,
+    pub rate_of_rise_window_secs: u64,
+    pub correlation_window_secs: u64,
+}
@@ -0,0 +1,161 @@
+use aide::axum::{routing::{get_with, put_with}, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, options::ReplaceOptions, Collection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::fire_alert_feature::{
+        fixed_value::{
+            DEFAULT_CORRELATION_WINDOW_SECS, DEFAULT_RATE_OF_RISE_PER_MINUTE,
+            DEFAULT_RATE_OF_RISE_WINDOW_SECS, MAX_ANALYSIS_WINDOW_SECS,
+        },
+        models::AnalysisSettings,
+    },
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetAnalysisSettingsQuery {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct AnalysisSettingsBody {
+    email: String,
+    rate_of_rise_per_minute: f32,
+    rate_of_rise_window_secs: u64,
+    correlation_window_secs: u64,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct AnalysisSettingsResponse {
+    message: String,
+    settings: Option<AnalysisSettings>,
+}
+
+async fn get_settings_coll() -> Collection<AnalysisSettings> {
+    get_collection("fire_alert_analysis_settings").await
+}
+
+async fn get_analysis_settings_handler(
+    Query(GetAnalysisSettingsQuery { email }): Query<GetAnalysisSettingsQuery>,
+) -> impl IntoApiResponse {
+    match get_settings_coll()
+        .await
+        .find_one(doc! { "owner_name": email.clone() }, None)
+        .await
+    {
+        Ok(settings) => (
+            StatusCode::OK,
+            Json(AnalysisSettingsResponse {
+                message: String::from("Successfully fetch analysis settings"),
+                settings: Some(settings.unwrap_or(AnalysisSettings {
+                    owner_name: email,
+                    rate_of_rise_per_minute: DEFAULT_RATE_OF_RISE_PER_MINUTE,
+                    rate_of_rise_window_secs: DEFAULT_RATE_OF_RISE_WINDOW_SECS,
+                    correlation_window_secs: DEFAULT_CORRELATION_WINDOW_SECS,
+                })),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(AnalysisSettingsResponse {
+                message: String::from("Unexpected error while fetching analysis settings"),
+                settings: None,
+            }),
+        ),
+    }
+}
+
+async fn put_analysis_settings_handler(
+    Json(AnalysisSettingsBody {
+        email,
+        rate_of_rise_per_minute,
+        rate_of_rise_window_secs,
+        correlation_window_secs,
+    }): Json<AnalysisSettingsBody>,
+) -> impl IntoApiResponse {
+    if rate_of_rise_per_minute <= 0.0 || rate_of_rise_window_secs == 0 || correlation_window_secs == 0 {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(AnalysisSettingsResponse {
+                message: String::from("Rate of rise and windows must be positive"),
+                settings: None,
+            }),
+        );
+    }
+    if rate_of_rise_window_secs > MAX_ANALYSIS_WINDOW_SECS || correlation_window_secs > MAX_ANALYSIS_WINDOW_SECS {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(AnalysisSettingsResponse {
+                message: format!("Windows must be at most {MAX_ANALYSIS_WINDOW_SECS} seconds"),
+                settings: None,
+            }),
+        );
+    }
+
+    let settings = AnalysisSettings {
+        owner_name: email.clone(),
+        rate_of_rise_per_minute,
+        rate_of_rise_window_secs,
+        correlation_window_secs,
+    };
+    match get_settings_coll()
+        .await
+        .replace_one(
+            doc! { "owner_name": email },
+            &settings,
+            ReplaceOptions::builder().upsert(true).build(),
+        )
+        .await
+    {
+        Ok(_) => (
+            StatusCode::OK,
+            Json(AnalysisSettingsResponse {
+                message: String::from("Analysis settings updated successfully"),
+                settings: Some(settings),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(AnalysisSettingsResponse {
+                message: String::from("Failed to update analysis settings"),
+                settings: None,
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/analysis-settings",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_analysis_settings_handler, |op| {
+                op.description("Get rate-of-rise and smoke correlation settings by user email")
+                    .tag("Fire alert")
+                    .response::<200, Json<AnalysisSettingsResponse>>()
+                    .response::<403, Json<AnalysisSettingsResponse>>()
+                    .response::<500, Json<AnalysisSettingsResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/analysis-settings",
+            Policy::Role(HouseholdRole::Member),
+            put_with(put_analysis_settings_handler, |op| {
+                op.description("Set the rate-of-rise in degree Celsius per minute, its window and the smoke correlation window")
+                    .tag("Fire alert")
+                    .response::<200, Json<AnalysisSettingsResponse>>()
+                    .response::<400, Json<AnalysisSettingsResponse>>()
+                    .response::<403, Json<AnalysisSettingsResponse>>()
+                    .response::<500, Json<AnalysisSettingsResponse>>()
+            }),
+        )
+}
@@ -1,25 +1,21 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
-};
-use mongodb::{
-    bson::{self, doc, Bson},
-    options::AggregateOptions,
-    Collection,
+    http::StatusCode,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backend_core::features::fire_alert_feature::{
-        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
-        models::{FireLog, Pagination, SensorLogData},
+    backend_core::{
+        features::fire_alert_feature::models::{Pagination, SensorDataType, SensorLogData, SensorRollup},
+        rollups::Resolution,
     },
     json::Json,
 };
 
-use super::MONGOC;
+use super::{find_sensor_logs, find_sensor_rollups};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetButtonLogsOfUserQuery {
@@ -28,119 +24,72 @@ pub struct GetButtonLogsOfUserQuery {
     end_time: Option<i32>,
     offset: Option<u32>,
     limit: Option<i64>,
+    resolution: Option<Resolution>,
 }
 
 #[derive(Serialize, JsonSchema)]
 pub struct GetButtonLogsOfUserResponse {
     message: String,
     button_logs: Option<Vec<SensorLogData>>,
+    rollups: Option<Vec<SensorRollup>>,
     pagination: Pagination,
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetButtonLogsOfUserQuery {
         email,
         start_time,
         end_time,
         offset,
         limit,
+        resolution,
     }): Query<GetButtonLogsOfUserQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetButtonLogsOfUserResponse {
-                message: String::from("Forbidden"),
-                button_logs: None,
-                pagination: Pagination {
-                    start_time,
-                    end_time,
-                    offset,
-                    limit,
-                },
-            }),
-        );
-    }
-
-    let fire_coll: Collection<FireLog> = {
-        let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
-    };
-
-    let query_doc = doc! {
-        "owner_name": email.clone(),
-    };
-
-    let pipeline = vec![
-        doc! {
-            "$match": query_doc,
-        },
-        doc! {
-            "$unwind": "$button_logs"
-        },
-        doc! {
-            "$sort": {
-                "button_logs.timestamp.secs_since_epoch": -1,
-                "button_logs.timestamp.nanos_since_epoch": -1
-            }
-        },
-        doc! {
-            "$match": {
-                "$and": [
-                    { "button_logs.timestamp.secs_since_epoch": { "$gte": start_time.unwrap_or(0) } },
-                    { "button_logs.timestamp.secs_since_epoch": { "$lte": end_time.unwrap_or(i32::MAX) } }
-            ]
-        }
-            },
-        doc! {
-            "$group": {
-                "_id": "$_id",
-                "button_logs": { "$push": "$button_logs" }
-            }
-        },
-        doc! {
-            "$project": {
-                "button_logs": {
-                    "$slice": ["$button_logs", offset.unwrap_or(0), limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)],
-                }
+    if let Some(resolution) = resolution.filter(|resolution| *resolution != Resolution::Raw) {
+        return match find_sensor_rollups(&email, &[SensorDataType::FireButton], resolution, start_time, end_time, offset, limit).await {
+            Ok(rollups) => (
+                StatusCode::OK,
+                Json(GetButtonLogsOfUserResponse {
+                    message: String::from("Successfully fetch button rollups"),
+                    button_logs: None,
+                    rollups: Some(rollups),
+                    pagination: Pagination {
+                        start_time,
+                        end_time,
+                        offset,
+                        limit,
+                    },
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error fetching sensor rollups: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(GetButtonLogsOfUserResponse {
+                        message: String::from("Unexpected error while fetching button rollups"),
+                        button_logs: None,
+                        rollups: None,
+                        pagination: Pagination {
+                            start_time,
+                            end_time,
+                            offset,
+                            limit,
+                        },
+                    }),
+                )
             }
-        },
-    ];
-
-    let aggregate_options = AggregateOptions::builder().build();
+        };
+    }
 
-    match fire_coll.aggregate(pipeline, Some(aggregate_options)).await {
-        Ok(mut cursor) => {
-            let mut button_logs = Vec::new();
-            while cursor.advance().await.unwrap_or(false) {
-                match cursor.deserialize_current() {
-                    Ok(document) => {
-                        if let Some(Bson::Array(button_logs_array)) = document.get("button_logs") {
-                            let logs: Vec<SensorLogData> = button_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            button_logs.extend(logs);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error deserializing document: {}", e);
-                    }
-                }
-            }
+    match find_sensor_logs(&email, SensorDataType::FireButton, start_time, end_time, offset, limit).await {
+        Ok(button_logs) => {
             if button_logs.is_empty() {
                 (
                     StatusCode::OK,
                     Json(GetButtonLogsOfUserResponse {
                         message: format!("Your fire matrix hasn't had any data"),
                         button_logs: None,
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -155,6 +104,7 @@ async fn handler(
                     Json(GetButtonLogsOfUserResponse {
                         message: format!("Successfully fetched fire log data"),
                         button_logs: Some(button_logs),
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -166,12 +116,13 @@ async fn handler(
             }
         }
         Err(e) => {
-            eprintln!("Error executing aggregation pipeline: {}", e);
+            eprintln!("Error fetching sensor logs: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(GetButtonLogsOfUserResponse {
                     message: format!("Unexpected error while fetching fire log data"),
                     button_logs: None,
+                    rollups: None,
                     pagination: Pagination {
                         start_time,
                         end_time,
@@ -185,8 +136,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/button-logs",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get button log by user email")
                 .tag("Fire alert")
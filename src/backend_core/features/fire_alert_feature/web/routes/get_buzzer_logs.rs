@@ -1,25 +1,21 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
-};
-use mongodb::{
-    bson::{self, doc, Bson},
-    options::AggregateOptions,
-    Collection,
+    http::StatusCode,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backend_core::features::fire_alert_feature::{
-        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
-        models::{FireLog, Pagination, SensorLogData},
+    backend_core::{
+        features::fire_alert_feature::models::{Pagination, SensorDataType, SensorLogData, SensorRollup},
+        rollups::Resolution,
     },
     json::Json,
 };
 
-use super::MONGOC;
+use super::{find_sensor_logs, find_sensor_rollups};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetBuzzerLogsOfUserQuery {
@@ -28,119 +24,72 @@ pub struct GetBuzzerLogsOfUserQuery {
     end_time: Option<i32>,
     offset: Option<u32>,
     limit: Option<i64>,
+    resolution: Option<Resolution>,
 }
 
 #[derive(Serialize, JsonSchema)]
 pub struct GetBuzzerLogsOfUserResponse {
     message: String,
     buzzer_logs: Option<Vec<SensorLogData>>,
+    rollups: Option<Vec<SensorRollup>>,
     pagination: Pagination,
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetBuzzerLogsOfUserQuery {
         email,
         start_time,
         end_time,
         offset,
         limit,
+        resolution,
     }): Query<GetBuzzerLogsOfUserQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetBuzzerLogsOfUserResponse {
-                message: String::from("Forbidden"),
-                buzzer_logs: None,
-                pagination: Pagination {
-                    start_time,
-                    end_time,
-                    offset,
-                    limit,
-                },
-            }),
-        );
-    }
-
-    let fire_coll: Collection<FireLog> = {
-        let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
-    };
-
-    let query_doc = doc! {
-        "owner_name": email.clone(),
-    };
-
-    let pipeline = vec![
-        doc! {
-            "$match": query_doc,
-        },
-        doc! {
-            "$unwind": "$buzzer_logs"
-        },
-        doc! {
-            "$sort": {
-                "buzzer_logs.timestamp.secs_since_epoch": -1,
-                "buzzer_logs.timestamp.nanos_since_epoch": -1
-            }
-        },
-        doc! {
-            "$match": {
-                "$and": [
-                    { "buzzer_logs.timestamp.secs_since_epoch": { "$gte": start_time.unwrap_or(0) } },
-                    { "buzzer_logs.timestamp.secs_since_epoch": { "$lte": end_time.unwrap_or(i32::MAX) } }
-            ]
-        }
-            },
-        doc! {
-            "$group": {
-                "_id": "$_id",
-                "buzzer_logs": { "$push": "$buzzer_logs" }
-            }
-        },
-        doc! {
-            "$project": {
-                "buzzer_logs": {
-                    "$slice": ["$buzzer_logs", offset.unwrap_or(0), limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)],
-                }
+    if let Some(resolution) = resolution.filter(|resolution| *resolution != Resolution::Raw) {
+        return match find_sensor_rollups(&email, &[SensorDataType::FireBuzzer], resolution, start_time, end_time, offset, limit).await {
+            Ok(rollups) => (
+                StatusCode::OK,
+                Json(GetBuzzerLogsOfUserResponse {
+                    message: String::from("Successfully fetch buzzer rollups"),
+                    buzzer_logs: None,
+                    rollups: Some(rollups),
+                    pagination: Pagination {
+                        start_time,
+                        end_time,
+                        offset,
+                        limit,
+                    },
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error fetching sensor rollups: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(GetBuzzerLogsOfUserResponse {
+                        message: String::from("Unexpected error while fetching buzzer rollups"),
+                        buzzer_logs: None,
+                        rollups: None,
+                        pagination: Pagination {
+                            start_time,
+                            end_time,
+                            offset,
+                            limit,
+                        },
+                    }),
+                )
             }
-        },
-    ];
-
-    let aggregate_options = AggregateOptions::builder().build();
+        };
+    }
 
-    match fire_coll.aggregate(pipeline, Some(aggregate_options)).await {
-        Ok(mut cursor) => {
-            let mut buzzer_logs = Vec::new();
-            while cursor.advance().await.unwrap_or(false) {
-                match cursor.deserialize_current() {
-                    Ok(document) => {
-                        if let Some(Bson::Array(buzzer_logs_array)) = document.get("button_logs") {
-                            let logs: Vec<SensorLogData> = buzzer_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            buzzer_logs.extend(logs);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error deserializing document: {}", e);
-                    }
-                }
-            }
+    match find_sensor_logs(&email, SensorDataType::FireBuzzer, start_time, end_time, offset, limit).await {
+        Ok(buzzer_logs) => {
             if buzzer_logs.is_empty() {
                 (
                     StatusCode::OK,
                     Json(GetBuzzerLogsOfUserResponse {
                         message: format!("Your fire matrix hasn't had any data"),
                         buzzer_logs: None,
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -155,6 +104,7 @@ async fn handler(
                     Json(GetBuzzerLogsOfUserResponse {
                         message: format!("Successfully fetched fire log data"),
                         buzzer_logs: Some(buzzer_logs),
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -166,12 +116,13 @@ async fn handler(
             }
         }
         Err(e) => {
-            eprintln!("Error executing aggregation pipeline: {}", e);
+            eprintln!("Error fetching sensor logs: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(GetBuzzerLogsOfUserResponse {
                     message: format!("Unexpected error while fetching fire log data"),
                     buzzer_logs: None,
+                    rollups: None,
                     pagination: Pagination {
                         start_time,
                         end_time,
@@ -185,8 +136,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/buzzer-logs",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get buzzer log by user email")
                 .tag("Fire alert")
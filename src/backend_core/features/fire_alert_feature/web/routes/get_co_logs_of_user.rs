@@ -1,25 +1,21 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
-};
-use mongodb::{
-    bson::{self, doc, Bson},
-    options::AggregateOptions,
-    Collection,
+    http::StatusCode,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backend_core::features::fire_alert_feature::{
-        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
-        models::{FireLog, Pagination, SensorLogData},
+    backend_core::{
+        features::fire_alert_feature::models::{Pagination, SensorDataType, SensorLogData, SensorRollup},
+        rollups::Resolution,
     },
     json::Json,
 };
 
-use super::MONGOC;
+use super::{find_sensor_logs, find_sensor_rollups};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetCOLogsOfUserQuery {
@@ -28,119 +24,72 @@ pub struct GetCOLogsOfUserQuery {
     end_time: Option<i32>,
     offset: Option<u32>,
     limit: Option<i64>,
+    resolution: Option<Resolution>,
 }
 
 #[derive(Serialize, JsonSchema)]
 pub struct GetCOLogsOfUserResponse {
     message: String,
     co_logs: Option<Vec<SensorLogData>>,
+    rollups: Option<Vec<SensorRollup>>,
     pagination: Pagination,
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetCOLogsOfUserQuery {
         email,
         start_time,
         end_time,
         offset,
         limit,
+        resolution,
     }): Query<GetCOLogsOfUserQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetCOLogsOfUserResponse {
-                message: String::from("Forbidden"),
-                co_logs: None,
-                pagination: Pagination {
-                    start_time,
-                    end_time,
-                    offset,
-                    limit,
-                },
-            }),
-        );
-    }
-
-    let fire_coll: Collection<FireLog> = {
-        let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
-    };
-
-    let query_doc = doc! {
-        "owner_name": email.clone(),
-    };
-
-    let pipeline = vec![
-        doc! {
-            "$match": query_doc,
-        },
-        doc! {
-            "$unwind": "$co_logs"
-        },
-        doc! {
-            "$sort": {
-                "co_logs.timestamp.secs_since_epoch": -1,
-                "co_logs.timestamp.nanos_since_epoch": -1
-            }
-        },
-        doc! {
-            "$match": {
-                "$and": [
-                    { "co_logs.timestamp.secs_since_epoch": { "$gte": start_time.unwrap_or(0) } },
-                    { "co_logs.timestamp.secs_since_epoch": { "$lte": end_time.unwrap_or(i32::MAX) } }
-            ]
-        }
-            },
-        doc! {
-            "$group": {
-                "_id": "$_id",
-                "co_logs": { "$push": "$co_logs" }
-            }
-        },
-        doc! {
-            "$project": {
-                "co_logs": {
-                    "$slice": ["$co_logs", offset.unwrap_or(0), limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)],
-                }
+    if let Some(resolution) = resolution.filter(|resolution| *resolution != Resolution::Raw) {
+        return match find_sensor_rollups(&email, &[SensorDataType::CO], resolution, start_time, end_time, offset, limit).await {
+            Ok(rollups) => (
+                StatusCode::OK,
+                Json(GetCOLogsOfUserResponse {
+                    message: String::from("Successfully fetch co rollups"),
+                    co_logs: None,
+                    rollups: Some(rollups),
+                    pagination: Pagination {
+                        start_time,
+                        end_time,
+                        offset,
+                        limit,
+                    },
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error fetching sensor rollups: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(GetCOLogsOfUserResponse {
+                        message: String::from("Unexpected error while fetching co rollups"),
+                        co_logs: None,
+                        rollups: None,
+                        pagination: Pagination {
+                            start_time,
+                            end_time,
+                            offset,
+                            limit,
+                        },
+                    }),
+                )
             }
-        },
-    ];
-
-    let aggregate_options = AggregateOptions::builder().build();
+        };
+    }
 
-    match fire_coll.aggregate(pipeline, Some(aggregate_options)).await {
-        Ok(mut cursor) => {
-            let mut co_logs = Vec::new();
-            while cursor.advance().await.unwrap_or(false) {
-                match cursor.deserialize_current() {
-                    Ok(document) => {
-                        if let Some(Bson::Array(co_logs_array)) = document.get("co_logs") {
-                            let logs: Vec<SensorLogData> = co_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            co_logs.extend(logs);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error deserializing document: {}", e);
-                    }
-                }
-            }
+    match find_sensor_logs(&email, SensorDataType::CO, start_time, end_time, offset, limit).await {
+        Ok(co_logs) => {
             if co_logs.is_empty() {
                 (
                     StatusCode::OK,
                     Json(GetCOLogsOfUserResponse {
                         message: format!("Your fire matrix hasn't had any data"),
                         co_logs: None,
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -155,6 +104,7 @@ async fn handler(
                     Json(GetCOLogsOfUserResponse {
                         message: format!("Successfully fetched fire log data"),
                         co_logs: Some(co_logs),
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -166,12 +116,13 @@ async fn handler(
             }
         }
         Err(e) => {
-            eprintln!("Error executing aggregation pipeline: {}", e);
+            eprintln!("Error fetching sensor logs: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(GetCOLogsOfUserResponse {
                     message: format!("Unexpected error while fetching fire log data"),
                     co_logs: None,
+                    rollups: None,
                     pagination: Pagination {
                         start_time,
                         end_time,
@@ -185,8 +136,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/co-logs",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get co log by user email")
                 .tag("Fire alert")
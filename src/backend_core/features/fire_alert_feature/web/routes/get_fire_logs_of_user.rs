@@ -1,25 +1,21 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
-};
-use mongodb::{
-    bson::{self, doc, Bson},
-    options::AggregateOptions,
-    Collection,
+    http::StatusCode,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backend_core::features::fire_alert_feature::{
-        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
-        models::{FireLog, Pagination, SensorLogData},
+    backend_core::{
+        features::fire_alert_feature::models::{Pagination, SensorDataType, SensorLogData, SensorRollup},
+        rollups::Resolution,
     },
     json::Json,
 };
 
-use super::MONGOC;
+use super::{find_sensor_logs, find_sensor_rollups};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetFireLogsOfUserQuery {
@@ -28,119 +24,72 @@ pub struct GetFireLogsOfUserQuery {
     end_time: Option<i32>,
     offset: Option<u32>,
     limit: Option<i64>,
+    resolution: Option<Resolution>,
 }
 
 #[derive(Serialize, JsonSchema)]
 pub struct GetFireLogsOfUserResponse {
     message: String,
     fire_logs: Option<Vec<SensorLogData>>,
+    rollups: Option<Vec<SensorRollup>>,
     pagination: Pagination,
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetFireLogsOfUserQuery {
         email,
         start_time,
         end_time,
         offset,
         limit,
+        resolution,
     }): Query<GetFireLogsOfUserQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetFireLogsOfUserResponse {
-                message: String::from("Forbidden"),
-                fire_logs: None,
-                pagination: Pagination {
-                    start_time,
-                    end_time,
-                    offset,
-                    limit,
-                },
-            }),
-        );
-    }
-
-    let fire_coll: Collection<FireLog> = {
-        let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
-    };
-
-    let query_doc = doc! {
-        "owner_name": email.clone(),
-    };
-
-    let pipeline = vec![
-        doc! {
-            "$match": query_doc,
-        },
-        doc! {
-            "$unwind": "$fire_logs"
-        },
-        doc! {
-            "$sort": {
-                "fire_logs.timestamp.secs_since_epoch": -1,
-                "fire_logs.timestamp.nanos_since_epoch": -1
-            }
-        },
-        doc! {
-            "$match": {
-                "$and": [
-                    { "fire_logs.timestamp.secs_since_epoch": { "$gte": start_time.unwrap_or(0) } },
-                    { "fire_logs.timestamp.secs_since_epoch": { "$lte": end_time.unwrap_or(i32::MAX) } }
-            ]
-        }
-            },
-        doc! {
-            "$group": {
-                "_id": "$_id",
-                "fire_logs": { "$push": "$fire_logs" }
-            }
-        },
-        doc! {
-            "$project": {
-                "fire_logs": {
-                    "$slice": ["$fire_logs", offset.unwrap_or(0), limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)],
-                }
+    if let Some(resolution) = resolution.filter(|resolution| *resolution != Resolution::Raw) {
+        return match find_sensor_rollups(&email, &[SensorDataType::Fire], resolution, start_time, end_time, offset, limit).await {
+            Ok(rollups) => (
+                StatusCode::OK,
+                Json(GetFireLogsOfUserResponse {
+                    message: String::from("Successfully fetch fire rollups"),
+                    fire_logs: None,
+                    rollups: Some(rollups),
+                    pagination: Pagination {
+                        start_time,
+                        end_time,
+                        offset,
+                        limit,
+                    },
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error fetching sensor rollups: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(GetFireLogsOfUserResponse {
+                        message: String::from("Unexpected error while fetching fire rollups"),
+                        fire_logs: None,
+                        rollups: None,
+                        pagination: Pagination {
+                            start_time,
+                            end_time,
+                            offset,
+                            limit,
+                        },
+                    }),
+                )
             }
-        },
-    ];
-
-    let aggregate_options = AggregateOptions::builder().build();
+        };
+    }
 
-    match fire_coll.aggregate(pipeline, Some(aggregate_options)).await {
-        Ok(mut cursor) => {
-            let mut fire_logs = Vec::new();
-            while cursor.advance().await.unwrap_or(false) {
-                match cursor.deserialize_current() {
-                    Ok(document) => {
-                        if let Some(Bson::Array(fire_logs_array)) = document.get("fire_logs") {
-                            let logs: Vec<SensorLogData> = fire_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            fire_logs.extend(logs);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error deserializing document: {}", e);
-                    }
-                }
-            }
+    match find_sensor_logs(&email, SensorDataType::Fire, start_time, end_time, offset, limit).await {
+        Ok(fire_logs) => {
             if fire_logs.is_empty() {
                 (
                     StatusCode::OK,
                     Json(GetFireLogsOfUserResponse {
                         message: format!("Your fire matrix hasn't had any data"),
                         fire_logs: None,
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -155,6 +104,7 @@ async fn handler(
                     Json(GetFireLogsOfUserResponse {
                         message: format!("Successfully fetched fire log data"),
                         fire_logs: Some(fire_logs),
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -166,12 +116,13 @@ async fn handler(
             }
         }
         Err(e) => {
-            eprintln!("Error executing aggregation pipeline: {}", e);
+            eprintln!("Error fetching sensor logs: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(GetFireLogsOfUserResponse {
                     message: format!("Unexpected error while fetching fire log data"),
                     fire_logs: None,
+                    rollups: None,
                     pagination: Pagination {
                         start_time,
                         end_time,
@@ -185,8 +136,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/fire-logs",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get fire log by user email")
                 .tag("Fire alert")
@@ -1,25 +1,21 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
-};
-use mongodb::{
-    bson::{self, doc, Bson},
-    options::AggregateOptions,
-    Collection,
+    http::StatusCode,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backend_core::features::fire_alert_feature::{
-        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
-        models::{FireLog, Pagination, SensorLogData},
+    backend_core::{
+        features::fire_alert_feature::models::{Pagination, SensorDataType, SensorLogData, SensorRollup},
+        rollups::Resolution,
     },
     json::Json,
 };
 
-use super::MONGOC;
+use super::{find_sensor_logs, find_sensor_rollups};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetGasLogsOfUserQuery {
@@ -28,118 +24,72 @@ pub struct GetGasLogsOfUserQuery {
     end_time: Option<i32>,
     offset: Option<u32>,
     limit: Option<i64>,
+    resolution: Option<Resolution>,
 }
 
 #[derive(Serialize, JsonSchema)]
 pub struct GetGasLogsOfUserResponse {
     message: String,
     gas_logs: Option<Vec<SensorLogData>>,
+    rollups: Option<Vec<SensorRollup>>,
     pagination: Pagination,
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetGasLogsOfUserQuery {
         email,
         start_time,
         end_time,
         offset,
         limit,
+        resolution,
     }): Query<GetGasLogsOfUserQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetGasLogsOfUserResponse {
-                message: String::from("Forbidden"),
-                gas_logs: None,
-                pagination: Pagination {
-                    start_time,
-                    end_time,
-                    offset,
-                    limit,
-                },
-            }),
-        );
-    }
-    let fire_coll: Collection<FireLog> = {
-        let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
-    };
-
-    let query_doc = doc! {
-        "owner_name": email.clone(),
-    };
-
-    let pipeline = vec![
-        doc! {
-            "$match": query_doc,
-        },
-        doc! {
-            "$unwind": "$lpg_logs"
-        },
-        doc! {
-            "$sort": {
-                "lpg_logs.timestamp.secs_since_epoch": -1,
-                "lpg_logs.timestamp.nanos_since_epoch": -1
-            }
-        },
-        doc! {
-            "$match": {
-                "$and": [
-                    { "lpg_logs.timestamp.secs_since_epoch": { "$gte": start_time.unwrap_or(0) } },
-                    { "lpg_logs.timestamp.secs_since_epoch": { "$lte": end_time.unwrap_or(i32::MAX) } }
-            ]
-        }
-            },
-        doc! {
-            "$group": {
-                "_id": "$_id",
-                "lpg_logs": { "$push": "$lpg_logs" }
-            }
-        },
-        doc! {
-            "$project": {
-                "lpg_logs": {
-                    "$slice": ["$lpg_logs", offset.unwrap_or(0), limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)],
-                }
+    if let Some(resolution) = resolution.filter(|resolution| *resolution != Resolution::Raw) {
+        return match find_sensor_rollups(&email, &[SensorDataType::LPG], resolution, start_time, end_time, offset, limit).await {
+            Ok(rollups) => (
+                StatusCode::OK,
+                Json(GetGasLogsOfUserResponse {
+                    message: String::from("Successfully fetch gas rollups"),
+                    gas_logs: None,
+                    rollups: Some(rollups),
+                    pagination: Pagination {
+                        start_time,
+                        end_time,
+                        offset,
+                        limit,
+                    },
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error fetching sensor rollups: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(GetGasLogsOfUserResponse {
+                        message: String::from("Unexpected error while fetching gas rollups"),
+                        gas_logs: None,
+                        rollups: None,
+                        pagination: Pagination {
+                            start_time,
+                            end_time,
+                            offset,
+                            limit,
+                        },
+                    }),
+                )
             }
-        },
-    ];
-
-    let aggregate_options = AggregateOptions::builder().build();
+        };
+    }
 
-    match fire_coll.aggregate(pipeline, Some(aggregate_options)).await {
-        Ok(mut cursor) => {
-            let mut lpg_logs = Vec::new();
-            while cursor.advance().await.unwrap_or(false) {
-                match cursor.deserialize_current() {
-                    Ok(document) => {
-                        if let Some(Bson::Array(lpg_logs_array)) = document.get("lpg_logs") {
-                            let logs: Vec<SensorLogData> = lpg_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            lpg_logs.extend(logs);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error deserializing document: {}", e);
-                    }
-                }
-            }
+    match find_sensor_logs(&email, SensorDataType::LPG, start_time, end_time, offset, limit).await {
+        Ok(lpg_logs) => {
             if lpg_logs.is_empty() {
                 (
                     StatusCode::OK,
                     Json(GetGasLogsOfUserResponse {
                         message: format!("Your fire matrix hasn't had any data"),
                         gas_logs: None,
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -154,6 +104,7 @@ async fn handler(
                     Json(GetGasLogsOfUserResponse {
                         message: format!("Successfully fetch smoke log data"),
                         gas_logs: Some(lpg_logs),
+                        rollups: None,
                         pagination: Pagination {
                             start_time,
                             end_time,
@@ -165,12 +116,13 @@ async fn handler(
             }
         }
         Err(e) => {
-            eprintln!("Error executing aggregation pipeline: {}", e);
+            eprintln!("Error fetching sensor logs: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(GetGasLogsOfUserResponse {
                     message: format!("Unexpected error while fetching smoke log data"),
                     gas_logs: None,
+                    rollups: None,
                     pagination: Pagination {
                         start_time,
                         end_time,
@@ -184,8 +136,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/gas-logs",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get gas log by user email")
                 .tag("Fire alert")
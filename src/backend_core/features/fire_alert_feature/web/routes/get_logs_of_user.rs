@@ -1,25 +1,21 @@
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
-};
-use mongodb::{
-    bson::{self, doc, Bson},
-    options::AggregateOptions,
-    Collection,
+    http::StatusCode,
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    backend_core::features::fire_alert_feature::{
-        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
-        models::{FireLog, Pagination, SensorLogData},
+    backend_core::{
+        features::fire_alert_feature::models::{Pagination, SensorDataType, SensorLogData, SensorRollup},
+        rollups::Resolution,
     },
     json::Json,
 };
 
-use super::MONGOC;
+use super::{find_sensor_logs, find_sensor_rollups};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetLogsOfUserQuery {
@@ -28,6 +24,7 @@ pub struct GetLogsOfUserQuery {
     end_time: Option<i32>,
     offset: Option<u32>,
     limit: Option<i64>,
+    resolution: Option<Resolution>,
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -37,6 +34,7 @@ pub struct GetLogsOfUserResponse {
     co_logs: Option<Vec<SensorLogData>>,
     heat_logs: Option<Vec<SensorLogData>>,
     button_logs: Option<Vec<SensorLogData>>,
+    rollups: Option<Vec<SensorRollup>>,
     message: String,
     pagination: Pagination,
 }
@@ -49,213 +47,85 @@ struct UserLogs {
     button_logs: Vec<SensorLogData>,
 }
 
+async fn find_user_logs(
+    email: &str,
+    start_time: Option<i32>,
+    end_time: Option<i32>,
+    offset: Option<u32>,
+    limit: Option<i64>,
+) -> mongodb::error::Result<UserLogs> {
+    Ok(UserLogs {
+        fire_logs: find_sensor_logs(email, SensorDataType::Fire, start_time, end_time, offset, limit).await?,
+        smoke_logs: find_sensor_logs(email, SensorDataType::Smoke, start_time, end_time, offset, limit).await?,
+        co_logs: find_sensor_logs(email, SensorDataType::CO, start_time, end_time, offset, limit).await?,
+        heat_logs: find_sensor_logs(email, SensorDataType::Heat, start_time, end_time, offset, limit).await?,
+        button_logs: find_sensor_logs(email, SensorDataType::FireButton, start_time, end_time, offset, limit).await?,
+    })
+}
+
 async fn handler(
-    headers: HeaderMap,
     Query(GetLogsOfUserQuery {
         email,
         start_time,
         end_time,
         offset,
         limit,
+        resolution,
     }): Query<GetLogsOfUserQuery>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetLogsOfUserResponse {
-                message: String::from("Forbidden"),
-                fire_logs: None,
-                smoke_logs: None,
-                co_logs: None,
-                heat_logs: None,
-                button_logs: None,
-                pagination: Pagination {
-                    start_time,
-                    end_time,
-                    offset,
-                    limit,
-                },
-            }),
-        );
-    }
-    let fire_coll: Collection<FireLog> = {
-        let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
-    };
-
-    let query_doc = doc! {
-        "owner_name": email.clone(),
-    };
-
-    let pipeline = vec![
-        doc! {
-            "$match": query_doc,
-        },
-        doc! {
-            "$project": {
-                "fire_logs": {
-                    "$slice": [
-                        {
-                            "$filter": {
-                                "input": "$fire_logs",
-                                "as": "fire_log",
-                                "cond": {
-                                    "$and": [
-                                        { "$gte": ["$$fire_log.timestamp.secs_since_epoch", start_time.unwrap_or(0)] },
-                                        { "$lte": ["$$fire_log.timestamp.secs_since_epoch", end_time.unwrap_or(i32::MAX)] }
-                                    ]
-                                }
-                            }
-                        },
-                        offset.unwrap_or(0),
-                        limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)
-                    ],
-                },
-                "smoke_logs": {
-                    "$slice": [
-                        {
-                            "$filter": {
-                                "input": "$smoke_logs",
-                                "as": "smoke_log",
-                                "cond": {
-                                    "$and": [
-                                        { "$gte": ["$$smoke_log.timestamp.secs_since_epoch", start_time.unwrap_or(0)] },
-                                        { "$lte": ["$$smoke_log.timestamp.secs_since_epoch", end_time.unwrap_or(i32::MAX)] }
-                                    ]
-                                }
-                            },
-                        },
-                        offset.unwrap_or(0),
-                        limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)
-                    ],
-                },
-                "co_logs": {
-                    "$slice": [
-                        {
-                            "$filter": {
-                                "input": "$co_logs",
-                                "as": "co_log",
-                                "cond": {
-                                    "$and": [
-                                        { "$gte": ["$$co_log.timestamp.secs_since_epoch", start_time.unwrap_or(0)] },
-                                        { "$lte": ["$$co_log.timestamp.secs_since_epoch", end_time.unwrap_or(i32::MAX)] }
-                                    ]
-                                }
-                            },
-                        },
-                        offset.unwrap_or(0),
-                        limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)
-                    ],
-                },
-                "heat_logs": {
-                    "$slice": [
-                        {
-                            "$filter": {
-                                "input": "$heat_logs",
-                                "as": "heat_log",
-                                "cond": {
-                                    "$and": [
-                                        { "$gte": ["$$heat_log.timestamp.secs_since_epoch", start_time.unwrap_or(0)] },
-                                        { "$lte": ["$$heat_log.timestamp.secs_since_epoch", end_time.unwrap_or(i32::MAX)] }
-                                    ]
-                                }
-                            },
-                        },
-                        offset.unwrap_or(0),
-                        limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)
-                    ],
-                },
-                "button_logs": {
-                    "$slice": [
-                        {
-                            "$filter": {
-                                "input": "$button_logs",
-                                "as": "button_log",
-                                "cond": {
-                                    "$and": [
-                                        { "$gte": ["$$button_log.timestamp.secs_since_epoch", start_time.unwrap_or(0)] },
-                                        { "$lte": ["$$button_log.timestamp.secs_since_epoch", end_time.unwrap_or(i32::MAX)] }
-                                    ]
-                                }
-                            },
+    if let Some(resolution) = resolution.filter(|resolution| *resolution != Resolution::Raw) {
+        let sensor_types = [
+            SensorDataType::Fire,
+            SensorDataType::Smoke,
+            SensorDataType::CO,
+            SensorDataType::Heat,
+            SensorDataType::FireButton,
+        ];
+        return match find_sensor_rollups(&email, &sensor_types, resolution, start_time, end_time, offset, limit).await {
+            Ok(rollups) => (
+                StatusCode::OK,
+                Json(GetLogsOfUserResponse {
+                    fire_logs: None,
+                    smoke_logs: None,
+                    co_logs: None,
+                    heat_logs: None,
+                    button_logs: None,
+                    rollups: Some(rollups),
+                    message: String::from("Successfully fetch fire sensor rollups"),
+                    pagination: Pagination {
+                        start_time,
+                        end_time,
+                        offset,
+                        limit,
+                    },
+                }),
+            ),
+            Err(e) => {
+                eprintln!("Error fetching sensor rollups: {}", e);
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(GetLogsOfUserResponse {
+                        fire_logs: None,
+                        smoke_logs: None,
+                        co_logs: None,
+                        heat_logs: None,
+                        button_logs: None,
+                        rollups: None,
+                        message: String::from("Unexpected error while fetching fire sensor rollups"),
+                        pagination: Pagination {
+                            start_time,
+                            end_time,
+                            offset,
+                            limit,
                         },
-                        offset.unwrap_or(0),
-                        limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST)
-                    ],
-                }
+                    }),
+                )
             }
-        },
-    ];
-
-    let aggregate_options = AggregateOptions::builder().build();
+        };
+    }
 
-    match fire_coll.aggregate(pipeline, Some(aggregate_options)).await {
-        Ok(mut cursor) => {
-            let mut user_logs = UserLogs {
-                fire_logs: Vec::new(),
-                smoke_logs: Vec::new(),
-                co_logs: Vec::new(),
-                button_logs: Vec::new(),
-                heat_logs: Vec::new(),
-            };
-            while cursor.advance().await.unwrap_or(false) {
-                match cursor.deserialize_current() {
-                    Ok(document) => {
-                        if let Some(Bson::Array(fire_logs_array)) = document.get("fire_logs") {
-                            let logs: Vec<SensorLogData> = fire_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            user_logs.fire_logs.extend(logs);
-                        }
-                        if let Some(Bson::Array(smoke_logs_array)) = document.get("smoke_logs") {
-                            let logs: Vec<SensorLogData> = smoke_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            user_logs.smoke_logs.extend(logs);
-                        }
-                        if let Some(Bson::Array(co_logs_array)) = document.get("co_logs") {
-                            let logs: Vec<SensorLogData> = co_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            user_logs.co_logs.extend(logs);
-                        }
-                        if let Some(Bson::Array(button_logs_array)) = document.get("button_logs") {
-                            let logs: Vec<SensorLogData> = button_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            user_logs.button_logs.extend(logs);
-                        }
-                        if let Some(Bson::Array(heat_logs_array)) = document.get("heat_logs") {
-                            let logs: Vec<SensorLogData> = heat_logs_array
-                                .iter()
-                                .filter_map(|log| {
-                                    bson::from_bson::<SensorLogData>(log.clone()).ok()
-                                })
-                                .collect();
-                            user_logs.heat_logs.extend(logs);
-                        }
-                    }
-                    Err(e) => {
-                        eprintln!("Error deserializing document: {}", e);
-                    }
-                }
-            }
+    match find_user_logs(&email, start_time, end_time, offset, limit).await {
+        Ok(user_logs) => {
             if user_logs.fire_logs.is_empty() {
                 (
                     StatusCode::OK,
@@ -265,6 +135,7 @@ async fn handler(
                         co_logs: None,
                         heat_logs: None,
                         button_logs: None,
+                        rollups: None,
                         message: format!("Your fire matrix hasn't had any data"),
                         pagination: Pagination {
                             start_time,
@@ -283,6 +154,7 @@ async fn handler(
                         co_logs: Some(user_logs.co_logs),
                         heat_logs: Some(user_logs.heat_logs),
                         button_logs: Some(user_logs.button_logs),
+                        rollups: None,
                         message: format!("Successfully fetch fire sensor log data"),
                         pagination: Pagination {
                             start_time,
@@ -295,7 +167,7 @@ async fn handler(
             }
         }
         Err(e) => {
-            eprintln!("Error executing aggregation pipeline: {}", e);
+            eprintln!("Error fetching sensor logs: {}", e);
             (
                 StatusCode::INTERNAL_SERVER_ERROR,
                 Json(GetLogsOfUserResponse {
@@ -304,6 +176,7 @@ async fn handler(
                     co_logs: None,
                     heat_logs: None,
                     button_logs: None,
+                    rollups: None,
                     message: format!("Unexpected error while fetching user log data"),
                     pagination: Pagination {
                         start_time,
@@ -318,8 +191,9 @@ async fn handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/fire-alert-logs",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get fire metrics log by user email")
                 .tag("Fire alert")
@@ -3,7 +3,7 @@ use std::time::SystemTime;
 use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
 };
 use mongodb::{
     bson::{self, doc, Bson},
@@ -12,9 +12,10 @@ use mongodb::{
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{backend_core::{features::{devices_status_feature::models::Device, fire_alert_feature::models::{FireLog, FireStatus, SensorDataType}}, models::Room}, json::Json};
+use crate::{backend_core::{features::{devices_status_feature::models::Device, fire_alert_feature::models::{FireStatus, SensorDataType, SensorLogEntry}}, models::Room}, json::Json};
 
 use super::MONGOC;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetStatusQuery {
@@ -81,7 +82,6 @@ struct ComponentStatusPipelineOutput {
 }
 
 async fn handler(
-    headers: HeaderMap,
     Query(GetStatusQuery {
         email,
         component_ids,
@@ -100,7 +100,7 @@ async fn handler(
 ) -> impl IntoApiResponse {
     if room_name.is_some() {
         if !vec![co, fire, gas, heat, light, button, buzzer, smoke].into_iter().any(|opt| opt.is_some_and(|v| v)) {
-            handle_room_status(headers, email, room_name.unwrap()).await
+            handle_room_status(email, room_name.unwrap()).await
         } else {
             let mut types = vec![];
             co.map(|_| types.push(SensorDataType::CO));
@@ -112,11 +112,11 @@ async fn handler(
             button.map(|_| types.push(SensorDataType::FireBuzzer));
             smoke.map(|_| types.push(SensorDataType::Smoke));
 
-            handle_room_status_of_types(headers, email, room_name.unwrap(), types, start_time, end_time).await
+            handle_room_status_of_types(email, room_name.unwrap(), types, start_time, end_time).await
         }
     } else if component_ids.is_some() {
         if let Ok(component_ids) = serde_json::from_str(&component_ids.unwrap()) {
-            handle_component_statuses(headers, email, component_ids).await
+            handle_component_statuses(email, component_ids).await
         } else {
             (
                 StatusCode::BAD_REQUEST,
@@ -138,14 +138,13 @@ async fn handler(
 }
 
 async fn handle_room_status_of_types(
-    headers: HeaderMap,
     email: String,
     room_name: String,
     types: Vec<SensorDataType>,
     start_time: Option<i32>,
     end_time: Option<i32>,
 ) -> (StatusCode, Json<GetStatusResponse>) {
-    let response = handle_room_status(headers, email.clone(), room_name.clone()).await;
+    let response = handle_room_status(email.clone(), room_name.clone()).await;
     if let GetStatusResponse::RoomSafetyStatus { message, component_statuses: value } = response.1.0 {
         if value.is_none() || value.as_ref().unwrap().len() == 0 {
             return (
@@ -174,49 +173,24 @@ async fn handle_room_status_of_types(
         let mut fire_logs = None;
         let mut lpg_logs = None;
 
-        let fire_coll: Collection<FireLog> = {
+        let sensor_log_coll: Collection<SensorLogEntry> = {
             let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-            mongoc.default_database().unwrap().collection("fire_alerts")
+            mongoc.default_database().unwrap().collection("fire_sensor_logs")
         };
 
         let (component_ids, _) = get_component_ids_by_room(email.clone(), room_name).await.unwrap();
 
         for typ in types {
-            let log_field = match typ {
-                SensorDataType::CO => "co_logs",
-                SensorDataType::LPG => "lpg_logs",
-                SensorDataType::Fire => "fire_logs",
-                SensorDataType::Heat => "heat_logs",
-                SensorDataType::Smoke => "smoke_logs",
-                SensorDataType::FireLight => "light_logs",
-                SensorDataType::FireButton => "button_logs",
-                SensorDataType::FireBuzzer => "buzzer_logs",
-            };
-
-            let pipeline = vec![ 
-                doc! { "$match": { "owner_name": email.clone() } },
-                doc! { "$project": {
-                    "_id": 0,
-                    log_field: 1,
-                } },
-                doc! {
-                    "$project": {
-                        "logs": {
-                            "$concatArrays": [format!("${log_field}")],
-                        }
-                    }
-                },
-                doc! {
-                    "$unwind": "$logs",
-                },
-                doc! {
-                    "$replaceRoot": {
-                        "newRoot": "$logs",
-                    }
-                },
+            let pipeline = vec![
                 doc! {
                     "$match": {
+                        "owner_name": email.clone(),
+                        "sensor_type": bson::to_bson(&typ).unwrap(),
                         "component": { "$in": bson::to_bson(&component_ids).unwrap() },
+                        "timestamp.secs_since_epoch": {
+                            "$gte": start_time.unwrap_or(0),
+                            "$lte": end_time.unwrap_or(i32::MAX),
+                        },
                     }
                 },
                 doc! {
@@ -226,16 +200,15 @@ async fn handle_room_status_of_types(
                     }
                 },
                 doc! {
-                    "$match": {
-                        "$and": [
-                            { "timestamp.secs_since_epoch": { "$gte": start_time.unwrap_or(0) } },
-                            { "timestamp.secs_since_epoch": { "$lte": end_time.unwrap_or(i32::MAX) } },
-                        ]
+                    "$project": {
+                        "_id": 0,
+                        "timestamp": 1,
+                        "value": 1,
                     }
                 },
             ];
-            
-            let mut cursor = fire_coll.aggregate(pipeline, None).await.unwrap();
+
+            let mut cursor = sensor_log_coll.aggregate(pipeline, None).await.unwrap();
 
             let mut res = vec![];
             while let Ok(true) = cursor.advance().await {
@@ -275,24 +248,9 @@ async fn handle_room_status_of_types(
 }
 
 async fn handle_room_status(
-    headers: HeaderMap,
     email: String,
     room_name: String,
 ) -> (StatusCode, Json<GetStatusResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetStatusResponse::RoomSafetyStatus {
-                message: String::from("Forbidden"),
-                component_statuses: None,
-            }),
-        );
-    }
-
     let component_ids = get_component_ids_by_room(email.clone(), room_name).await;
     if component_ids.is_none() {
         return (
@@ -338,24 +296,9 @@ async fn handle_room_status(
 }
 
 async fn handle_component_statuses(
-    headers: HeaderMap,
     email: String,
     component_ids: Vec<usize>,
 ) -> (StatusCode, Json<GetStatusResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetStatusResponse::ComponentSafetyStatuses {
-                message: String::from("Forbidden"),
-                value: None,
-            }),
-        );
-    }
-
     let statuses = get_component_statuses(email, component_ids).await.map(|inner| inner.into_iter().map(|v| ComponentSafetyStatus { id: v._id, status: v.alert }).collect());
     (
         StatusCode::OK,
@@ -423,41 +366,26 @@ async fn get_component_ids_by_room(email: String, room_name: String) -> Option<(
 }
 
 async fn get_component_statuses(email: String, component_ids: Vec<usize>) -> Option<Vec<ComponentStatusPipelineOutput>> {
-    let fire_coll: Collection<FireLog> = {
+    let sensor_log_coll: Collection<SensorLogEntry> = {
         let mongoc = unsafe { MONGOC.as_ref().clone().unwrap().lock() }.await;
-        mongoc.default_database().unwrap().collection("fire_alerts")
+        mongoc.default_database().unwrap().collection("fire_sensor_logs")
     };
 
-    let pipeline = vec![ 
-        doc! { "$match": { "owner_name": email.clone() } },
-        doc! { "$project": {
-            "_id": 0,
-            "co_logs": 1,
-            "buzzer_logs": 1,
-            "fire_logs": 1,
-            "lpg_logs": 1,
-            "heat_logs": 1,
-            "light_logs": 1,
-            "smoke_logs": 1,
-            "button_logs": 1,
-        } },
-        doc! {
-            "$project": {
-                "logs": {
-                    "$concatArrays": ["$co_logs", "$buzzer_logs", "$lpg_logs", "$heat_logs", "$light_logs", "$smoke_logs", "$button_logs"],
-                }
-            }
-        },
-        doc! {
-            "$unwind": "$logs",
-        },
-        doc! {
-            "$replaceRoot": {
-                "newRoot": "$logs",
-            }
-        },
+    let sensor_types = [
+        SensorDataType::CO,
+        SensorDataType::FireBuzzer,
+        SensorDataType::LPG,
+        SensorDataType::Heat,
+        SensorDataType::FireLight,
+        SensorDataType::Smoke,
+        SensorDataType::FireButton,
+    ];
+
+    let pipeline = vec![
         doc! {
             "$match": {
+                "owner_name": email.clone(),
+                "sensor_type": { "$in": bson::to_bson(&sensor_types).unwrap() },
                 "component": { "$in": bson::to_bson(&component_ids).unwrap() },
             }
         },
@@ -475,7 +403,7 @@ async fn get_component_statuses(email: String, component_ids: Vec<usize>) -> Opt
         },
     ];
 
-    let mut cursor = fire_coll.aggregate(pipeline, None).await.unwrap();
+    let mut cursor = sensor_log_coll.aggregate(pipeline, None).await.unwrap();
     let mut res = vec![];
     while cursor.advance().await.unwrap() {
         let document = cursor.deserialize_current().unwrap();
@@ -486,8 +414,9 @@ async fn get_component_statuses(email: String, component_ids: Vec<usize>) -> Opt
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/status",
+        Policy::Role(HouseholdRole::Viewer),
         get_with(handler, |op| {
             op.description("Get fire-device status")
                 .tag("Fire alert")
@@ -0,0 +1,325 @@
+use std::time::{Duration, SystemTime};
+
+use aide::axum::{
+    routing::{get_with, post_with},
+    ApiRouter, IntoApiResponse,
+};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{
+    bson::{doc, to_bson, Bson, Document},
+    options::{FindOneAndUpdateOptions, FindOptions, ReturnDocument},
+    Collection,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::fire_alert_feature::{
+        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
+        models::{Incident, IncidentEvent, IncidentStatus},
+    },
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+// Incidents cannot be silenced for longer than a day at once
+const MAX_SILENCE_MINUTES: u64 = 24 * 60;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetIncidentsQuery {
+    email: String,
+    incident_id: Option<String>,
+    status: Option<IncidentStatus>,
+    start_time: Option<i32>,
+    end_time: Option<i32>,
+    offset: Option<u64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetIncidentsResponse {
+    message: String,
+    incidents: Option<Vec<Incident>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct IncidentActionBody {
+    email: String,
+    incident_id: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SilenceIncidentBody {
+    email: String,
+    incident_id: String,
+    minutes: u64,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct IncidentActionResponse {
+    message: String,
+    incident: Option<Incident>,
+}
+
+async fn get_incident_coll() -> Collection<Incident> {
+    get_collection("fire_incidents").await
+}
+
+async fn get_incidents_handler(
+    Query(GetIncidentsQuery {
+        email,
+        incident_id,
+        status,
+        start_time,
+        end_time,
+        offset,
+        limit,
+    }): Query<GetIncidentsQuery>,
+) -> impl IntoApiResponse {
+    let mut filter = doc! {
+        "owner_name": email.clone(),
+        "opened_at.secs_since_epoch": {
+            "$gte": start_time.unwrap_or(0),
+            "$lte": end_time.unwrap_or(i32::MAX),
+        },
+    };
+    if let Some(incident_id) = incident_id {
+        filter.insert("id", incident_id);
+    }
+    if let Some(status) = status {
+        filter.insert("status", to_bson(&status).unwrap());
+    }
+
+    let find_options = FindOptions::builder()
+        .sort(doc! { "opened_at.secs_since_epoch": -1, "opened_at.nanos_since_epoch": -1 })
+        .skip(offset)
+        .limit(limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST))
+        .build();
+
+    match get_incident_coll().await.find(filter, find_options).await {
+        Ok(mut cursor) => {
+            let mut incidents = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(incident) => incidents.push(incident),
+                    Err(e) => eprintln!("Error deserializing incident: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetIncidentsResponse {
+                    message: String::from("Successfully fetch incidents"),
+                    incidents: Some(incidents),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetIncidentsResponse {
+                message: String::from("Unexpected error while fetching incidents"),
+                incidents: None,
+            }),
+        ),
+    }
+}
+
+// Applies a lifecycle transition only if the incident is currently in one of
+// the allowed states, so concurrent transitions cannot skip a step.
+async fn transition_incident(
+    email: String,
+    incident_id: String,
+    allowed_from: &[IncidentStatus],
+    update: Document,
+) -> (StatusCode, Json<IncidentActionResponse>) {
+    let incident_coll = get_incident_coll().await;
+    let allowed_from: Vec<Bson> = allowed_from.iter().map(|s| to_bson(s).unwrap()).collect();
+
+    let options = FindOneAndUpdateOptions::builder()
+        .return_document(ReturnDocument::After)
+        .build();
+
+    match incident_coll
+        .find_one_and_update(
+            doc! { "id": incident_id.clone(), "owner_name": email.clone(), "status": { "$in": allowed_from } },
+            update,
+            options,
+        )
+        .await
+    {
+        Ok(Some(incident)) => (
+            StatusCode::OK,
+            Json(IncidentActionResponse {
+                message: format!("Incident '{incident_id}' updated successfully"),
+                incident: Some(incident),
+            }),
+        ),
+        Ok(None) => match incident_coll
+            .find_one(doc! { "id": incident_id.clone(), "owner_name": email }, None)
+            .await
+        {
+            Ok(Some(incident)) => (
+                StatusCode::BAD_REQUEST,
+                Json(IncidentActionResponse {
+                    message: format!("Incident '{incident_id}' cannot be updated in its current state"),
+                    incident: Some(incident),
+                }),
+            ),
+            Ok(None) => (
+                StatusCode::NOT_FOUND,
+                Json(IncidentActionResponse {
+                    message: format!("No incident with id '{incident_id}'"),
+                    incident: None,
+                }),
+            ),
+            Err(_) => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(IncidentActionResponse {
+                    message: String::from("Internal server error"),
+                    incident: None,
+                }),
+            ),
+        },
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(IncidentActionResponse {
+                message: String::from("Internal server error"),
+                incident: None,
+            }),
+        ),
+    }
+}
+
+async fn acknowledge_incident_handler(
+    Json(IncidentActionBody { email, incident_id }): Json<IncidentActionBody>,
+) -> impl IntoApiResponse {
+    let event = IncidentEvent::Acknowledged {
+        timestamp: SystemTime::now(),
+        by: email.clone(),
+    };
+    transition_incident(
+        email,
+        incident_id,
+        &[IncidentStatus::Open],
+        doc! {
+            "$set": { "status": to_bson(&IncidentStatus::Acknowledged).unwrap() },
+            "$push": { "timeline": to_bson(&event).unwrap() },
+        },
+    )
+    .await
+}
+
+async fn silence_incident_handler(
+    Json(SilenceIncidentBody {
+        email,
+        incident_id,
+        minutes,
+    }): Json<SilenceIncidentBody>,
+) -> impl IntoApiResponse {
+    let now = SystemTime::now();
+    let Some(until) = Some(minutes)
+        .filter(|minutes| *minutes <= MAX_SILENCE_MINUTES)
+        .and_then(|minutes| minutes.checked_mul(60))
+        .and_then(|secs| now.checked_add(Duration::from_secs(secs)))
+    else {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(IncidentActionResponse {
+                message: format!("Incidents can be silenced for at most {MAX_SILENCE_MINUTES} minutes"),
+                incident: None,
+            }),
+        );
+    };
+    let event = IncidentEvent::Silenced {
+        timestamp: now,
+        by: email.clone(),
+        until,
+    };
+    transition_incident(
+        email,
+        incident_id,
+        &[IncidentStatus::Open, IncidentStatus::Acknowledged],
+        doc! {
+            "$set": { "silenced_until": to_bson(&until).unwrap() },
+            "$push": { "timeline": to_bson(&event).unwrap() },
+        },
+    )
+    .await
+}
+
+async fn resolve_incident_handler(
+    Json(IncidentActionBody { email, incident_id }): Json<IncidentActionBody>,
+) -> impl IntoApiResponse {
+    let event = IncidentEvent::Resolved {
+        timestamp: SystemTime::now(),
+        by: Some(email.clone()),
+    };
+    transition_incident(
+        email,
+        incident_id,
+        &[IncidentStatus::Open, IncidentStatus::Acknowledged],
+        doc! {
+            "$set": { "status": to_bson(&IncidentStatus::Resolved).unwrap() },
+            "$push": { "timeline": to_bson(&event).unwrap() },
+        },
+    )
+    .await
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/incidents",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_incidents_handler, |op| {
+                op.description("Get fire incidents and their timeline by user email")
+                    .tag("Fire alert")
+                    .response::<200, Json<GetIncidentsResponse>>()
+                    .response::<403, Json<GetIncidentsResponse>>()
+                    .response::<500, Json<GetIncidentsResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/incidents/acknowledge",
+            Policy::Role(HouseholdRole::Member),
+            post_with(acknowledge_incident_handler, |op| {
+                op.description("Acknowledge an open incident")
+                    .tag("Fire alert")
+                    .response::<200, Json<IncidentActionResponse>>()
+                    .response::<400, Json<IncidentActionResponse>>()
+                    .response::<403, Json<IncidentActionResponse>>()
+                    .response::<404, Json<IncidentActionResponse>>()
+                    .response::<500, Json<IncidentActionResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/incidents/silence",
+            Policy::Role(HouseholdRole::Member),
+            post_with(silence_incident_handler, |op| {
+                op.description("Suppress repeated push notifications of an incident for a number of minutes")
+                    .tag("Fire alert")
+                    .response::<200, Json<IncidentActionResponse>>()
+                    .response::<400, Json<IncidentActionResponse>>()
+                    .response::<403, Json<IncidentActionResponse>>()
+                    .response::<404, Json<IncidentActionResponse>>()
+                    .response::<500, Json<IncidentActionResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/incidents/resolve",
+            Policy::Role(HouseholdRole::Member),
+            post_with(resolve_incident_handler, |op| {
+                op.description("Manually resolve an incident")
+                    .tag("Fire alert")
+                    .response::<200, Json<IncidentActionResponse>>()
+                    .response::<400, Json<IncidentActionResponse>>()
+                    .response::<403, Json<IncidentActionResponse>>()
+                    .response::<404, Json<IncidentActionResponse>>()
+                    .response::<500, Json<IncidentActionResponse>>()
+            }),
+        )
+}
@@ -1,12 +1,24 @@
 use std::sync::Arc;
 
 use aide::axum::ApiRouter;
+use mongodb::{
+    bson::{self, doc, to_bson},
+    Collection,
+};
 use tokio::sync::Mutex;
 
 use super::WebFireFeature;
+use crate::backend_core::{
+    features::fire_alert_feature::{
+        fixed_value::MAX_AMOUNT_DOCUMENT_PER_REQUEST,
+        models::{SensorDataType, SensorLogData, SensorLogEntry, SensorRollup},
+    },
+    rollups::Resolution,
+};
 
 pub static mut MONGOC: Option<Arc<Mutex<mongodb::Client>>> = None;
 
+mod analysis_settings;
 mod get_logs_of_user;
 mod get_button_logs_of_user;
 mod get_co_logs_of_user;
@@ -17,6 +29,97 @@ mod get_smoke_logs_of_user;
 mod get_buzzer_logs;
 mod get_light_logs;
 mod get_status;
+mod incidents;
+mod thresholds;
+
+#[allow(static_mut_refs)]
+pub async fn get_collection<T>(name: &str) -> Collection<T> {
+    let mongoc = unsafe { MONGOC.as_ref().unwrap().lock() }.await;
+    mongoc.default_database().unwrap().collection(name)
+}
+
+// Newest readings first, paginated within the requested time range.
+pub async fn find_sensor_logs(
+    email: &str,
+    sensor_type: SensorDataType,
+    start_time: Option<i32>,
+    end_time: Option<i32>,
+    offset: Option<u32>,
+    limit: Option<i64>,
+) -> mongodb::error::Result<Vec<SensorLogData>> {
+    let sensor_log_coll: Collection<SensorLogEntry> = get_collection("fire_sensor_logs").await;
+
+    let pipeline = vec![
+        doc! {
+            "$match": {
+                "owner_name": email,
+                "sensor_type": to_bson(&sensor_type)?,
+                "timestamp.secs_since_epoch": {
+                    "$gte": start_time.unwrap_or(0),
+                    "$lte": end_time.unwrap_or(i32::MAX),
+                },
+            }
+        },
+        doc! {
+            "$sort": {
+                "timestamp.secs_since_epoch": -1,
+                "timestamp.nanos_since_epoch": -1,
+            }
+        },
+        doc! { "$skip": offset.unwrap_or(0) },
+        doc! { "$limit": limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST) },
+    ];
+
+    let mut cursor = sensor_log_coll.aggregate(pipeline, None).await?;
+    let mut logs = vec![];
+    while cursor.advance().await? {
+        match bson::from_document::<SensorLogEntry>(cursor.deserialize_current()?) {
+            Ok(entry) => logs.push(entry.data),
+            Err(e) => eprintln!("Error deserializing document: {}", e),
+        }
+    }
+    Ok(logs)
+}
+
+// Newest buckets first, across every requested sensor type.
+pub async fn find_sensor_rollups(
+    email: &str,
+    sensor_types: &[SensorDataType],
+    resolution: Resolution,
+    start_time: Option<i32>,
+    end_time: Option<i32>,
+    offset: Option<u32>,
+    limit: Option<i64>,
+) -> mongodb::error::Result<Vec<SensorRollup>> {
+    let rollup_coll: Collection<SensorRollup> = get_collection("fire_sensor_rollups").await;
+
+    let pipeline = vec![
+        doc! {
+            "$match": {
+                "owner_name": email,
+                "sensor_type": { "$in": to_bson(sensor_types)? },
+                "resolution": resolution.as_str(),
+                "bucket": {
+                    "$gte": start_time.unwrap_or(0),
+                    "$lte": end_time.unwrap_or(i32::MAX),
+                },
+            }
+        },
+        doc! { "$sort": { "bucket": -1 } },
+        doc! { "$skip": offset.unwrap_or(0) },
+        doc! { "$limit": limit.unwrap_or(MAX_AMOUNT_DOCUMENT_PER_REQUEST) },
+    ];
+
+    let mut cursor = rollup_coll.aggregate(pipeline, None).await?;
+    let mut rollups = vec![];
+    while cursor.advance().await? {
+        match bson::from_document::<SensorRollup>(cursor.deserialize_current()?) {
+            Ok(rollup) => rollups.push(rollup),
+            Err(e) => eprintln!("Error deserializing document: {}", e),
+        }
+    }
+    Ok(rollups)
+}
 
 pub fn create_router(web: &mut WebFireFeature) -> ApiRouter {
     unsafe {
@@ -33,4 +136,7 @@ pub fn create_router(web: &mut WebFireFeature) -> ApiRouter {
                     .nest("/", get_buzzer_logs::routes())
                     .nest("/", get_light_logs::routes())
                     .nest("/", get_status::routes())
+                    .nest("/", thresholds::routes())
+                    .nest("/", incidents::routes())
+                    .nest("/", analysis_settings::routes())
 }
@@ -0,0 +1,272 @@
+use aide::axum::{routing::{get_with, post_with}, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{
+    bson::{doc, to_bson, Document},
+    Collection,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::fire_alert_feature::models::{AlertThreshold, SensorDataType},
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetThresholdsQuery {
+    email: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetThresholdsResponse {
+    message: String,
+    thresholds: Option<Vec<AlertThreshold>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ThresholdBody {
+    email: String,
+    sensor_type: SensorDataType,
+    component: Option<u32>,
+    value: f32,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ThresholdIdentifier {
+    email: String,
+    sensor_type: SensorDataType,
+    component: Option<u32>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ThresholdMessageResponse {
+    message: String,
+}
+
+async fn get_threshold_coll() -> Collection<AlertThreshold> {
+    get_collection("fire_alert_thresholds").await
+}
+
+fn threshold_filter(email: &str, sensor_type: SensorDataType, component: Option<u32>) -> Document {
+    doc! {
+        "owner_name": email,
+        "sensor_type": to_bson(&sensor_type).unwrap(),
+        "component": component,
+    }
+}
+
+async fn get_thresholds_handler(
+    Query(GetThresholdsQuery { email }): Query<GetThresholdsQuery>,
+) -> impl IntoApiResponse {
+    let threshold_coll = get_threshold_coll().await;
+    match threshold_coll
+        .find(doc! { "owner_name": email.clone() }, None)
+        .await
+    {
+        Ok(mut cursor) => {
+            let mut thresholds = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(threshold) => thresholds.push(threshold),
+                    Err(e) => eprintln!("Error deserializing threshold: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetThresholdsResponse {
+                    message: String::from("Successfully fetch alert thresholds"),
+                    thresholds: Some(thresholds),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetThresholdsResponse {
+                message: String::from("Unexpected error while fetching alert thresholds"),
+                thresholds: None,
+            }),
+        ),
+    }
+}
+
+async fn create_threshold_handler(
+    Json(ThresholdBody {
+        email,
+        sensor_type,
+        component,
+        value,
+    }): Json<ThresholdBody>,
+) -> (StatusCode, Json<ThresholdMessageResponse>) {
+    if !sensor_type.is_measurement() {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(ThresholdMessageResponse {
+                message: String::from("Thresholds only apply to fire, smoke, co, heat and lpg sensors"),
+            }),
+        );
+    }
+
+    let threshold_coll = get_threshold_coll().await;
+    if threshold_coll
+        .find_one(threshold_filter(&email, sensor_type, component), None)
+        .await
+        .unwrap_or(None)
+        .is_some()
+    {
+        return (
+            StatusCode::CONFLICT,
+            Json(ThresholdMessageResponse {
+                message: String::from("Threshold already exists"),
+            }),
+        );
+    }
+
+    if threshold_coll
+        .insert_one(
+            AlertThreshold {
+                owner_name: email.clone(),
+                sensor_type,
+                component,
+                value,
+            },
+            None,
+        )
+        .await
+        .is_err()
+    {
+        return (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ThresholdMessageResponse {
+                message: String::from("Failed to create threshold"),
+            }),
+        );
+    }
+
+    (
+        StatusCode::OK,
+        Json(ThresholdMessageResponse {
+            message: String::from("Threshold created successfully"),
+        }),
+    )
+}
+
+async fn update_threshold_handler(
+    Json(ThresholdBody {
+        email,
+        sensor_type,
+        component,
+        value,
+    }): Json<ThresholdBody>,
+) -> (StatusCode, Json<ThresholdMessageResponse>) {
+    let threshold_coll = get_threshold_coll().await;
+    match threshold_coll
+        .find_one_and_update(
+            threshold_filter(&email, sensor_type, component),
+            doc! { "$set": { "value": value } },
+            None,
+        )
+        .await
+    {
+        Ok(Some(_)) => (
+            StatusCode::OK,
+            Json(ThresholdMessageResponse {
+                message: String::from("Threshold updated successfully"),
+            }),
+        ),
+        Ok(None) => (
+            StatusCode::NOT_FOUND,
+            Json(ThresholdMessageResponse {
+                message: String::from("Threshold does not exist"),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ThresholdMessageResponse {
+                message: String::from("Failed to update threshold"),
+            }),
+        ),
+    }
+}
+
+async fn delete_threshold_handler(
+    Query(ThresholdIdentifier {
+        email,
+        sensor_type,
+        component,
+    }): Query<ThresholdIdentifier>,
+) -> (StatusCode, Json<ThresholdMessageResponse>) {
+    let threshold_coll = get_threshold_coll().await;
+    match threshold_coll
+        .delete_one(threshold_filter(&email, sensor_type, component), None)
+        .await
+    {
+        Ok(result) if result.deleted_count > 0 => (
+            StatusCode::OK,
+            Json(ThresholdMessageResponse {
+                message: String::from("Threshold deleted successfully"),
+            }),
+        ),
+        Ok(_) => (
+            StatusCode::NOT_FOUND,
+            Json(ThresholdMessageResponse {
+                message: String::from("Threshold does not exist"),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(ThresholdMessageResponse {
+                message: String::from("Failed to delete threshold"),
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/thresholds",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_thresholds_handler, |op| {
+                op.description("Get alert thresholds by user email")
+                    .tag("Fire alert")
+                    .response::<200, Json<GetThresholdsResponse>>()
+                    .response::<403, Json<GetThresholdsResponse>>()
+                    .response::<500, Json<GetThresholdsResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/thresholds",
+            Policy::Role(HouseholdRole::Member),
+            post_with(create_threshold_handler, |op| {
+                op.description("Create an alert threshold for a sensor type, optionally scoped to a component")
+                    .tag("Fire alert")
+                    .response::<200, Json<ThresholdMessageResponse>>()
+                    .response::<400, Json<ThresholdMessageResponse>>()
+                    .response::<403, Json<ThresholdMessageResponse>>()
+                    .response::<409, Json<ThresholdMessageResponse>>()
+                    .response::<500, Json<ThresholdMessageResponse>>()
+            })
+            .patch_with(update_threshold_handler, |op| {
+                op.description("Update an existing alert threshold")
+                    .tag("Fire alert")
+                    .response::<200, Json<ThresholdMessageResponse>>()
+                    .response::<403, Json<ThresholdMessageResponse>>()
+                    .response::<404, Json<ThresholdMessageResponse>>()
+                    .response::<500, Json<ThresholdMessageResponse>>()
+            })
+            .delete_with(delete_threshold_handler, |op| {
+                op.description("Delete an alert threshold")
+                    .tag("Fire alert")
+                    .response::<200, Json<ThresholdMessageResponse>>()
+                    .response::<403, Json<ThresholdMessageResponse>>()
+                    .response::<404, Json<ThresholdMessageResponse>>()
+                    .response::<500, Json<ThresholdMessageResponse>>()
+            }),
+        )
+}
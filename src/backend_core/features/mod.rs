@@ -3,6 +3,11 @@ use std::{any::Any, sync::{Arc, Weak}};
 use aide::axum::ApiRouter;
 use axum::async_trait;
 
+use super::{
+    channels::{self, EventSubscriber},
+    events::{BusEvent, FeatureEvent},
+};
+
 #[async_trait]
 pub trait IotFeature {
     fn create(
@@ -34,6 +39,20 @@ pub trait IotFeature {
     fn get_mqttc(&mut self) -> rumqttc::AsyncClient;
     fn get_mongoc(&mut self) -> mongodb::Client;
 
+    fn publish_event(&self, event: impl Into<FeatureEvent>)
+    where
+        Self: Sized,
+    {
+        channels::publish_event(event)
+    }
+
+    fn subscribe<E: BusEvent>(&self) -> EventSubscriber<E>
+    where
+        Self: Sized,
+    {
+        channels::subscribe()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn Any>;
 }
 
@@ -61,6 +80,20 @@ pub trait WebFeature {
     async fn send_message_to_iot(&self, message: String) -> String;
     async fn respond_message_from_iot(&self, message: String) -> String;
 
+    fn publish_event(&self, event: impl Into<FeatureEvent>)
+    where
+        Self: Sized,
+    {
+        channels::publish_event(event)
+    }
+
+    fn subscribe<E: BusEvent>(&self) -> EventSubscriber<E>
+    where
+        Self: Sized,
+    {
+        channels::subscribe()
+    }
+
     fn into_any(self: Arc<Self>) -> Arc<dyn Any>;
 }
 
@@ -0,0 +1,282 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOneAndUpdateOptions, FindOptions, IndexOptions, ReturnDocument},
+    Collection, IndexModel,
+};
+
+use super::{
+    iot::mqtt_messages::{BuzzerRemoteControlCommand, LightRemoteControlCommand},
+    models::{CommandRecord, CommandStatus, RemoteCommand},
+    notifications::RemoteControlWebNotification,
+};
+use crate::{errors::AppError, publish_mqtt_message::publish_mqtt_message};
+
+// Queued commands are dropped after this long unless the caller asks otherwise,
+// so that a "buzzer on" is never replayed long after anyone wanted it
+pub const DEFAULT_COMMAND_EXPIRY_SECS: u64 = 300;
+pub const MAX_COMMAND_EXPIRY_SECS: u64 = 24 * 3600;
+// HTTP requests waiting for an acknowledgement are never held open longer
+pub const MAX_ACK_TIMEOUT_MS: u64 = 30_000;
+
+// Commands that may still be delivered, as long as they have not expired
+const UNDELIVERED_STATUSES: [CommandStatus; 2] = [CommandStatus::Pending, CommandStatus::TimedOut];
+
+fn to_secs(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64
+}
+
+pub fn get_command_coll(mongoc: &mongodb::Client) -> Collection<CommandRecord> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("remote_control_commands")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_command_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "command_id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "owner_name": 1, "created_at.secs_since_epoch": -1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "status": 1, "created_at.secs_since_epoch": 1 })
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "owner_name": 1, "status": 1, "expires_at.secs_since_epoch": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn create_command(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    client_id: String,
+    device_id: usize,
+    component_id: usize,
+    command: RemoteCommand,
+    expires_in: Duration,
+) -> mongodb::error::Result<CommandRecord> {
+    let now = SystemTime::now();
+    let record = CommandRecord {
+        command_id: uuid::Uuid::now_v7().into(),
+        owner_name,
+        client_id,
+        device_id,
+        component_id,
+        command,
+        status: CommandStatus::Pending,
+        error: None,
+        created_at: now,
+        updated_at: now,
+        expires_at: Some(now + expires_in),
+        delivery_attempts: 0,
+        last_delivered_at: None,
+    };
+    get_command_coll(mongoc).insert_one(&record, None).await?;
+    Ok(record)
+}
+
+// Moves a command of `owner_name` out of `from` into `to`. Returns the updated
+// command, or `None` when it does not exist or is no longer in `from`.
+pub async fn transition_command(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    command_id: String,
+    from: &[CommandStatus],
+    to: CommandStatus,
+    error: Option<String>,
+) -> mongodb::error::Result<Option<CommandRecord>> {
+    let from = from
+        .iter()
+        .map(|status| to_bson(status).unwrap())
+        .collect::<Vec<_>>();
+    get_command_coll(mongoc)
+        .find_one_and_update(
+            doc! {
+                "command_id": command_id,
+                "owner_name": owner_name,
+                "status": { "$in": from },
+            },
+            doc! { "$set": {
+                "status": to_bson(&to).unwrap(),
+                "error": error,
+                "updated_at": to_bson(&SystemTime::now()).unwrap(),
+            } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+}
+
+// The notification the `/light` and `/buzzer` routes send to the IoT feature,
+// for callers that issue commands on behalf of the user.
+pub fn to_web_notification(
+    owner_name: String,
+    client_id: String,
+    device_id: usize,
+    component_id: usize,
+    command: RemoteCommand,
+    ack_timeout_ms: Option<u64>,
+    expires_in_secs: Option<u64>,
+) -> RemoteControlWebNotification {
+    match command {
+        RemoteCommand::Light(command) => RemoteControlWebNotification::LightCommandNotification {
+            device_id,
+            component_id,
+            command,
+            client_id,
+            owner_name,
+            ack_timeout_ms,
+            expires_in_secs,
+        },
+        RemoteCommand::Buzzer(command) => RemoteControlWebNotification::BuzzerCommandNotification {
+            device_id,
+            component_id,
+            command,
+            client_id,
+            owner_name,
+            ack_timeout_ms,
+            expires_in_secs,
+        },
+    }
+}
+
+// Gives up on every command that has been pending for longer than `timeout`.
+pub async fn expire_pending_commands(
+    mongoc: &mongodb::Client,
+    timeout: Duration,
+) -> mongodb::error::Result<u64> {
+    let cutoff = to_secs(SystemTime::now().checked_sub(timeout).unwrap_or(UNIX_EPOCH));
+    let result = get_command_coll(mongoc)
+        .update_many(
+            doc! {
+                "status": to_bson(&CommandStatus::Pending).unwrap(),
+                "created_at.secs_since_epoch": { "$lt": cutoff },
+            },
+            doc! { "$set": {
+                "status": to_bson(&CommandStatus::TimedOut).unwrap(),
+                "updated_at": to_bson(&SystemTime::now()).unwrap(),
+            } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count)
+}
+
+// Publishes a command to `<client_id>/remote-control-command` and counts the
+// attempt. Gateways must treat a repeated `command_id` as already executed.
+pub async fn publish_command(
+    mongoc: &mongodb::Client,
+    mqttc: rumqttc::AsyncClient,
+    record: &CommandRecord,
+) -> Result<(), AppError> {
+    let client_id = record.client_id.clone();
+    let feature_name = String::from("remote-control");
+    match record.command {
+        RemoteCommand::Light(command) => {
+            let message = LightRemoteControlCommand {
+                command_id: record.command_id.clone(),
+                device_id: record.device_id,
+                component_id: record.component_id,
+                command,
+            };
+            publish_mqtt_message(message, mqttc, client_id, feature_name).await?;
+        }
+        RemoteCommand::Buzzer(command) => {
+            let message = BuzzerRemoteControlCommand {
+                command_id: record.command_id.clone(),
+                device_id: record.device_id,
+                component_id: record.component_id,
+                command,
+            };
+            publish_mqtt_message(message, mqttc, client_id, feature_name).await?;
+        }
+    }
+
+    // The command is out, so failing to count it must not report a failure
+    if let Err(e) = get_command_coll(mongoc)
+        .update_one(
+            doc! { "command_id": record.command_id.clone() },
+            doc! {
+                "$set": { "last_delivered_at": to_bson(&SystemTime::now()).unwrap() },
+                "$inc": { "delivery_attempts": 1 },
+            },
+            None,
+        )
+        .await
+    {
+        eprintln!("Failed to count delivery of command '{}': {}", record.command_id, e);
+    }
+    Ok(())
+}
+
+// Replays, oldest first, every command of `owner_name` that was never
+// acknowledged and has not expired yet. Called when a gateway reconnects.
+pub async fn redeliver_commands(
+    mongoc: &mongodb::Client,
+    mqttc: rumqttc::AsyncClient,
+    owner_name: String,
+) -> Result<u64, AppError> {
+    let statuses = UNDELIVERED_STATUSES
+        .iter()
+        .map(|status| to_bson(status).unwrap())
+        .collect::<Vec<_>>();
+    let find_options = FindOptions::builder()
+        .sort(doc! { "created_at.secs_since_epoch": 1, "created_at.nanos_since_epoch": 1 })
+        .build();
+    let mut cursor = get_command_coll(mongoc)
+        .find(
+            doc! {
+                "owner_name": owner_name,
+                "status": { "$in": statuses },
+                "expires_at.secs_since_epoch": { "$gt": to_secs(SystemTime::now()) },
+            },
+            find_options,
+        )
+        .await?;
+    let mut queued = vec![];
+    while cursor.advance().await? {
+        queued.push(cursor.deserialize_current()?);
+    }
+
+    let mut redelivered = 0;
+    for record in queued {
+        publish_command(mongoc, mqttc.clone(), &record).await?;
+        redelivered += 1;
+    }
+    Ok(redelivered)
+}
+
+// Drops every undelivered command past its expiry from the queue.
+pub async fn expire_stale_commands(mongoc: &mongodb::Client) -> mongodb::error::Result<u64> {
+    let statuses = UNDELIVERED_STATUSES
+        .iter()
+        .map(|status| to_bson(status).unwrap())
+        .collect::<Vec<_>>();
+    let result = get_command_coll(mongoc)
+        .update_many(
+            doc! {
+                "status": { "$in": statuses },
+                "expires_at.secs_since_epoch": { "$lte": to_secs(SystemTime::now()) },
+            },
+            doc! { "$set": {
+                "status": to_bson(&CommandStatus::Expired).unwrap(),
+                "updated_at": to_bson(&SystemTime::now()).unwrap(),
+            } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count)
+}
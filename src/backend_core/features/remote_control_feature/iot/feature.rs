@@ -1,14 +1,16 @@
-use axum::{async_trait, http::StatusCode};
+use axum::async_trait;
 use rumqttc::{Event, Incoming, Publish};
-use std::{any::Any, sync::{Arc, Weak}};
-use tokio::sync::Mutex;
-use crate::{backend_core::{
+use std::{any::Any, collections::HashMap, sync::{Arc, Weak}, time::Duration};
+use tokio::sync::{oneshot, Mutex};
+use crate::{auth::get_email_from_topic_token, backend_core::{
+    events::CommandAcked,
     features::{
-        remote_control_feature::{notifications::{RemoteControlWebNotification, RemoteControlIotNotification}, web::WebRemoteControlFeature}, IotFeature, WebFeature
+        devices_status_feature::heartbeat::record_heartbeat,
+        remote_control_feature::{commands::{create_command, publish_command, transition_command, DEFAULT_COMMAND_EXPIRY_SECS, MAX_ACK_TIMEOUT_MS, MAX_COMMAND_EXPIRY_SECS}, models::{CommandStatus, RemoteCommand}, notifications::{RemoteControlWebNotification, RemoteControlIotNotification}, web::WebRemoteControlFeature}, IotFeature, WebFeature
     }, utils::non_primitive_cast,
-}, publish_mqtt_message::publish_mqtt_message};
+}};
 
-use super::mqtt_messages::{BuzzerRemoteControlCommand, LightRemoteControlCommand};
+use super::mqtt_messages::{CommandAckData, RemoteControlMQTTMessage};
 
 #[derive(Clone)]
 pub struct IotRemoteControlFeature {
@@ -17,9 +19,53 @@ pub struct IotRemoteControlFeature {
     mongoc: mongodb::Client,
     web_instance: Option<Weak<WebRemoteControlFeature>>,
     jwt_key: String,
+    // HTTP requests waiting for the gateway to acknowledge a command
+    ack_waiters: Arc<Mutex<HashMap<String, oneshot::Sender<CommandStatus>>>>,
 }
 
-impl IotRemoteControlFeature {}
+fn notification(status_code: usize, message: &str, command_id: Option<String>, command_status: Option<CommandStatus>) -> String {
+    serde_json::to_string(&RemoteControlIotNotification {
+        status_code,
+        message: String::from(message),
+        command_id,
+        command_status,
+    }).unwrap()
+}
+
+impl IotRemoteControlFeature {
+    async fn acknowledge_commands(&self, owner_name: String, data: Vec<CommandAckData>) {
+        for CommandAckData { command_id, success, error } in data {
+            let status = if success { CommandStatus::Acked } else { CommandStatus::Failed };
+            // A late ack still tells us what the gateway did with a timed-out command
+            match transition_command(&self.mongoc, owner_name.clone(), command_id.clone(), &[CommandStatus::Pending, CommandStatus::TimedOut], status, error).await {
+                Ok(Some(record)) => {
+                    // An acknowledgement is traffic from the component like any other
+                    if let (Ok(device_id), Ok(component)) = (u32::try_from(record.device_id), u32::try_from(record.component_id)) {
+                        record_heartbeat(&self.mongoc, record.owner_name.clone(), device_id, Some(component)).await;
+                    }
+                    if let Some(waiter) = self.ack_waiters.lock().await.remove(&command_id) {
+                        let _ = waiter.send(status);
+                    }
+                    self.publish_event(CommandAcked {
+                        owner_name: record.owner_name,
+                        command_id: record.command_id,
+                        device_id: record.device_id,
+                        component_id: record.component_id,
+                        command: record.command,
+                        status: record.status,
+                        error: record.error,
+                    });
+                }
+                Ok(None) => {
+                    eprintln!("Command '{}' of user '{}' is not awaiting an acknowledgement", command_id, owner_name);
+                }
+                Err(_) => {
+                    eprintln!("Failed to process acknowledgement of command '{}'", command_id);
+                }
+            }
+        }
+    }
+}
 
 #[async_trait]
 impl IotFeature for IotRemoteControlFeature {
@@ -35,6 +81,7 @@ impl IotFeature for IotRemoteControlFeature {
             mongoc: mongoc.clone(),
             web_instance: None,
             jwt_key,
+            ack_waiters: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 
@@ -60,9 +107,24 @@ impl IotFeature for IotRemoteControlFeature {
     async fn process_next_mqtt_message(&mut self) {
         let mut mongoc = self.get_mongoc();
         let mut mqtt_event_loop = self.mqtt_event_loop.lock().await;
-        if let Ok(Event::Incoming(Incoming::Publish(Publish { payload, .. }))) =
+        if let Ok(Event::Incoming(Incoming::Publish(Publish { topic, payload, .. }))) =
             mqtt_event_loop.poll().await
         {
+            match String::from_utf8(payload.to_vec())
+                .ok()
+                .and_then(|raw_json| serde_json::from_str::<RemoteControlMQTTMessage>(raw_json.as_ref()).ok())
+            {
+                Some(RemoteControlMQTTMessage::CommandAck { token, data }) => {
+                    if let Some(username) = get_email_from_topic_token(self.jwt_key.as_str(), &topic, token, &mut mongoc).await {
+                        self.acknowledge_commands(username, data).await;
+                    } else {
+                        eprintln!("Invalid token");
+                    }
+                }
+                None => {
+                    eprintln!("Failed to process MQTT message");
+                }
+            }
         }
     }
 
@@ -83,30 +145,51 @@ impl IotFeature for IotRemoteControlFeature {
 
     async fn respond_message_from_web(&self, message: String) -> String {
         let notif = serde_json::from_str(message.as_str()).unwrap();
-        let send_res = match notif {
-            RemoteControlWebNotification::LightCommandNotification { device_id, component_id, command, client_id } => {
-                let message = LightRemoteControlCommand { device_id, component_id, command };
-                publish_mqtt_message(message, self.mqttc.clone(), client_id, self.get_module_name()).await 
+        let (device_id, component_id, command, client_id, owner_name, ack_timeout_ms, expires_in_secs) = match notif {
+            RemoteControlWebNotification::LightCommandNotification { device_id, component_id, command, client_id, owner_name, ack_timeout_ms, expires_in_secs } => {
+                (device_id, component_id, RemoteCommand::Light(command), client_id, owner_name, ack_timeout_ms, expires_in_secs)
             },
-            RemoteControlWebNotification::BuzzerCommandNotification { device_id, component_id, command, client_id } => {
-                let message = BuzzerRemoteControlCommand { device_id, component_id, command };
-                publish_mqtt_message(message, self.mqttc.clone(), client_id, self.get_module_name()).await 
+            RemoteControlWebNotification::BuzzerCommandNotification { device_id, component_id, command, client_id, owner_name, ack_timeout_ms, expires_in_secs } => {
+                (device_id, component_id, RemoteCommand::Buzzer(command), client_id, owner_name, ack_timeout_ms, expires_in_secs)
             }
         };
-        let resp = if let Ok(_) = send_res {
-            RemoteControlIotNotification {
-                status_code: 200,
-                message: String::from("Published message successfully"), 
-            }
-        } else {
-            RemoteControlIotNotification {
-                status_code: 500,
-                message: String::from("Internal server error"), 
+
+        let expires_in = Duration::from_secs(expires_in_secs.unwrap_or(DEFAULT_COMMAND_EXPIRY_SECS).min(MAX_COMMAND_EXPIRY_SECS));
+        let Ok(record) = create_command(&self.mongoc, owner_name.clone(), client_id, device_id, component_id, command, expires_in).await else {
+            return notification(500, "Internal server error", None, None);
+        };
+        let command_id = record.command_id.clone();
+
+        // Registered before publishing so that a fast ack cannot be missed
+        let waiter = match ack_timeout_ms {
+            Some(timeout_ms) => {
+                let (sender, receiver) = oneshot::channel();
+                self.ack_waiters.lock().await.insert(command_id.clone(), sender);
+                Some((timeout_ms.min(MAX_ACK_TIMEOUT_MS), receiver))
             }
+            None => None,
         };
-        serde_json::to_string(&resp).unwrap()
+
+        let send_res = publish_command(&self.mongoc, self.mqttc.clone(), &record).await;
+        if send_res.is_err() {
+            self.ack_waiters.lock().await.remove(&command_id);
+            let _ = transition_command(&self.mongoc, owner_name, command_id.clone(), &[CommandStatus::Pending], CommandStatus::Failed, Some(String::from("Failed to publish command"))).await;
+            return notification(500, "Internal server error", Some(command_id), Some(CommandStatus::Failed));
+        }
+
+        let Some((timeout_ms, receiver)) = waiter else {
+            return notification(202, "Published command, awaiting acknowledgement", Some(command_id), Some(CommandStatus::Pending));
+        };
+        match tokio::time::timeout(Duration::from_millis(timeout_ms), receiver).await {
+            Ok(Ok(CommandStatus::Acked)) => notification(200, "Command acknowledged", Some(command_id), Some(CommandStatus::Acked)),
+            Ok(Ok(status)) => notification(502, "Gateway failed to execute command", Some(command_id), Some(status)),
+            _ => {
+                self.ack_waiters.lock().await.remove(&command_id);
+                notification(504, "Timed out waiting for acknowledgement, the command stays queued until it expires", Some(command_id), Some(CommandStatus::Pending))
+            }
+        }
     }
-    
+
     fn into_any(self: Arc<Self>) -> Arc<dyn Any> {
         self
     }
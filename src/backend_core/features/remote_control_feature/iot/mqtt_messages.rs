@@ -1,9 +1,12 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::backend_core::features::remote_control_feature::models::{BuzzerCommand, LightCommand};
 
+type Token = String;
+
 #[derive(Serialize)]
 pub struct LightRemoteControlCommand {
+    pub command_id: String,
     pub device_id: usize,
     pub component_id: usize,
     pub command: LightCommand,
@@ -11,7 +14,74 @@ pub struct LightRemoteControlCommand {
 
 #[derive(Serialize)]
 pub struct BuzzerRemoteControlCommand {
+    pub command_id: String,
     pub device_id: usize,
     pub component_id: usize,
     pub command: BuzzerCommand,
 }
+
+// Sent by gateways on `<client_id>/remote-control-metrics`
+#[derive(Deserialize, Serialize)]
+#[cfg_attr(test, derive(std::cmp::PartialEq, Debug))]
+#[serde(tag = "kind", content = "payload")]
+pub enum RemoteControlMQTTMessage {
+    #[serde(rename = "0")]
+    CommandAck {
+        token: Token,
+        data: Vec<CommandAckData>,
+    },
+}
+
+#[derive(Deserialize, Serialize)]
+#[cfg_attr(test, derive(std::cmp::PartialEq, Debug))]
+pub struct CommandAckData {
+    pub command_id: String,
+    pub success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[cfg(test)]
+mod deserialize_tests {
+    use super::{CommandAckData, RemoteControlMQTTMessage, Token};
+
+    #[test]
+    fn deserialize_commandack() {
+        let input = r#"{
+            "kind": "0",
+            "payload": {
+                "token": "abcd",
+                "data": [
+                    {
+                        "command_id": "0190a1b2",
+                        "success": true
+                    },
+                    {
+                        "command_id": "0190a1b3",
+                        "success": false,
+                        "error": "component not responding"
+                    }
+                ]
+            }
+        }"#;
+
+        let result: RemoteControlMQTTMessage = serde_json::from_str(input).unwrap();
+        let expected = RemoteControlMQTTMessage::CommandAck {
+            token: Token::from("abcd"),
+            data: vec![
+                CommandAckData {
+                    command_id: String::from("0190a1b2"),
+                    success: true,
+                    error: None,
+                },
+                CommandAckData {
+                    command_id: String::from("0190a1b3"),
+                    success: false,
+                    error: Some(String::from("component not responding")),
+                },
+            ],
+        };
+
+        assert_eq!(result, expected);
+    }
+}
@@ -1,6 +1,9 @@
+pub mod commands;
 mod iot;
 pub mod models;
 mod notifications;
+pub mod scenes;
+pub mod schedules;
 mod web;
 
 pub use iot::IotRemoteControlFeature as IotFeature;
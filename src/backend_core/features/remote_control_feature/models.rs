@@ -1,7 +1,9 @@
+use std::time::SystemTime;
+
 use schemars::JsonSchema;
 use serde::{Serialize, Deserialize};
 
-#[derive(Serialize, Deserialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug)]
 pub enum BuzzerCommand {
     #[serde(rename = "toggle")]
     Toggle,
@@ -11,7 +13,7 @@ pub enum BuzzerCommand {
     Off,
 }
 
-#[derive(Serialize, Deserialize, JsonSchema)]
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug)]
 pub enum LightCommand {
     #[serde(rename = "toggle")]
     Toggle,
@@ -20,3 +22,135 @@ pub enum LightCommand {
     #[serde(rename = "off")]
     Off,
 }
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, Debug)]
+#[serde(tag = "target", content = "command")]
+pub enum RemoteCommand {
+    #[serde(rename = "light")]
+    Light(LightCommand),
+    #[serde(rename = "buzzer")]
+    Buzzer(BuzzerCommand),
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum CommandStatus {
+    #[serde(rename = "pending")]
+    Pending,
+    #[serde(rename = "acked")]
+    Acked,
+    #[serde(rename = "failed")]
+    Failed,
+    #[serde(rename = "timed_out")]
+    TimedOut,
+    #[serde(rename = "expired")]
+    Expired,
+}
+
+// Every command sent to a gateway, one document per command in
+// `remote_control_commands`. Commands that are neither acknowledged nor
+// expired double as the queue that is replayed when the gateway reconnects.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct CommandRecord {
+    pub command_id: String,
+    pub owner_name: String,
+    pub client_id: String,
+    pub device_id: usize,
+    pub component_id: usize,
+    pub command: RemoteCommand,
+    pub status: CommandStatus,
+    pub error: Option<String>,
+    pub created_at: SystemTime,
+    pub updated_at: SystemTime,
+    #[serde(default)]
+    pub expires_at: Option<SystemTime>,
+    #[serde(default)]
+    pub delivery_attempts: u32,
+    #[serde(default)]
+    pub last_delivered_at: Option<SystemTime>,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum ScheduleTrigger {
+    #[serde(rename = "once")]
+    Once { at: SystemTime },
+    // Five field cron expression evaluated at the given offset from UTC
+    #[serde(rename = "cron")]
+    Cron {
+        expression: String,
+        #[serde(default)]
+        utc_offset_minutes: i32,
+    },
+}
+
+// A command to send on a trigger, one document per schedule in
+// `remote_control_schedules`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Schedule {
+    pub id: String,
+    pub owner_name: String,
+    pub name: String,
+    pub device_id: usize,
+    pub component_id: usize,
+    pub command: RemoteCommand,
+    pub trigger: ScheduleTrigger,
+    pub enabled: bool,
+    pub next_run_at: Option<SystemTime>,
+    pub last_run_at: Option<SystemTime>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ScheduleRunOutcome {
+    #[serde(rename = "dispatched")]
+    Dispatched,
+    #[serde(rename = "missed")]
+    Missed,
+    #[serde(rename = "failed")]
+    Failed,
+}
+
+// One document per execution of a schedule in `remote_control_schedule_runs`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct ScheduleRun {
+    pub schedule_id: String,
+    pub owner_name: String,
+    pub scheduled_for: Option<SystemTime>,
+    pub ran_at: SystemTime,
+    pub outcome: ScheduleRunOutcome,
+    pub command_id: Option<String>,
+    pub message: String,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+#[serde(tag = "kind")]
+pub enum SceneAction {
+    #[serde(rename = "command")]
+    Command {
+        device_id: usize,
+        component_id: usize,
+        command: RemoteCommand,
+    },
+    // Every light of the room, or of every device of the user without a room
+    #[serde(rename = "all_lights")]
+    AllLights {
+        room_name: Option<String>,
+        command: LightCommand,
+    },
+    #[serde(rename = "all_buzzers")]
+    AllBuzzers {
+        room_name: Option<String>,
+        command: BuzzerCommand,
+    },
+}
+
+// A named batch of commands sent together, one document per scene in
+// `remote_control_scenes`
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Debug)]
+pub struct Scene {
+    pub id: String,
+    pub owner_name: String,
+    pub name: String,
+    pub actions: Vec<SceneAction>,
+    pub created_at: SystemTime,
+}
@@ -1,11 +1,15 @@
 use serde::{Deserialize, Serialize};
 
-use super::models::{BuzzerCommand, LightCommand};
+use super::models::{BuzzerCommand, CommandStatus, LightCommand};
 
 #[derive(Serialize, Deserialize)]
 pub struct RemoteControlIotNotification {
     pub status_code: usize,
     pub message: String,
+    #[serde(default)]
+    pub command_id: Option<String>,
+    #[serde(default)]
+    pub command_status: Option<CommandStatus>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -15,11 +19,19 @@ pub enum RemoteControlWebNotification {
         component_id: usize,
         command: BuzzerCommand,
         client_id: String,
+        owner_name: String,
+        // How long to wait for the gateway to acknowledge, if at all
+        ack_timeout_ms: Option<u64>,
+        // How long the command may stay queued for an offline gateway
+        expires_in_secs: Option<u64>,
     },
     LightCommandNotification {
         device_id: usize,
         component_id: usize,
         command: LightCommand,
         client_id: String,
+        owner_name: String,
+        ack_timeout_ms: Option<u64>,
+        expires_in_secs: Option<u64>,
     },
 }
@@ -0,0 +1,173 @@
+use mongodb::{
+    bson::doc,
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+
+use super::models::{RemoteCommand, Scene, SceneAction};
+use crate::backend_core::{
+    features::devices_status_feature::{iot::mqtt_messages::ComponentType, models::Device},
+    models::Room,
+};
+
+pub fn get_scene_coll(mongoc: &mongodb::Client) -> Collection<Scene> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("remote_control_scenes")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_scene_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "owner_name": 1, "name": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+fn is_light(kind: &ComponentType) -> bool {
+    matches!(kind, ComponentType::GeneralLight | ComponentType::FireLight)
+}
+
+fn is_buzzer(kind: &ComponentType) -> bool {
+    matches!(kind, ComponentType::GeneralBuzzer | ComponentType::FireBuzzer)
+}
+
+// The `(device_id, component_id)` of every component of the given kind.
+pub fn matching_components(
+    devices: &[Device],
+    is_kind: fn(&ComponentType) -> bool,
+) -> Vec<(usize, usize)> {
+    devices
+        .iter()
+        .flat_map(|device| {
+            device
+                .components
+                .iter()
+                .filter(|component| is_kind(&component.kind))
+                .map(|component| (device.id as usize, component.id as usize))
+        })
+        .collect()
+}
+
+async fn find_devices(
+    mongoc: &mongodb::Client,
+    owner_name: &str,
+    room_name: Option<&str>,
+) -> mongodb::error::Result<Vec<Device>> {
+    let database = mongoc.default_database().unwrap();
+    let mut filter = doc! { "owner_name": owner_name };
+    if let Some(room_name) = room_name {
+        let room = database
+            .collection::<Room>("rooms")
+            .find_one(doc! { "owner_name": owner_name, "name": room_name }, None)
+            .await?;
+        let Some(room) = room else {
+            return Ok(vec![]);
+        };
+        filter.insert("id", doc! { "$in": room.devices });
+    }
+
+    let mut cursor = database
+        .collection::<Device>("devices")
+        .find(filter, None)
+        .await?;
+    let mut devices = vec![];
+    while cursor.advance().await? {
+        match cursor.deserialize_current() {
+            Ok(device) => devices.push(device),
+            Err(e) => eprintln!("Error deserializing device: {}", e),
+        }
+    }
+    Ok(devices)
+}
+
+// Resolves the actions of a scene into individual commands. A component
+// targeted by several actions only receives the first command for it.
+pub async fn expand_scene(
+    mongoc: &mongodb::Client,
+    scene: &Scene,
+) -> mongodb::error::Result<Vec<(usize, usize, RemoteCommand)>> {
+    let mut commands: Vec<(usize, usize, RemoteCommand)> = vec![];
+    for action in &scene.actions {
+        let targets = match action {
+            SceneAction::Command {
+                device_id,
+                component_id,
+                command,
+            } => vec![(*device_id, *component_id, *command)],
+            SceneAction::AllLights { room_name, command } => {
+                let devices = find_devices(mongoc, &scene.owner_name, room_name.as_deref()).await?;
+                matching_components(&devices, is_light)
+                    .into_iter()
+                    .map(|(device_id, component_id)| {
+                        (device_id, component_id, RemoteCommand::Light(*command))
+                    })
+                    .collect()
+            }
+            SceneAction::AllBuzzers { room_name, command } => {
+                let devices = find_devices(mongoc, &scene.owner_name, room_name.as_deref()).await?;
+                matching_components(&devices, is_buzzer)
+                    .into_iter()
+                    .map(|(device_id, component_id)| {
+                        (device_id, component_id, RemoteCommand::Buzzer(*command))
+                    })
+                    .collect()
+            }
+        };
+        for target in targets {
+            if !commands
+                .iter()
+                .any(|(device_id, component_id, _)| (*device_id, *component_id) == (target.0, target.1))
+            {
+                commands.push(target);
+            }
+        }
+    }
+    Ok(commands)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend_core::features::devices_status_feature::models::Component;
+
+    fn device(id: u32, kinds: Vec<ComponentType>) -> Device {
+        Device {
+            id,
+            battery_logs: vec![],
+            error_logs: vec![],
+            components: kinds
+                .into_iter()
+                .enumerate()
+                .map(|(index, kind)| Component {
+                    id: index as u32,
+                    kind,
+                    logs: vec![],
+                })
+                .collect(),
+            owner_name: String::from("owner"),
+        }
+    }
+
+    #[test]
+    fn selects_general_and_fire_components_of_a_kind() {
+        let devices = vec![
+            device(1, vec![ComponentType::Smoke, ComponentType::FireLight, ComponentType::FireBuzzer]),
+            device(2, vec![ComponentType::GeneralLight, ComponentType::GeneralBuzzer]),
+        ];
+        assert_eq!(matching_components(&devices, is_light), vec![(1, 1), (2, 0)]);
+        assert_eq!(matching_components(&devices, is_buzzer), vec![(1, 2), (2, 1)]);
+    }
+}
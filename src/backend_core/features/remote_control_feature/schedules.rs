@@ -0,0 +1,385 @@
+use std::{
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, FixedOffset, NaiveDate, TimeZone, Timelike,
+    Utc,
+};
+use mongodb::{
+    bson::{doc, to_bson},
+    options::IndexOptions,
+    Collection, IndexModel,
+};
+
+use super::{
+    commands::{to_web_notification, DEFAULT_COMMAND_EXPIRY_SECS},
+    models::{Schedule, ScheduleRun, ScheduleTrigger},
+    notifications::RemoteControlWebNotification,
+};
+
+// Upper bound on how far `next_after` walks before deciding an expression can
+// never fire, such as the 30th of February
+const MAX_CRON_STEPS: usize = 100_000;
+
+// Real time zones are all within 18 hours of UTC
+pub const MAX_UTC_OFFSET_MINUTES: i32 = 18 * 60;
+
+// A standard five field cron expression: minute, hour, day of month, month and
+// day of week (0 or 7 is Sunday). Fields accept `*`, lists, ranges and steps.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CronExpression {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    days_of_month_restricted: bool,
+    days_of_week_restricted: bool,
+}
+
+fn parse_number(value: &str, min: u32, max: u32) -> Result<u32, String> {
+    let number = value
+        .parse::<u32>()
+        .map_err(|_| format!("'{value}' is not a number"))?;
+    if number < min || number > max {
+        return Err(format!("{number} is outside {min}-{max}"));
+    }
+    Ok(number)
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, String> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = parse_number(step, 1, max.max(1))?;
+                (range, Some(step))
+            }
+            None => (part, None),
+        };
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (parse_number(start, min, max)?, parse_number(end, min, max)?)
+        } else {
+            let start = parse_number(range, min, max)?;
+            // `5/15` means every 15 starting at 5
+            (start, if step.is_some() { max } else { start })
+        };
+        if start > end {
+            return Err(format!("range '{range}' is reversed"));
+        }
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+fn has(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+impl FromStr for CronExpression {
+    type Err = String;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let expression = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" => "0 0 1 1 *",
+            expression => expression,
+        };
+        let fields = expression.split_whitespace().collect::<Vec<_>>();
+        let [minute, hour, day_of_month, month, day_of_week] = fields[..] else {
+            return Err(String::from("expected 5 fields"));
+        };
+
+        let mut days_of_week = parse_field(day_of_week, 0, 7)?;
+        if has(days_of_week, 7) {
+            days_of_week = (days_of_week | 1) & !(1 << 7);
+        }
+        Ok(CronExpression {
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days_of_month: parse_field(day_of_month, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            days_of_week,
+            days_of_month_restricted: day_of_month != "*",
+            days_of_week_restricted: day_of_week != "*",
+        })
+    }
+}
+
+impl CronExpression {
+    // As in cron, a day matches either field when both are restricted.
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day_of_month = has(self.days_of_month, date.day());
+        let day_of_week = has(self.days_of_week, date.weekday().num_days_from_sunday());
+        if self.days_of_month_restricted && self.days_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+
+    // The first matching minute strictly after `after`, in its time zone.
+    pub fn next_after(&self, after: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        let mut time = after
+            .naive_local()
+            .with_second(0)?
+            .with_nanosecond(0)?
+            + ChronoDuration::try_minutes(1)?;
+
+        for _ in 0..MAX_CRON_STEPS {
+            let date = time.date();
+            if !has(self.months, date.month()) {
+                let (year, month) = if date.month() == 12 {
+                    (date.year() + 1, 1)
+                } else {
+                    (date.year(), date.month() + 1)
+                };
+                time = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(date) {
+                time = date.succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if !has(self.hours, time.hour()) {
+                time = date.and_hms_opt(time.hour(), 0, 0)? + ChronoDuration::try_hours(1)?;
+            } else if !has(self.minutes, time.minute()) {
+                time += ChronoDuration::try_minutes(1)?;
+            } else {
+                return after.offset().from_local_datetime(&time).single();
+            }
+        }
+        None
+    }
+}
+
+pub fn utc_offset(minutes: i32) -> Result<FixedOffset, String> {
+    Some(minutes)
+        .filter(|minutes| (-MAX_UTC_OFFSET_MINUTES..=MAX_UTC_OFFSET_MINUTES).contains(minutes))
+        .and_then(|minutes| minutes.checked_mul(60))
+        .and_then(FixedOffset::east_opt)
+        .ok_or_else(|| format!("UTC offset must be within {MAX_UTC_OFFSET_MINUTES} minutes"))
+}
+
+fn to_offset_time(time: SystemTime, offset: FixedOffset) -> DateTime<FixedOffset> {
+    DateTime::<Utc>::from(time).with_timezone(&offset)
+}
+
+// When a trigger should next fire after `after`, or `None` once it never will.
+pub fn next_run(trigger: &ScheduleTrigger, after: SystemTime) -> Result<Option<SystemTime>, String> {
+    match trigger {
+        ScheduleTrigger::Once { at } => Ok((*at > after).then_some(*at)),
+        ScheduleTrigger::Cron {
+            expression,
+            utc_offset_minutes,
+        } => {
+            let cron = expression.parse::<CronExpression>()?;
+            let offset = utc_offset(*utc_offset_minutes)?;
+            Ok(cron
+                .next_after(to_offset_time(after, offset))
+                .map(SystemTime::from))
+        }
+    }
+}
+
+pub fn get_schedule_coll(mongoc: &mongodb::Client) -> Collection<Schedule> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("remote_control_schedules")
+}
+
+pub fn get_schedule_run_coll(mongoc: &mongodb::Client) -> Collection<ScheduleRun> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("remote_control_schedule_runs")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_schedule_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "enabled": 1, "next_run_at.secs_since_epoch": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    get_schedule_run_coll(mongoc)
+        .create_index(
+            IndexModel::builder()
+                .keys(doc! { "owner_name": 1, "schedule_id": 1, "ran_at.secs_since_epoch": -1 })
+                .build(),
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Takes every enabled schedule that is due and moves it on to its next run,
+// returning each claimed schedule as it was before. Advancing is conditional
+// on `next_run_at` being unchanged, so a schedule is claimed only once even
+// if several schedulers race. Runs missed while the server was down collapse
+// into the one claimed now.
+pub async fn claim_due_schedules(
+    mongoc: &mongodb::Client,
+    now: SystemTime,
+) -> mongodb::error::Result<Vec<Schedule>> {
+    let schedule_coll = get_schedule_coll(mongoc);
+    let now_secs = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+
+    let mut due = vec![];
+    let mut cursor = schedule_coll
+        .find(
+            doc! { "enabled": true, "next_run_at.secs_since_epoch": { "$lte": now_secs } },
+            None,
+        )
+        .await?;
+    while cursor.advance().await? {
+        match cursor.deserialize_current() {
+            Ok(schedule) => due.push(schedule),
+            Err(e) => eprintln!("Error deserializing schedule: {}", e),
+        }
+    }
+
+    let mut claimed = vec![];
+    for schedule in due {
+        let next_run_at = next_run(&schedule.trigger, now).unwrap_or(None);
+        let result = schedule_coll
+            .update_one(
+                doc! { "id": schedule.id.clone(), "next_run_at": to_bson(&schedule.next_run_at)? },
+                doc! { "$set": {
+                    "next_run_at": to_bson(&next_run_at)?,
+                    "last_run_at": to_bson(&now)?,
+                    "enabled": next_run_at.is_some(),
+                } },
+                None,
+            )
+            .await?;
+        if result.modified_count == 1 {
+            claimed.push(schedule);
+        }
+    }
+    Ok(claimed)
+}
+
+// Whether a claimed run is still worth executing, or was missed by so much
+// that running it now would surprise the user
+pub fn is_missed(schedule: &Schedule, now: SystemTime, grace: Duration) -> bool {
+    schedule
+        .next_run_at
+        .and_then(|scheduled_for| now.duration_since(scheduled_for).ok())
+        .is_some_and(|late_by| late_by > grace)
+}
+
+pub async fn record_run(mongoc: &mongodb::Client, run: ScheduleRun) -> mongodb::error::Result<()> {
+    get_schedule_run_coll(mongoc).insert_one(run, None).await?;
+    Ok(())
+}
+
+// The notification the `/light` and `/buzzer` routes would send to the IoT
+// feature for the same command.
+pub fn to_notification(schedule: &Schedule, client_id: String) -> RemoteControlWebNotification {
+    to_web_notification(
+        schedule.owner_name.clone(),
+        client_id,
+        schedule.device_id,
+        schedule.component_id,
+        schedule.command,
+        None,
+        Some(DEFAULT_COMMAND_EXPIRY_SECS),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDateTime;
+
+    use super::*;
+
+    fn at(offset_hours: i32, datetime: &str) -> DateTime<FixedOffset> {
+        let naive = NaiveDateTime::parse_from_str(datetime, "%Y-%m-%d %H:%M").unwrap();
+        FixedOffset::east_opt(offset_hours * 3600)
+            .unwrap()
+            .from_local_datetime(&naive)
+            .unwrap()
+    }
+
+    fn next(expression: &str, after: DateTime<FixedOffset>) -> Option<DateTime<FixedOffset>> {
+        expression.parse::<CronExpression>().unwrap().next_after(after)
+    }
+
+    #[test]
+    fn fires_daily_at_a_fixed_local_time() {
+        assert_eq!(
+            next("0 22 * * *", at(7, "2024-03-01 21:30")),
+            Some(at(7, "2024-03-01 22:00"))
+        );
+        assert_eq!(
+            next("0 22 * * *", at(7, "2024-03-01 22:00")),
+            Some(at(7, "2024-03-02 22:00"))
+        );
+    }
+
+    #[test]
+    fn supports_lists_ranges_and_steps() {
+        // Every 15 minutes during working hours on weekdays; 2024-03-02 is a Saturday
+        assert_eq!(
+            next("*/15 9-17 * * 1-5", at(0, "2024-03-01 17:50")),
+            Some(at(0, "2024-03-04 09:00"))
+        );
+        assert_eq!(
+            next("5,35 * * * *", at(0, "2024-03-01 10:06")),
+            Some(at(0, "2024-03-01 10:35"))
+        );
+    }
+
+    #[test]
+    fn monthly_self_test_rolls_over_the_year() {
+        assert_eq!(
+            next("@monthly", at(0, "2024-12-15 08:00")),
+            Some(at(0, "2025-01-01 00:00"))
+        );
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either() {
+        // The 13th or any Friday; 2024-03-08 is a Friday
+        assert_eq!(
+            next("0 0 13 * 5", at(0, "2024-03-02 00:00")),
+            Some(at(0, "2024-03-08 00:00"))
+        );
+    }
+
+    #[test]
+    fn never_fires_on_impossible_dates() {
+        assert_eq!(next("0 0 30 2 *", at(0, "2024-01-01 00:00")), None);
+    }
+
+    #[test]
+    fn rejects_offsets_beyond_real_time_zones() {
+        assert_eq!(utc_offset(-18 * 60), Ok(FixedOffset::west_opt(18 * 3600).unwrap()));
+        assert_eq!(utc_offset(330), Ok(FixedOffset::east_opt(330 * 60).unwrap()));
+        for minutes in [18 * 60 + 1, i32::MAX, i32::MIN] {
+            assert!(utc_offset(minutes).is_err(), "{minutes}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expression in ["* * * *", "60 * * * *", "* 5-2 * * *", "*/0 * * * *", "a * * * *"] {
+            assert!(expression.parse::<CronExpression>().is_err(), "{expression}");
+        }
+    }
+}
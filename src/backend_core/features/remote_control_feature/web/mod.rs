@@ -14,7 +14,7 @@ mod routes;
 pub struct WebRemoteControlFeature {
     mongoc: mongodb::Client,
     iot_instance: Option<Weak<IotRemoteControlFeature>>,
-    jwt_key: String,
+    _jwt_key: String,
 }
 
 #[async_trait]
@@ -26,7 +26,7 @@ impl WebFeature for WebRemoteControlFeature {
         Some(WebRemoteControlFeature {
             mongoc,
             iot_instance: None,
-            jwt_key,
+            _jwt_key: jwt_key,
         })
     }
 
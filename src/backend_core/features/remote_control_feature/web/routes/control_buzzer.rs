@@ -1,23 +1,31 @@
 use aide::axum::{routing::post_with, ApiRouter, IntoApiResponse};
 use axum::{
-    extract::Query, http::{HeaderMap, StatusCode},
+    extract::Query, http::StatusCode,
 };
 use mongodb::bson::doc;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use crate::{auth::get_client_id_from_web_token, backend_core::features::{remote_control_feature::{models::*, notifications::RemoteControlIotNotification, WebNotification}, WebFeature}, json::Json};
+use crate::{auth::get_client_id_from_email, backend_core::features::{remote_control_feature::{models::*, notifications::RemoteControlIotNotification, WebNotification}, WebFeature}, json::Json};
 
 use super::WEB_INSTANCE;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct ControlBuzzerQuery {
     email: String,
+    // Wait up to this long for the gateway to acknowledge the command, at
+    // most `MAX_ACK_TIMEOUT_MS`
+    ack_timeout_ms: Option<u64>,
+    // Drop the command if the gateway has not picked it up within this long
+    expires_in_secs: Option<u64>,
 }
 
 #[derive(Serialize, JsonSchema)]
 pub struct ControlBuzzerResponse {
     message: String,
+    command_id: Option<String>,
+    command_status: Option<CommandStatus>,
 }
 
 #[derive(Deserialize, JsonSchema)]
@@ -28,38 +36,36 @@ pub struct ControlBuzzerRequestBody {
 }
 
 async fn handler(
-    headers: HeaderMap,
-    Query(ControlBuzzerQuery { email }): Query<ControlBuzzerQuery>,
+    Query(ControlBuzzerQuery { email, ack_timeout_ms, expires_in_secs }): Query<ControlBuzzerQuery>,
     Json(ControlBuzzerRequestBody { device_id, component_id, command }): Json<ControlBuzzerRequestBody>,
 ) -> impl IntoApiResponse {
-    let mut web_instance = unsafe {
+    let web_instance = unsafe {
         WEB_INSTANCE.clone().unwrap()        
     };
 
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str()) {  
+    // Members of a household command the gateway of its owner
+    let Some(client_id) = get_client_id_from_email(&web_instance.mongoc, &email).await else {
         return (
-            StatusCode::FORBIDDEN,
+            StatusCode::INTERNAL_SERVER_ERROR,
             Json(ControlBuzzerResponse {
-                message: String::from("Forbidden"),
+                message: String::from("Internal server error"),
+                command_id: None,
+                command_status: None,
             }),
         );
-    }
-        
-    let jwt = String::from(headers.get("jwt").unwrap().to_str().unwrap());
-    let client_id = get_client_id_from_web_token(web_instance.jwt_key.as_str(), jwt, &mut web_instance.mongoc).await.unwrap();
+    };
 
-    let notif = WebNotification::BuzzerCommandNotification { device_id, component_id, command, client_id };
+    let notif = WebNotification::BuzzerCommandNotification { device_id, component_id, command, client_id, owner_name: email, ack_timeout_ms, expires_in_secs };
    
     if let Ok(response) = serde_json::from_str::<RemoteControlIotNotification>(
         &web_instance.clone().send_message_to_iot(serde_json::to_string(&notif).unwrap()).await
     ) {
         return (
-            { if response.status_code == 200 { StatusCode::OK } else { StatusCode::BAD_REQUEST } },
+            StatusCode::from_u16(response.status_code as u16).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
             Json(ControlBuzzerResponse {
                 message: response.message,
+                command_id: response.command_id,
+                command_status: response.command_status,
             }),
         );
     }
@@ -68,19 +74,25 @@ async fn handler(
         StatusCode::INTERNAL_SERVER_ERROR,
         Json(ControlBuzzerResponse {
             message: String::from("Internal server error"),
+            command_id: None,
+            command_status: None,
         }),
     );
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/buzzer",
+        Policy::Role(HouseholdRole::Member),
         post_with(handler, |op| {
             op.description("Control a specific buzzer by email")
                 .tag("Remote control")
                 .response::<200, Json<ControlBuzzerResponse>>()
+                .response::<202, Json<ControlBuzzerResponse>>()
                 .response::<403, Json<ControlBuzzerResponse>>()
                 .response::<500, Json<ControlBuzzerResponse>>()
+                .response::<502, Json<ControlBuzzerResponse>>()
+                .response::<504, Json<ControlBuzzerResponse>>()
         }),
     )
 }
@@ -0,0 +1,105 @@
+use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{
+    bson::{doc, to_bson},
+    options::FindOptions,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::remote_control_feature::models::{CommandRecord, CommandStatus},
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+const MAX_COMMANDS_PER_REQUEST: i64 = 100;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetCommandsQuery {
+    email: String,
+    command_id: Option<String>,
+    status: Option<CommandStatus>,
+    offset: Option<u64>,
+    limit: Option<i64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetCommandsResponse {
+    message: String,
+    commands: Option<Vec<CommandRecord>>,
+}
+
+async fn find_commands(
+    GetCommandsQuery {
+        email,
+        command_id,
+        status,
+        offset,
+        limit,
+    }: GetCommandsQuery,
+) -> mongodb::error::Result<Vec<CommandRecord>> {
+    let mut filter = doc! { "owner_name": email };
+    if let Some(command_id) = command_id {
+        filter.insert("command_id", command_id);
+    }
+    if let Some(status) = status {
+        filter.insert("status", to_bson(&status)?);
+    }
+
+    let find_options = FindOptions::builder()
+        .sort(doc! { "created_at.secs_since_epoch": -1, "created_at.nanos_since_epoch": -1 })
+        .skip(offset)
+        .limit(
+            limit
+                .unwrap_or(MAX_COMMANDS_PER_REQUEST)
+                .min(MAX_COMMANDS_PER_REQUEST),
+        )
+        .build();
+    let mut cursor = get_collection::<CommandRecord>("remote_control_commands")
+        .find(filter, find_options)
+        .await?;
+    let mut commands = vec![];
+    while cursor.advance().await? {
+        commands.push(cursor.deserialize_current()?);
+    }
+    Ok(commands)
+}
+
+async fn handler(Query(query): Query<GetCommandsQuery>) -> impl IntoApiResponse {
+    match find_commands(query).await {
+        Ok(commands) => (
+            StatusCode::OK,
+            Json(GetCommandsResponse {
+                message: String::from("Successfully fetch commands"),
+                commands: Some(commands),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetCommandsResponse {
+                message: String::from("Unexpected error while fetching commands"),
+                commands: None,
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new().authorized_route(
+        "/commands",
+        Policy::Role(HouseholdRole::Viewer),
+        get_with(handler, |op| {
+            op.description("Get sent commands and their delivery status by user email, newest first")
+                .tag("Remote control")
+                .response::<200, Json<GetCommandsResponse>>()
+                .response::<403, Json<GetCommandsResponse>>()
+                .response::<500, Json<GetCommandsResponse>>()
+        }),
+    )
+}
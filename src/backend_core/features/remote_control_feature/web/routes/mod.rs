@@ -1,4 +1,5 @@
 use aide::axum::ApiRouter;
+use mongodb::Collection;
 
 use super::WebRemoteControlFeature;
 
@@ -7,6 +8,15 @@ static mut WEB_INSTANCE: Option<WebRemoteControlFeature> = None;
 
 mod control_light;
 mod control_buzzer;
+mod get_commands;
+mod scenes;
+mod schedules;
+
+#[allow(static_mut_refs)]
+pub fn get_collection<T>(name: &str) -> Collection<T> {
+    let web_instance = unsafe { WEB_INSTANCE.as_ref().unwrap() };
+    web_instance.mongoc.default_database().unwrap().collection(name)
+}
 
 pub fn create_router(web_feature_instance: &mut WebRemoteControlFeature) -> ApiRouter {
     unsafe {
@@ -15,4 +25,7 @@ pub fn create_router(web_feature_instance: &mut WebRemoteControlFeature) -> ApiR
 
     ApiRouter::new().nest("/", control_buzzer::routes())
                     .nest("/", control_light::routes())
+                    .nest("/", get_commands::routes())
+                    .nest("/", schedules::routes())
+                    .nest("/", scenes::routes())
 }
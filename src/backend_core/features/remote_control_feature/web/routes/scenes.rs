@@ -0,0 +1,330 @@
+use std::time::SystemTime;
+
+use aide::axum::{
+    routing::{get_with, post_with},
+    ApiRouter, IntoApiResponse,
+};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, Collection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::get_client_id_from_email,
+    backend_core::features::{
+        remote_control_feature::{
+            commands::to_web_notification,
+            models::{CommandStatus, RemoteCommand, Scene, SceneAction},
+            notifications::RemoteControlIotNotification,
+            scenes::expand_scene,
+        },
+        WebFeature,
+    },
+    json::Json,
+};
+
+use super::{get_collection, WEB_INSTANCE};
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetScenesQuery {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateSceneBody {
+    email: String,
+    name: String,
+    actions: Vec<SceneAction>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpdateSceneBody {
+    email: String,
+    scene_id: String,
+    name: Option<String>,
+    actions: Option<Vec<SceneAction>>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SceneIdentifier {
+    email: String,
+    scene_id: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ActivateSceneBody {
+    email: String,
+    scene_id: String,
+    // Wait up to this long for every gateway acknowledgement, at most
+    // `MAX_ACK_TIMEOUT_MS`
+    ack_timeout_ms: Option<u64>,
+    expires_in_secs: Option<u64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetScenesResponse {
+    message: String,
+    scenes: Option<Vec<Scene>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SceneResponse {
+    message: String,
+    scene: Option<Scene>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SceneCommandResult {
+    device_id: usize,
+    component_id: usize,
+    command: RemoteCommand,
+    status_code: usize,
+    message: String,
+    command_id: Option<String>,
+    command_status: Option<CommandStatus>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ActivateSceneResponse {
+    message: String,
+    results: Option<Vec<SceneCommandResult>>,
+}
+
+fn scene_response(status_code: StatusCode, message: &str, scene: Option<Scene>) -> (StatusCode, Json<SceneResponse>) {
+    (
+        status_code,
+        Json(SceneResponse {
+            message: String::from(message),
+            scene,
+        }),
+    )
+}
+
+fn activate_response(status_code: StatusCode, message: &str, results: Option<Vec<SceneCommandResult>>) -> (StatusCode, Json<ActivateSceneResponse>) {
+    (
+        status_code,
+        Json(ActivateSceneResponse {
+            message: String::from(message),
+            results,
+        }),
+    )
+}
+
+fn get_scene_coll() -> Collection<Scene> {
+    get_collection("remote_control_scenes")
+}
+
+async fn get_scenes_handler(
+    Query(GetScenesQuery { email }): Query<GetScenesQuery>,
+) -> impl IntoApiResponse {
+    match get_scene_coll()
+        .find(doc! { "owner_name": email }, None)
+        .await
+    {
+        Ok(mut cursor) => {
+            let mut scenes = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(scene) => scenes.push(scene),
+                    Err(e) => eprintln!("Error deserializing scene: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetScenesResponse {
+                    message: String::from("Successfully fetch scenes"),
+                    scenes: Some(scenes),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetScenesResponse {
+                message: String::from("Unexpected error while fetching scenes"),
+                scenes: None,
+            }),
+        ),
+    }
+}
+
+async fn create_scene_handler(
+    Json(CreateSceneBody { email, name, actions }): Json<CreateSceneBody>,
+) -> impl IntoApiResponse {
+    let scene = Scene {
+        id: uuid::Uuid::now_v7().into(),
+        owner_name: email,
+        name,
+        actions,
+        created_at: SystemTime::now(),
+    };
+    match get_scene_coll().insert_one(&scene, None).await {
+        Ok(_) => scene_response(StatusCode::OK, "Scene created successfully", Some(scene)),
+        Err(_) => scene_response(StatusCode::BAD_REQUEST, "Failed to create scene, its name may already be taken", None),
+    }
+}
+
+async fn update_scene_handler(
+    Json(UpdateSceneBody {
+        email,
+        scene_id,
+        name,
+        actions,
+    }): Json<UpdateSceneBody>,
+) -> impl IntoApiResponse {
+    let scene_coll = get_scene_coll();
+    let filter = doc! { "id": scene_id, "owner_name": email };
+    let mut scene = match scene_coll.find_one(filter.clone(), None).await {
+        Ok(Some(scene)) => scene,
+        Ok(None) => return scene_response(StatusCode::NOT_FOUND, "Scene not found", None),
+        Err(_) => return scene_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update scene", None),
+    };
+    if let Some(name) = name {
+        scene.name = name;
+    }
+    if let Some(actions) = actions {
+        scene.actions = actions;
+    }
+
+    match scene_coll.replace_one(filter, &scene, None).await {
+        Ok(_) => scene_response(StatusCode::OK, "Scene updated successfully", Some(scene)),
+        Err(_) => scene_response(StatusCode::BAD_REQUEST, "Failed to update scene, its name may already be taken", None),
+    }
+}
+
+async fn delete_scene_handler(
+    Json(SceneIdentifier { email, scene_id }): Json<SceneIdentifier>,
+) -> impl IntoApiResponse {
+    match get_scene_coll()
+        .find_one_and_delete(doc! { "id": scene_id, "owner_name": email }, None)
+        .await
+    {
+        Ok(Some(scene)) => scene_response(StatusCode::OK, "Scene deleted successfully", Some(scene)),
+        Ok(None) => scene_response(StatusCode::NOT_FOUND, "Scene not found", None),
+        Err(_) => scene_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete scene", None),
+    }
+}
+
+// Sends every command of the scene through the IoT feature at once, exactly
+// as `/light` and `/buzzer` would, and reports how each one went.
+async fn activate_scene_handler(
+    Json(ActivateSceneBody {
+        email,
+        scene_id,
+        ack_timeout_ms,
+        expires_in_secs,
+    }): Json<ActivateSceneBody>,
+) -> impl IntoApiResponse {
+    #[allow(static_mut_refs)]
+    let web_instance = unsafe { WEB_INSTANCE.clone().unwrap() };
+    // Members of a household command the gateway of its owner
+    let Some(client_id) = get_client_id_from_email(&web_instance.mongoc, &email).await else {
+        return activate_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", None);
+    };
+
+    let scene = match get_scene_coll()
+        .find_one(doc! { "id": scene_id, "owner_name": email.clone() }, None)
+        .await
+    {
+        Ok(Some(scene)) => scene,
+        Ok(None) => return activate_response(StatusCode::NOT_FOUND, "Scene not found", None),
+        Err(_) => return activate_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", None),
+    };
+    let Ok(commands) = expand_scene(&web_instance.mongoc, &scene).await else {
+        return activate_response(StatusCode::INTERNAL_SERVER_ERROR, "Internal server error", None);
+    };
+
+    let results = futures::future::join_all(commands.into_iter().map(|(device_id, component_id, command)| {
+        let web_instance = web_instance.clone();
+        let notif = to_web_notification(
+            email.clone(),
+            client_id.clone(),
+            device_id,
+            component_id,
+            command,
+            ack_timeout_ms,
+            expires_in_secs,
+        );
+        async move {
+            let response = serde_json::from_str::<RemoteControlIotNotification>(
+                &web_instance.send_message_to_iot(serde_json::to_string(&notif).unwrap()).await,
+            )
+            .unwrap_or(RemoteControlIotNotification {
+                status_code: 500,
+                message: String::from("Internal server error"),
+                command_id: None,
+                command_status: None,
+            });
+            SceneCommandResult {
+                device_id,
+                component_id,
+                command,
+                status_code: response.status_code,
+                message: response.message,
+                command_id: response.command_id,
+                command_status: response.command_status,
+            }
+        }
+    }))
+    .await;
+
+    activate_response(StatusCode::OK, &format!("Activated scene '{}'", scene.name), Some(results))
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/scenes",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_scenes_handler, |op| {
+                op.description("Get scenes by user email")
+                    .tag("Remote control")
+                    .response::<200, Json<GetScenesResponse>>()
+                    .response::<403, Json<GetScenesResponse>>()
+                    .response::<500, Json<GetScenesResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/scenes",
+            Policy::Role(HouseholdRole::Member),
+            post_with(create_scene_handler, |op| {
+                op.description("Create a scene from explicit commands or every light or buzzer of a room")
+                    .tag("Remote control")
+                    .response::<200, Json<SceneResponse>>()
+                    .response::<400, Json<SceneResponse>>()
+                    .response::<403, Json<SceneResponse>>()
+            })
+            .patch_with(update_scene_handler, |op| {
+                op.description("Rename a scene or replace its actions")
+                    .tag("Remote control")
+                    .response::<200, Json<SceneResponse>>()
+                    .response::<400, Json<SceneResponse>>()
+                    .response::<403, Json<SceneResponse>>()
+                    .response::<404, Json<SceneResponse>>()
+                    .response::<500, Json<SceneResponse>>()
+            })
+            .delete_with(delete_scene_handler, |op| {
+                op.description("Delete a scene")
+                    .tag("Remote control")
+                    .response::<200, Json<SceneResponse>>()
+                    .response::<403, Json<SceneResponse>>()
+                    .response::<404, Json<SceneResponse>>()
+                    .response::<500, Json<SceneResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/scenes/activate",
+            Policy::Role(HouseholdRole::Member),
+            post_with(activate_scene_handler, |op| {
+                op.description("Send every command of a scene and return the result of each")
+                    .tag("Remote control")
+                    .response::<200, Json<ActivateSceneResponse>>()
+                    .response::<403, Json<ActivateSceneResponse>>()
+                    .response::<404, Json<ActivateSceneResponse>>()
+                    .response::<500, Json<ActivateSceneResponse>>()
+            }),
+        )
+}
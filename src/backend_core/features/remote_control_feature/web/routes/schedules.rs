@@ -0,0 +1,331 @@
+use std::time::SystemTime;
+
+use aide::axum::{routing::{get_with, post_with}, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, options::FindOptions, Collection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    backend_core::features::remote_control_feature::{
+        models::{RemoteCommand, Schedule, ScheduleRun, ScheduleTrigger},
+        schedules::{next_run, utc_offset},
+    },
+    json::Json,
+};
+
+use super::get_collection;
+use crate::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+const MAX_RUNS_PER_REQUEST: i64 = 100;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetSchedulesQuery {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetScheduleRunsQuery {
+    email: String,
+    schedule_id: Option<String>,
+    offset: Option<u64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateScheduleBody {
+    email: String,
+    name: String,
+    device_id: usize,
+    component_id: usize,
+    command: RemoteCommand,
+    trigger: ScheduleTrigger,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpdateScheduleBody {
+    email: String,
+    schedule_id: String,
+    name: Option<String>,
+    command: Option<RemoteCommand>,
+    trigger: Option<ScheduleTrigger>,
+    enabled: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DeleteScheduleBody {
+    email: String,
+    schedule_id: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetSchedulesResponse {
+    message: String,
+    schedules: Option<Vec<Schedule>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetScheduleRunsResponse {
+    message: String,
+    runs: Option<Vec<ScheduleRun>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct ScheduleResponse {
+    message: String,
+    schedule: Option<Schedule>,
+}
+
+fn get_schedule_coll() -> Collection<Schedule> {
+    get_collection("remote_control_schedules")
+}
+
+fn schedule_response(status_code: StatusCode, message: &str, schedule: Option<Schedule>) -> (StatusCode, Json<ScheduleResponse>) {
+    (
+        status_code,
+        Json(ScheduleResponse {
+            message: String::from(message),
+            schedule,
+        }),
+    )
+}
+
+// Validates the trigger of a schedule and, if it is enabled, works out its
+// first run.
+fn plan_next_run(trigger: &ScheduleTrigger, enabled: bool) -> Result<Option<SystemTime>, String> {
+    // Checked even when disabled, so that enabling it later cannot fail
+    if let ScheduleTrigger::Cron { utc_offset_minutes, .. } = trigger {
+        utc_offset(*utc_offset_minutes)?;
+    }
+    if !enabled {
+        return Ok(None);
+    }
+    match next_run(trigger, SystemTime::now()) {
+        Ok(Some(next_run_at)) => Ok(Some(next_run_at)),
+        Ok(None) => Err(String::from("Schedule would never run")),
+        Err(e) => Err(format!("Invalid schedule trigger: {e}")),
+    }
+}
+
+async fn get_schedules_handler(
+    Query(GetSchedulesQuery { email }): Query<GetSchedulesQuery>,
+) -> impl IntoApiResponse {
+    match get_schedule_coll()
+        .find(doc! { "owner_name": email }, None)
+        .await
+    {
+        Ok(mut cursor) => {
+            let mut schedules = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(schedule) => schedules.push(schedule),
+                    Err(e) => eprintln!("Error deserializing schedule: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetSchedulesResponse {
+                    message: String::from("Successfully fetch schedules"),
+                    schedules: Some(schedules),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetSchedulesResponse {
+                message: String::from("Unexpected error while fetching schedules"),
+                schedules: None,
+            }),
+        ),
+    }
+}
+
+async fn create_schedule_handler(
+    Json(CreateScheduleBody {
+        email,
+        name,
+        device_id,
+        component_id,
+        command,
+        trigger,
+    }): Json<CreateScheduleBody>,
+) -> impl IntoApiResponse {
+    let next_run_at = match plan_next_run(&trigger, true) {
+        Ok(next_run_at) => next_run_at,
+        Err(message) => return schedule_response(StatusCode::BAD_REQUEST, &message, None),
+    };
+    let schedule = Schedule {
+        id: uuid::Uuid::now_v7().into(),
+        owner_name: email,
+        name,
+        device_id,
+        component_id,
+        command,
+        trigger,
+        enabled: true,
+        next_run_at,
+        last_run_at: None,
+        created_at: SystemTime::now(),
+    };
+    match get_schedule_coll().insert_one(&schedule, None).await {
+        Ok(_) => schedule_response(StatusCode::OK, "Schedule created successfully", Some(schedule)),
+        Err(_) => schedule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create schedule", None),
+    }
+}
+
+async fn update_schedule_handler(
+    Json(UpdateScheduleBody {
+        email,
+        schedule_id,
+        name,
+        command,
+        trigger,
+        enabled,
+    }): Json<UpdateScheduleBody>,
+) -> impl IntoApiResponse {
+    let filter = doc! { "id": schedule_id, "owner_name": email };
+    let mut schedule = match get_schedule_coll().find_one(filter.clone(), None).await {
+        Ok(Some(schedule)) => schedule,
+        Ok(None) => return schedule_response(StatusCode::NOT_FOUND, "Schedule not found", None),
+        Err(_) => return schedule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update schedule", None),
+    };
+    if let Some(name) = name {
+        schedule.name = name;
+    }
+    if let Some(command) = command {
+        schedule.command = command;
+    }
+    if let Some(trigger) = trigger {
+        schedule.trigger = trigger;
+    }
+    if let Some(enabled) = enabled {
+        schedule.enabled = enabled;
+    }
+    schedule.next_run_at = match plan_next_run(&schedule.trigger, schedule.enabled) {
+        Ok(next_run_at) => next_run_at,
+        Err(message) => return schedule_response(StatusCode::BAD_REQUEST, &message, None),
+    };
+
+    match get_schedule_coll().replace_one(filter, &schedule, None).await {
+        Ok(_) => schedule_response(StatusCode::OK, "Schedule updated successfully", Some(schedule)),
+        Err(_) => schedule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update schedule", None),
+    }
+}
+
+async fn delete_schedule_handler(
+    Json(DeleteScheduleBody { email, schedule_id }): Json<DeleteScheduleBody>,
+) -> impl IntoApiResponse {
+    match get_schedule_coll()
+        .find_one_and_delete(doc! { "id": schedule_id, "owner_name": email }, None)
+        .await
+    {
+        Ok(Some(schedule)) => schedule_response(StatusCode::OK, "Schedule deleted successfully", Some(schedule)),
+        Ok(None) => schedule_response(StatusCode::NOT_FOUND, "Schedule not found", None),
+        Err(_) => schedule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete schedule", None),
+    }
+}
+
+async fn get_schedule_runs_handler(
+    Query(GetScheduleRunsQuery {
+        email,
+        schedule_id,
+        offset,
+        limit,
+    }): Query<GetScheduleRunsQuery>,
+) -> impl IntoApiResponse {
+    let mut filter = doc! { "owner_name": email };
+    if let Some(schedule_id) = schedule_id {
+        filter.insert("schedule_id", schedule_id);
+    }
+    let find_options = FindOptions::builder()
+        .sort(doc! { "ran_at.secs_since_epoch": -1, "ran_at.nanos_since_epoch": -1 })
+        .skip(offset)
+        .limit(limit.unwrap_or(MAX_RUNS_PER_REQUEST).min(MAX_RUNS_PER_REQUEST))
+        .build();
+    match get_collection::<ScheduleRun>("remote_control_schedule_runs")
+        .find(filter, find_options)
+        .await
+    {
+        Ok(mut cursor) => {
+            let mut runs = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(run) => runs.push(run),
+                    Err(e) => eprintln!("Error deserializing schedule run: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetScheduleRunsResponse {
+                    message: String::from("Successfully fetch schedule runs"),
+                    runs: Some(runs),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetScheduleRunsResponse {
+                message: String::from("Unexpected error while fetching schedule runs"),
+                runs: None,
+            }),
+        ),
+    }
+}
+
+pub fn routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/schedules",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_schedules_handler, |op| {
+                op.description("Get scheduled commands by user email")
+                    .tag("Remote control")
+                    .response::<200, Json<GetSchedulesResponse>>()
+                    .response::<403, Json<GetSchedulesResponse>>()
+                    .response::<500, Json<GetSchedulesResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/schedules",
+            Policy::Role(HouseholdRole::Member),
+            post_with(create_schedule_handler, |op| {
+                op.description("Schedule a light or buzzer command once or on a cron expression")
+                    .tag("Remote control")
+                    .response::<200, Json<ScheduleResponse>>()
+                    .response::<400, Json<ScheduleResponse>>()
+                    .response::<403, Json<ScheduleResponse>>()
+                    .response::<500, Json<ScheduleResponse>>()
+            })
+            .patch_with(update_schedule_handler, |op| {
+                op.description("Update, enable or disable a schedule")
+                    .tag("Remote control")
+                    .response::<200, Json<ScheduleResponse>>()
+                    .response::<400, Json<ScheduleResponse>>()
+                    .response::<403, Json<ScheduleResponse>>()
+                    .response::<404, Json<ScheduleResponse>>()
+                    .response::<500, Json<ScheduleResponse>>()
+            })
+            .delete_with(delete_schedule_handler, |op| {
+                op.description("Delete a schedule")
+                    .tag("Remote control")
+                    .response::<200, Json<ScheduleResponse>>()
+                    .response::<403, Json<ScheduleResponse>>()
+                    .response::<404, Json<ScheduleResponse>>()
+                    .response::<500, Json<ScheduleResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/schedule-runs",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_schedule_runs_handler, |op| {
+                op.description("Get the run history of schedules, newest first")
+                    .tag("Remote control")
+                    .response::<200, Json<GetScheduleRunsResponse>>()
+                    .response::<403, Json<GetScheduleRunsResponse>>()
+                    .response::<500, Json<GetScheduleRunsResponse>>()
+            }),
+        )
+}
@@ -0,0 +1,271 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use mongodb::{
+    bson::{doc, to_bson},
+    options::{FindOneAndUpdateOptions, IndexOptions, ReturnDocument},
+    Collection, IndexModel,
+};
+
+use super::models::{Household, HouseholdInvitation, HouseholdMember, HouseholdRole};
+use crate::auth::{generate_opaque_token, hash_opaque_token};
+
+pub fn get_household_coll(mongoc: &mongodb::Client) -> Collection<Household> {
+    mongoc.default_database().unwrap().collection("households")
+}
+
+pub fn get_invitation_coll(mongoc: &mongodb::Client) -> Collection<HouseholdInvitation> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection("household_invitations")
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    get_household_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "id": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "owner_name": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "members.email": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    get_invitation_coll(mongoc)
+        .create_indexes(
+            vec![
+                IndexModel::builder()
+                    .keys(doc! { "token_hash": 1 })
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                IndexModel::builder()
+                    .keys(doc! { "household_id": 1, "email": 1 })
+                    .build(),
+            ],
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+fn secs_since_epoch(time: SystemTime) -> i64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+// Households the user is a member of, including the one they own if any
+pub async fn find_households(mongoc: &mongodb::Client, email: &str) -> mongodb::error::Result<Vec<Household>> {
+    let mut cursor = get_household_coll(mongoc)
+        .find(doc! { "members.email": email }, None)
+        .await?;
+    let mut households = vec![];
+    while cursor.advance().await? {
+        households.push(cursor.deserialize_current()?);
+    }
+    Ok(households)
+}
+
+pub async fn find_household(mongoc: &mongodb::Client, owner_name: &str) -> mongodb::error::Result<Option<Household>> {
+    get_household_coll(mongoc)
+        .find_one(doc! { "owner_name": owner_name }, None)
+        .await
+}
+
+// Roles of the user in the households of others, keyed by `owner_name`
+pub async fn find_household_roles(
+    mongoc: &mongodb::Client,
+    email: &str,
+) -> mongodb::error::Result<HashMap<String, HouseholdRole>> {
+    Ok(find_households(mongoc, email)
+        .await?
+        .into_iter()
+        .filter(|household| household.owner_name != email)
+        .filter_map(|household| {
+            let role = household
+                .members
+                .iter()
+                .find(|member| member.email == email)?
+                .role;
+            Some((household.owner_name, role))
+        })
+        .collect())
+}
+
+pub async fn find_household_role(
+    mongoc: &mongodb::Client,
+    email: &str,
+    owner_name: &str,
+) -> mongodb::error::Result<Option<HouseholdRole>> {
+    if email == owner_name {
+        return Ok(Some(HouseholdRole::Owner));
+    }
+    Ok(find_household_roles(mongoc, email).await?.remove(owner_name))
+}
+
+// Returns `None` if the user already owns a household
+pub async fn create_household(
+    mongoc: &mongodb::Client,
+    owner_name: String,
+    name: String,
+) -> mongodb::error::Result<Option<Household>> {
+    if find_household(mongoc, &owner_name).await?.is_some() {
+        return Ok(None);
+    }
+    let now = SystemTime::now();
+    let household = Household {
+        id: uuid::Uuid::now_v7().into(),
+        name,
+        members: vec![HouseholdMember {
+            email: owner_name.clone(),
+            role: HouseholdRole::Owner,
+            joined_at: now,
+        }],
+        owner_name,
+        created_at: now,
+    };
+    get_household_coll(mongoc).insert_one(&household, None).await?;
+    Ok(Some(household))
+}
+
+// Replaces any pending invitation of the same user to the household. Returns
+// the token to send by mail.
+pub async fn create_invitation(
+    mongoc: &mongodb::Client,
+    household: &Household,
+    email: String,
+    role: HouseholdRole,
+    invited_by: String,
+    ttl_secs: u64,
+) -> mongodb::error::Result<Option<(HouseholdInvitation, String)>> {
+    let Some(token) = generate_opaque_token() else {
+        return Ok(None);
+    };
+    let invitation_coll = get_invitation_coll(mongoc);
+    invitation_coll
+        .delete_many(doc! { "household_id": household.id.clone(), "email": email.clone() }, None)
+        .await?;
+    let now = SystemTime::now();
+    let invitation = HouseholdInvitation {
+        id: uuid::Uuid::now_v7().into(),
+        household_id: household.id.clone(),
+        owner_name: household.owner_name.clone(),
+        email,
+        role,
+        invited_by,
+        token_hash: hash_opaque_token(&token),
+        created_at: now,
+        expires_at: now + Duration::from_secs(ttl_secs),
+    };
+    invitation_coll.insert_one(&invitation, None).await?;
+    Ok(Some((invitation, token)))
+}
+
+// Invitations are single use and only work for the invited email
+pub async fn accept_invitation(
+    mongoc: &mongodb::Client,
+    email: &str,
+    token: &str,
+) -> mongodb::error::Result<Option<Household>> {
+    let Some(invitation) = get_invitation_coll(mongoc)
+        .find_one_and_delete(
+            doc! {
+                "token_hash": hash_opaque_token(token),
+                "email": email,
+                "expires_at.secs_since_epoch": { "$gt": secs_since_epoch(SystemTime::now()) },
+            },
+            None,
+        )
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    let household_coll = get_household_coll(mongoc);
+    let filter = doc! { "id": invitation.household_id };
+    // Accepting again replaces the previous role, never the owner's
+    household_coll
+        .update_one(
+            filter.clone(),
+            doc! { "$pull": { "members": { "email": email, "role": { "$ne": to_bson(&HouseholdRole::Owner)? } } } },
+            None,
+        )
+        .await?;
+    let member = HouseholdMember {
+        email: email.to_string(),
+        role: invitation.role,
+        joined_at: SystemTime::now(),
+    };
+    household_coll
+        .find_one_and_update(
+            filter,
+            doc! { "$push": { "members": to_bson(&member)? } },
+            FindOneAndUpdateOptions::builder()
+                .return_document(ReturnDocument::After)
+                .build(),
+        )
+        .await
+}
+
+// The owner's role cannot be changed
+pub async fn set_member_role(
+    mongoc: &mongodb::Client,
+    owner_name: &str,
+    member_email: &str,
+    role: HouseholdRole,
+) -> mongodb::error::Result<bool> {
+    let result = get_household_coll(mongoc)
+        .update_one(
+            doc! {
+                "owner_name": owner_name,
+                "members": { "$elemMatch": {
+                    "email": member_email,
+                    "role": { "$ne": to_bson(&HouseholdRole::Owner)? },
+                } },
+            },
+            doc! { "$set": { "members.$.role": to_bson(&role)? } },
+            None,
+        )
+        .await?;
+    Ok(result.matched_count > 0)
+}
+
+// The owner cannot be removed from their household
+pub async fn remove_member(
+    mongoc: &mongodb::Client,
+    owner_name: &str,
+    member_email: &str,
+) -> mongodb::error::Result<bool> {
+    let result = get_household_coll(mongoc)
+        .update_one(
+            doc! { "owner_name": owner_name },
+            doc! { "$pull": { "members": {
+                "email": member_email,
+                "role": { "$ne": to_bson(&HouseholdRole::Owner)? },
+            } } },
+            None,
+        )
+        .await?;
+    Ok(result.modified_count > 0)
+}
+
+pub async fn purge_expired_invitations(mongoc: &mongodb::Client) -> mongodb::error::Result<u64> {
+    let result = get_invitation_coll(mongoc)
+        .delete_many(
+            doc! { "expires_at.secs_since_epoch": { "$lte": secs_since_epoch(SystemTime::now()) } },
+            None,
+        )
+        .await?;
+    Ok(result.deleted_count)
+}
@@ -0,0 +1,2 @@
+pub mod memberships;
+pub mod models;
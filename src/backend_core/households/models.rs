@@ -0,0 +1,54 @@
+use std::time::SystemTime;
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+// Ordered from the least to the most privileged, so that a role grants
+// everything the roles before it do.
+#[derive(Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum HouseholdRole {
+    // Can see readings, devices and alerts
+    #[serde(rename = "viewer")]
+    Viewer,
+    // Can also send remote control commands and change rooms, rules and settings
+    #[serde(rename = "member")]
+    Member,
+    // Can also invite, remove members and change their roles
+    #[serde(rename = "admin")]
+    Admin,
+    #[serde(rename = "owner")]
+    Owner,
+}
+
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct HouseholdMember {
+    pub email: String,
+    pub role: HouseholdRole,
+    pub joined_at: SystemTime,
+}
+
+// A home shared by several users. Gateways, rooms and devices stay keyed on
+// the `owner_name` of their account, the household grants its members access
+// to everything under that name.
+#[derive(Serialize, Deserialize, JsonSchema, Clone)]
+pub struct Household {
+    pub id: String,
+    pub name: String,
+    pub owner_name: String,
+    pub members: Vec<HouseholdMember>,
+    pub created_at: SystemTime,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HouseholdInvitation {
+    pub id: String,
+    pub household_id: String,
+    pub owner_name: String,
+    pub email: String,
+    pub role: HouseholdRole,
+    pub invited_by: String,
+    // Only a hash of the token sent by mail is kept
+    pub token_hash: String,
+    pub created_at: SystemTime,
+    pub expires_at: SystemTime,
+}
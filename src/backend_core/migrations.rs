@@ -0,0 +1,186 @@
+use std::{future::Future, time::SystemTime};
+
+use mongodb::{
+    bson::{doc, spec::BinarySubtype, to_document, Binary, Document},
+    error::{BulkWriteFailure, ErrorKind},
+    options::InsertManyOptions,
+    Collection,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{hashing::hash_password, login_attempts, password_resets, security_events, sessions, two_factor};
+
+use super::{
+    automation,
+    features::{devices_status_feature, fire_alert_feature, remote_control_feature},
+    households, rollups,
+};
+
+const DUPLICATE_KEY_ERROR: i32 = 11000;
+
+#[derive(Serialize, Deserialize)]
+pub struct MigrationRecord {
+    pub name: String,
+    pub applied_at: SystemTime,
+}
+
+// Copies entries under ids derived from their source, so that a migration
+// rerun after a crash skips the entries already copied instead of copying
+// them twice
+pub(crate) async fn insert_once<T: Serialize>(
+    coll: &Collection<Document>,
+    entries: Vec<(String, T)>,
+) -> mongodb::error::Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let documents = entries
+        .into_iter()
+        .map(|(id, entry)| {
+            let mut document = to_document(&entry)?;
+            document.insert("_id", id);
+            Ok(document)
+        })
+        .collect::<mongodb::error::Result<Vec<_>>>()?;
+    match coll
+        .insert_many(documents, InsertManyOptions::builder().ordered(false).build())
+        .await
+    {
+        Ok(_) => Ok(()),
+        Err(e) => match *e.kind {
+            ErrorKind::BulkWrite(BulkWriteFailure {
+                write_errors: Some(ref write_errors),
+                write_concern_error: None,
+                ..
+            }) if write_errors.iter().all(|error| error.code == DUPLICATE_KEY_ERROR) => Ok(()),
+            _ => Err(e),
+        },
+    }
+}
+
+// Runs a one-shot migration unless the `migrations` collection says it has
+// already been applied.
+async fn apply_once(
+    mongoc: &mongodb::Client,
+    name: &str,
+    migration: impl Future<Output = mongodb::error::Result<()>>,
+) -> mongodb::error::Result<()> {
+    let migration_coll: Collection<MigrationRecord> =
+        mongoc.default_database().unwrap().collection("migrations");
+
+    if migration_coll
+        .find_one(doc! { "name": name }, None)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    migration.await?;
+    migration_coll
+        .insert_one(
+            MigrationRecord {
+                name: name.to_string(),
+                applied_at: SystemTime::now(),
+            },
+            None,
+        )
+        .await?;
+    println!("✅ Applied migration '{name}'");
+    Ok(())
+}
+
+// Accounts created before email verification existed keep working
+async fn mark_existing_users_verified(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    mongoc
+        .default_database()
+        .unwrap()
+        .collection::<Document>("users")
+        .update_many(
+            doc! { "email_verified": { "$exists": false } },
+            doc! { "$set": { "email_verified": true } },
+            None,
+        )
+        .await?;
+    Ok(())
+}
+
+// Replaces the plaintext client secrets with their PBKDF2 hash. Gateways keep
+// authenticating with the same secret.
+pub async fn hash_plaintext_client_secrets(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    let user_coll: Collection<Document> = mongoc.default_database().unwrap().collection("users");
+    let mut cursor = user_coll
+        .find(doc! { "client_secret": { "$type": "string" } }, None)
+        .await?;
+    while cursor.advance().await? {
+        let user = cursor.deserialize_current()?;
+        let (Ok(id), Ok(client_secret)) = (user.get_object_id("_id"), user.get_str("client_secret")) else {
+            continue;
+        };
+        // Failing the migration runs it again on the next start, skipping the
+        // user would leave their gateway unable to ever log in
+        let Some((hashed_client_secret, client_secret_salt)) = hash_password(client_secret.to_string()) else {
+            return Err(std::io::Error::other(format!("Failed to hash the client secret of user '{id}'")).into());
+        };
+        let binary = |bytes: &[u8]| Binary {
+            subtype: BinarySubtype::Generic,
+            bytes: bytes.to_vec(),
+        };
+        user_coll
+            .update_one(
+                doc! { "_id": id },
+                doc! {
+                    "$set": {
+                        "hashed_client_secret": binary(&hashed_client_secret),
+                        "client_secret_salt": binary(&client_secret_salt),
+                    },
+                    "$unset": { "client_secret": "" },
+                },
+                None,
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+pub async fn run_migrations(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    fire_alert_feature::migrations::create_indexes(mongoc).await?;
+    devices_status_feature::migrations::create_indexes(mongoc).await?;
+    remote_control_feature::commands::create_indexes(mongoc).await?;
+    remote_control_feature::scenes::create_indexes(mongoc).await?;
+    remote_control_feature::schedules::create_indexes(mongoc).await?;
+    rollups::create_indexes(mongoc).await?;
+    automation::rules::create_indexes(mongoc).await?;
+    households::memberships::create_indexes(mongoc).await?;
+    security_events::create_indexes(mongoc).await?;
+    sessions::create_indexes(mongoc).await?;
+    password_resets::create_indexes(mongoc).await?;
+    two_factor::create_indexes(mongoc).await?;
+    login_attempts::create_indexes(mongoc).await?;
+
+    apply_once(
+        mongoc,
+        "fire-alert-split-log-arrays",
+        fire_alert_feature::migrations::split_log_arrays(mongoc),
+    )
+    .await?;
+    apply_once(
+        mongoc,
+        "device-status-split-log-arrays",
+        devices_status_feature::migrations::split_log_arrays(mongoc),
+    )
+    .await?;
+    apply_once(
+        mongoc,
+        "users-mark-existing-verified",
+        mark_existing_users_verified(mongoc),
+    )
+    .await?;
+    apply_once(
+        mongoc,
+        "users-hash-client-secrets",
+        hash_plaintext_client_secrets(mongoc),
+    )
+    .await?;
+    Ok(())
+}
@@ -1,3 +1,9 @@
+pub mod automation;
+pub mod channels;
+pub mod events;
 pub mod features;
+pub mod households;
+pub mod migrations;
 pub mod models;
+pub mod rollups;
 pub mod utils;
@@ -5,13 +5,20 @@ use serde::{Deserialize, Serialize};
 #[derive(Serialize, Deserialize)]
 pub struct User {
     pub client_id: String,
-    pub client_secret: String,
+    // The client secret is hashed like the password
+    #[serde(with = "serde_bytes")]
+    pub hashed_client_secret: [u8; SHA512_OUTPUT_LEN],
+    #[serde(with = "serde_bytes")]
+    pub client_secret_salt: [u8; SHA512_OUTPUT_LEN],
     pub email: String,
     #[serde(with = "serde_bytes")]
     pub hashed_password: [u8; SHA512_OUTPUT_LEN],
     #[serde(with = "serde_bytes")]
     pub salt: [u8; SHA512_OUTPUT_LEN],
     pub enabled_features: Vec<String>,
+    // Gateways of unverified accounts cannot authenticate
+    #[serde(default)]
+    pub email_verified: bool,
 }
 
 #[derive(Serialize, Deserialize, JsonSchema)]
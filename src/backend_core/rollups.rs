@@ -0,0 +1,235 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use mongodb::{
+    bson::{doc, Document},
+    options::{IndexOptions, UpdateOptions},
+    Collection, IndexModel,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, JsonSchema, PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum Resolution {
+    #[default]
+    #[serde(rename = "raw")]
+    Raw,
+    #[serde(rename = "1m")]
+    Minute,
+    #[serde(rename = "1h")]
+    Hour,
+}
+
+impl Resolution {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Resolution::Raw => "raw",
+            Resolution::Minute => "1m",
+            Resolution::Hour => "1h",
+        }
+    }
+
+    pub fn secs(&self) -> i64 {
+        match self {
+            Resolution::Raw => 0,
+            Resolution::Minute => 60,
+            Resolution::Hour => 3600,
+        }
+    }
+}
+
+// A raw per-reading collection and the collection its min/max/avg rollups
+// are merged into, keyed by the fields that identify one series.
+pub struct RollupSeries {
+    pub source: &'static str,
+    pub target: &'static str,
+    pub key_fields: &'static [&'static str],
+    pub value_field: &'static str,
+}
+
+pub const FIRE_SENSOR_SERIES: RollupSeries = RollupSeries {
+    source: "fire_sensor_logs",
+    target: "fire_sensor_rollups",
+    key_fields: &["owner_name", "sensor_type", "id", "component"],
+    value_field: "value",
+};
+
+pub const BATTERY_SERIES: RollupSeries = RollupSeries {
+    source: "device_battery_logs",
+    target: "device_battery_rollups",
+    key_fields: &["owner_name", "device_id"],
+    value_field: "battery",
+};
+
+#[derive(Serialize, Deserialize)]
+struct RollupWatermark {
+    target: String,
+    resolution: Resolution,
+    until: i64,
+}
+
+fn now_secs() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+fn merge_keys(series: &RollupSeries) -> Vec<&'static str> {
+    let mut keys = series.key_fields.to_vec();
+    keys.push("resolution");
+    keys.push("bucket");
+    keys
+}
+
+pub async fn create_indexes(mongoc: &mongodb::Client) -> mongodb::error::Result<()> {
+    for series in [FIRE_SENSOR_SERIES, BATTERY_SERIES] {
+        let mut keys = Document::new();
+        for key in merge_keys(&series) {
+            keys.insert(key, 1);
+        }
+        mongoc
+            .default_database()
+            .unwrap()
+            .collection::<Document>(series.target)
+            .create_index(
+                IndexModel::builder()
+                    .keys(keys)
+                    .options(IndexOptions::builder().unique(true).build())
+                    .build(),
+                None,
+            )
+            .await?;
+        // Rollups and purges select the raw readings by time across owners
+        mongoc
+            .default_database()
+            .unwrap()
+            .collection::<Document>(series.source)
+            .create_index(
+                IndexModel::builder()
+                    .keys(doc! { "timestamp.secs_since_epoch": 1 })
+                    .build(),
+                None,
+            )
+            .await?;
+    }
+    Ok(())
+}
+
+// Aggregates every complete bucket since the last run into the rollup
+// collection. Buckets are replaced rather than accumulated, so re-running
+// over the same range is harmless.
+pub async fn compute_rollups(
+    mongoc: &mongodb::Client,
+    series: &RollupSeries,
+    resolution: Resolution,
+) -> mongodb::error::Result<()> {
+    let bucket_secs = resolution.secs();
+    if bucket_secs == 0 {
+        return Ok(());
+    }
+
+    let database = mongoc.default_database().unwrap();
+    let watermark_coll: Collection<RollupWatermark> = database.collection("rollup_watermarks");
+    let watermark_filter = doc! { "target": series.target, "resolution": resolution.as_str() };
+
+    let from = watermark_coll
+        .find_one(watermark_filter.clone(), None)
+        .await?
+        .map(|watermark| watermark.until)
+        .unwrap_or(0);
+    let until = now_secs() / bucket_secs * bucket_secs;
+    if until <= from {
+        return Ok(());
+    }
+
+    let mut group_id = Document::new();
+    let mut projection = doc! { "_id": 0 };
+    for key in series.key_fields {
+        group_id.insert(*key, format!("${key}"));
+        projection.insert(*key, format!("$_id.{key}"));
+    }
+    group_id.insert(
+        "bucket",
+        doc! { "$subtract": [
+            "$timestamp.secs_since_epoch",
+            { "$mod": ["$timestamp.secs_since_epoch", bucket_secs] },
+        ] },
+    );
+    projection.insert("resolution", resolution.as_str());
+    projection.insert("bucket", "$_id.bucket");
+    projection.insert(
+        "timestamp",
+        doc! { "secs_since_epoch": "$_id.bucket", "nanos_since_epoch": { "$literal": 0 } },
+    );
+    projection.insert("min", 1);
+    projection.insert("max", 1);
+    projection.insert("avg", 1);
+    projection.insert("count", 1);
+
+    let value = format!("${}", series.value_field);
+    let pipeline = vec![
+        doc! { "$match": { "timestamp.secs_since_epoch": { "$gte": from, "$lt": until } } },
+        doc! { "$group": {
+            "_id": group_id,
+            "min": { "$min": value.clone() },
+            "max": { "$max": value.clone() },
+            "avg": { "$avg": value },
+            "count": { "$sum": 1 },
+        } },
+        doc! { "$project": projection },
+        doc! { "$merge": {
+            "into": series.target,
+            "on": merge_keys(series),
+            "whenMatched": "replace",
+            "whenNotMatched": "insert",
+        } },
+    ];
+
+    database
+        .collection::<Document>(series.source)
+        .aggregate(pipeline, None)
+        .await?;
+
+    watermark_coll
+        .update_one(
+            watermark_filter,
+            doc! { "$set": { "until": until } },
+            UpdateOptions::builder().upsert(true).build(),
+        )
+        .await?;
+    Ok(())
+}
+
+pub async fn purge_raw(
+    mongoc: &mongodb::Client,
+    collection: &str,
+    keep_days: u64,
+) -> mongodb::error::Result<u64> {
+    let before = now_secs() - (keep_days * 24 * 3600) as i64;
+    let result = mongoc
+        .default_database()
+        .unwrap()
+        .collection::<Document>(collection)
+        .delete_many(doc! { "timestamp.secs_since_epoch": { "$lt": before } }, None)
+        .await?;
+    Ok(result.deleted_count)
+}
+
+pub async fn purge_rollups(
+    mongoc: &mongodb::Client,
+    series: &RollupSeries,
+    resolution: Resolution,
+    keep_days: u64,
+) -> mongodb::error::Result<u64> {
+    let before = now_secs() - (keep_days * 24 * 3600) as i64;
+    let result = mongoc
+        .default_database()
+        .unwrap()
+        .collection::<Document>(series.target)
+        .delete_many(
+            doc! { "resolution": resolution.as_str(), "bucket": { "$lt": before } },
+            None,
+        )
+        .await?;
+    Ok(result.deleted_count)
+}
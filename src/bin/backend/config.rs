@@ -1,5 +1,5 @@
 use std::{
-    net::{AddrParseError, SocketAddr},
+    net::{AddrParseError, IpAddr, SocketAddr},
     path::PathBuf,
     str::FromStr,
 };
@@ -7,6 +7,8 @@ use std::{
 use config::Environment;
 use once_cell::sync::Lazy;
 use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tempusalert_be::auth::login_attempts::ThrottlePolicy;
 
 pub const ENV_PREFIX: &str = "APP";
 
@@ -15,11 +17,24 @@ pub static CONFIG: Lazy<AppConfig> =
 
 pub static JWT_KEY: Lazy<String> = Lazy::new(|| std::env::var("JWT_KEY").unwrap());
 
+// Encrypts the TOTP secrets at rest, any string works as it is hashed to the
+// key length of AES-256
+// Grants the operator routes under `/admin`, which are disabled when unset
+pub static ADMIN_KEY: Lazy<Option<String>> =
+    Lazy::new(|| std::env::var("ADMIN_KEY").ok().filter(|key| !key.is_empty()));
+
+pub static TOTP_KEY: Lazy<[u8; 32]> = Lazy::new(|| {
+    Sha256::digest(std::env::var("TOTP_ENCRYPTION_KEY").unwrap().as_bytes()).into()
+});
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct WebConfig {
     pub addr: String,
     pub port: u16,
     pub protocol: String,
+    // Reverse proxies whose `X-Forwarded-For` is believed
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 impl WebConfig {
@@ -34,10 +49,76 @@ impl WebConfig {
 #[derive(Debug, Deserialize, Clone)]
 pub struct IotConfig {}
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct SeriesRetentionConfig {
+    pub raw_days: u64,
+    pub minute_rollup_days: u64,
+    pub hour_rollup_days: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RetentionConfig {
+    pub interval_secs: u64,
+    // Security events, such as gateways publishing on the topic of another
+    // client, are kept for this long
+    pub security_event_days: u64,
+    pub fire_sensor_logs: SeriesRetentionConfig,
+    pub device_battery_logs: SeriesRetentionConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct WatchdogConfig {
+    pub interval_secs: u64,
+    pub offline_after_secs: u64,
+    pub command_ack_timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct SchedulerConfig {
+    pub interval_secs: u64,
+    pub missed_run_grace_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct RulesConfig {
+    pub command_expires_in_secs: u64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct AuthConfig {
+    pub web_access_token_ttl_secs: u64,
+    pub web_refresh_token_ttl_secs: u64,
+    pub iot_access_token_ttl_secs: u64,
+    pub iot_refresh_token_ttl_secs: u64,
+    pub email_verification_ttl_secs: u64,
+    pub password_reset_ttl_secs: u64,
+    pub household_invitation_ttl_secs: u64,
+    // How long the second step of a two-factor login may wait
+    pub pre_auth_token_ttl_secs: u64,
+    // Base urls of the links sent by mail
+    pub api_url: String,
+    pub frontend_url: String,
+}
+
+// Gateways and IPs have their own limits, see `AttemptScope`
+#[derive(Debug, Deserialize, Clone)]
+pub struct LoginThrottleConfig {
+    pub web_account: ThrottlePolicy,
+    pub web_ip: ThrottlePolicy,
+    pub iot_client: ThrottlePolicy,
+    pub iot_ip: ThrottlePolicy,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct AppConfig {
     pub server: WebConfig,
+    pub auth: AuthConfig,
+    pub login_throttle: LoginThrottleConfig,
     pub iot: IotConfig,
+    pub retention: RetentionConfig,
+    pub watchdog: WatchdogConfig,
+    pub scheduler: SchedulerConfig,
+    pub rules: RulesConfig,
 }
 
 impl AppConfig {
@@ -52,7 +133,14 @@ impl AppConfig {
             .add_source(config::File::from(config_dir.join(profile_filename)))
             .add_source(env_src)
             .build()?;
-        config.try_deserialize()
+        let app_config: Self = config.try_deserialize()?;
+        // A zero period would make `tokio::time::interval` panic at startup
+        if app_config.retention.interval_secs == 0 {
+            return Err(config::ConfigError::Message(String::from(
+                "retention.interval_secs must be positive",
+            )));
+        }
+        Ok(app_config)
     }
 }
 
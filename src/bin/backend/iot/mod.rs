@@ -1,8 +1,13 @@
-use mongodb::bson::Document;
-use tempusalert_be::backend_core::features::IotFeature;
+use tempusalert_be::{
+    auth::forget_client,
+    backend_core::{
+        channels::{get_user_subscriber, UserEvent, UserEventKind},
+        features::IotFeature,
+    },
+};
 
 use crate::{
-    clonable_wrapper::ClonableWrapper, config::IotConfig, globals::channels::{get_user_subscriber, UserEvent, UserEventKind}, types::IotFeatureDyn, AppResult
+    clonable_wrapper::ClonableWrapper, config::IotConfig, types::IotFeatureDyn, AppResult
 };
 
 pub struct IotTask {
@@ -42,65 +47,30 @@ impl IotTask {
 }
 
 async fn watch_users(mut feat: Box<dyn IotFeature + Send + Sync>) {
-    let (feature_id, mqttc, mongoc) = {
-        (feat.get_module_name(), feat.get_mqttc(), feat.get_mongoc())
-    };
-    let collection = mongoc.default_database().unwrap().collection("users");
-
-    let mut user_cursor = collection.find(None, None).await.unwrap();
-    while let Ok(true) = user_cursor.advance().await {
-        let user_doc = user_cursor.deserialize_current();
-        if let Some(cur_client_id) = user_doc.ok().and_then(|doc: Document| {
-            doc.get("client_id")
-                .and_then(|id| id.as_str())
-                .map(|s| s.to_owned())
-        }) {
-            let mqtt_topic = format!("{}/{}-metrics", cur_client_id, feature_id);
-            println!("Listen to existing user: {mqtt_topic}");
+    let (feature_id, mqttc) = (feat.get_module_name(), feat.get_mqttc());
 
-            if let Err(error) = mqttc
-                .subscribe(mqtt_topic.clone(), rumqttc::QoS::AtLeastOnce)
-                .await
-            {
-                eprintln!("Failed to subscribe to MQTT topic: {}", error);
-            }
-        }
+    // A single wildcard subscription covers users registered at any time, the
+    // client id is read back from the topic of each message
+    let mqtt_topic = format!("+/{}-metrics", feature_id);
+    if let Err(error) = mqttc
+        .subscribe(mqtt_topic.clone(), rumqttc::QoS::AtLeastOnce)
+        .await
+    {
+        eprintln!("Failed to subscribe to MQTT topic: {}", error);
+    } else {
+        println!("Listen to all users: {mqtt_topic}");
     }
 
-    // Watch on user insertion and user deletion
+    // Watch on user insertion and user deletion to drop stale cached owners
     let mut user_subscriber = get_user_subscriber().await;
 
     loop {
         match user_subscriber.recv().await {
             Ok(UserEvent {
-                kind: UserEventKind::JOIN,
-                client_id,
-            }) => {
-                let mqtt_topic = format!("{}/{}-metrics", client_id, feature_id);
-                if let Err(e) = mqttc.subscribe(mqtt_topic.clone(), rumqttc::QoS::AtLeastOnce).await {
-                    eprintln!(
-                        "Error subscribing to a new user with client id {}: {}",
-                        client_id, e
-                    );
-                } else {
-                    println!("Listen to new user: {mqtt_topic}");
-                }
-            }
-
-            Ok(UserEvent {
-                kind: UserEventKind::CANCEL,
+                kind: UserEventKind::JOIN | UserEventKind::CANCEL,
                 client_id,
             }) => {
-                let mqtt_topic = format!("{}/{}-metrics", client_id, feature_id);
-                if let Err(e) = mqttc.unsubscribe(mqtt_topic.clone()).await {
-                    eprintln!(
-                        "Error unsubscribing from an old user with client id {}: {}",
-                        client_id, e
-                    );
-                } else {
-                    println!("Listen terminated for old user: {mqtt_topic}");
-                }
-
+                forget_client(&client_id);
             }
 
             Err(e) => {
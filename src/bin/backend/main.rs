@@ -3,17 +3,20 @@ use database_client::{init_database, MONGOC};
 use dotenv::dotenv;
 use futures::FutureExt;
 use iot::IotTask;
+use retention::RetentionTask;
+use rules::RulesTask;
 use rumqttc::{AsyncClient, EventLoop};
+use scheduler::SchedulerTask;
 use tempusalert_be::{
-    backend_core::features::{devices_status_feature, fire_alert_feature, remote_control_feature, IotFeature, WebFeature},
+    backend_core::{features::{devices_status_feature, fire_alert_feature, remote_control_feature, IotFeature, WebFeature}, migrations::run_migrations},
     errors::AppError,
     mqtt_client::{self, ClientConfig}, parse_env_var::parse_env_var,
 };
+use watchdog::WatchdogTask;
 use web::WebTask;
 
 mod config;
 mod database_client;
-mod globals;
 mod mail;
 mod clonable_wrapper;
 
@@ -21,7 +24,11 @@ mod clonable_wrapper;
 mod macros;
 mod web;
 mod iot;
+mod retention;
+mod rules;
+mod scheduler;
 mod types;
+mod watchdog;
 
 pub type AppResult<T = ()> = std::result::Result<T, AppError>;
 
@@ -77,6 +84,7 @@ async fn main() -> AppResult {
     dotenv().ok();
     let config = CONFIG.clone();
     let mongoc = MONGOC.get_or_init(init_database).await;
+    run_migrations(mongoc).await?;
 
     let (web_feats, iot_feats, toggable_feat_names) = create_features!(
         mongoc.clone(),
@@ -90,12 +98,29 @@ async fn main() -> AppResult {
         TOGGABLE_FEATURES_NAMES = toggable_feat_names;
     }
 
+    let remote_control_feat = || {
+        web_feats
+            .iter()
+            .map(|feat| feat.clone())
+            .find(|feat| feat.get_module_name() == remote_control_feature::IotFeature::name())
+            .unwrap()
+    };
+    let scheduler_task =
+        SchedulerTask::create(config.scheduler, mongoc.clone(), remote_control_feat()).await?;
+    let rules_task = RulesTask::create(config.rules, mongoc.clone(), remote_control_feat()).await?;
+
     let web_task = WebTask::create(config.server, web_feats).await?;
     let iot_task = IotTask::create(config.iot, iot_feats).await?;
+    let retention_task = RetentionTask::create(config.retention, mongoc.clone()).await?;
+    let watchdog_task = WatchdogTask::create(config.watchdog, mongoc.clone()).await?;
 
     join_all(vec![
         (true, web_task.run().boxed()),
         (true, iot_task.run().boxed()),
+        (true, retention_task.run().boxed()),
+        (true, watchdog_task.run().boxed()),
+        (true, scheduler_task.run().boxed()),
+        (true, rules_task.run().boxed()),
     ])
     .await
     .unwrap();
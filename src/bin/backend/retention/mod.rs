@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+use tempusalert_be::{
+    auth::{
+        login_attempts::purge_expired_login_attempts, password_resets::purge_expired_password_resets,
+        security_events::purge_security_events, sessions::purge_expired_sessions,
+    },
+    backend_core::{
+        households::memberships::purge_expired_invitations,
+        rollups::{
+            compute_rollups, purge_raw, purge_rollups, Resolution, RollupSeries, BATTERY_SERIES,
+            FIRE_SENSOR_SERIES,
+        },
+    },
+};
+
+use crate::{
+    config::{RetentionConfig, SeriesRetentionConfig},
+    AppResult,
+};
+
+pub struct RetentionTask {
+    pub config: RetentionConfig,
+    mongoc: mongodb::Client,
+}
+
+impl RetentionTask {
+    pub async fn create(config: RetentionConfig, mongoc: mongodb::Client) -> AppResult<Self> {
+        Ok(Self { config, mongoc })
+    }
+
+    pub async fn run(self) -> AppResult {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            interval.tick().await;
+            for (series, retention) in [
+                (&FIRE_SENSOR_SERIES, &self.config.fire_sensor_logs),
+                (&BATTERY_SERIES, &self.config.device_battery_logs),
+            ] {
+                if let Err(e) = enforce(&self.mongoc, series, retention).await {
+                    eprintln!("Failed to enforce retention of '{}': {}", series.source, e);
+                }
+            }
+            if let Err(e) = purge_expired_sessions(&self.mongoc).await {
+                eprintln!("Failed to purge expired sessions: {}", e);
+            }
+            if let Err(e) = purge_expired_password_resets(&self.mongoc).await {
+                eprintln!("Failed to purge expired password resets: {}", e);
+            }
+            if let Err(e) = purge_expired_invitations(&self.mongoc).await {
+                eprintln!("Failed to purge expired household invitations: {}", e);
+            }
+            if let Err(e) = purge_expired_login_attempts(&self.mongoc).await {
+                eprintln!("Failed to purge expired login attempts: {}", e);
+            }
+            if let Err(e) = purge_security_events(&self.mongoc, self.config.security_event_days).await {
+                eprintln!("Failed to purge old security events: {}", e);
+            }
+        }
+    }
+}
+
+// Rollups are brought up to date before any raw reading is purged, so a
+// reading is never deleted before it has been counted in its buckets.
+async fn enforce(
+    mongoc: &mongodb::Client,
+    series: &RollupSeries,
+    retention: &SeriesRetentionConfig,
+) -> mongodb::error::Result<()> {
+    compute_rollups(mongoc, series, Resolution::Minute).await?;
+    compute_rollups(mongoc, series, Resolution::Hour).await?;
+
+    purge_raw(mongoc, series.source, retention.raw_days).await?;
+    purge_rollups(mongoc, series, Resolution::Minute, retention.minute_rollup_days).await?;
+    purge_rollups(mongoc, series, Resolution::Hour, retention.hour_rollup_days).await?;
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use std::time::SystemTime;
+
+use mongodb::bson::doc;
+use tempusalert_be::{
+    backend_core::{
+        automation::{
+            models::{Rule, RuleAction, RuleActionResult, RuleRun},
+            rules::{claim_rule, expand_rule_commands, find_matching_rules, record_run},
+        },
+        channels::subscribe_events,
+        events::FeatureEvent,
+        features::remote_control_feature::{commands::to_web_notification, IotNotification},
+        models::User,
+    },
+    push_notification::push_notification,
+};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{config::RulesConfig, mail::send_mail, types::WebFeatureDyn, AppResult};
+
+pub struct RulesTask {
+    pub config: RulesConfig,
+    mongoc: mongodb::Client,
+    remote_control: Box<WebFeatureDyn>,
+}
+
+impl RulesTask {
+    pub async fn create(
+        config: RulesConfig,
+        mongoc: mongodb::Client,
+        remote_control: Box<WebFeatureDyn>,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            config,
+            mongoc,
+            remote_control,
+        })
+    }
+
+    pub async fn run(self) -> AppResult {
+        let mut events = subscribe_events();
+        loop {
+            match events.recv().await {
+                Ok(event) => self.handle(event).await,
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Automation rules fell behind, skipped {skipped} events")
+                }
+                Err(RecvError::Closed) => return Ok(()),
+            }
+        }
+    }
+
+    async fn handle(&self, event: FeatureEvent) {
+        let rules = match find_matching_rules(&self.mongoc, &event).await {
+            Ok(rules) => rules,
+            Err(e) => {
+                eprintln!("Failed to find rules of user '{}': {}", event.owner_name(), e);
+                return;
+            }
+        };
+        for rule in rules {
+            let now = SystemTime::now();
+            match claim_rule(&self.mongoc, &rule, now).await {
+                Ok(true) => {}
+                Ok(false) => continue,
+                Err(e) => {
+                    eprintln!("Failed to claim rule '{}': {}", rule.id, e);
+                    continue;
+                }
+            }
+            let run = RuleRun {
+                rule_id: rule.id.clone(),
+                owner_name: rule.owner_name.clone(),
+                results: self.execute(&rule, &event).await,
+                event: event.clone(),
+                fired_at: now,
+            };
+            if let Err(e) = record_run(&self.mongoc, run).await {
+                eprintln!("Failed to record run of rule '{}': {}", rule.id, e);
+            }
+        }
+    }
+
+    async fn execute(&self, rule: &Rule, event: &FeatureEvent) -> Vec<RuleActionResult> {
+        let mut results = self.send_commands(rule).await;
+        for action in &rule.actions {
+            match action {
+                RuleAction::Push { message } => {
+                    let message = message.clone().unwrap_or_else(|| event.describe());
+                    let success = push_notification(rule.owner_name.clone(), message, &mut self.mongoc.clone())
+                        .await
+                        .is_some();
+                    results.push(RuleActionResult::Push { success });
+                }
+                RuleAction::Email { subject, body } => {
+                    let receiver_email = rule.owner_name.clone();
+                    let title = subject
+                        .clone()
+                        .unwrap_or_else(|| format!("Tempusalert: {}", rule.name));
+                    let body = body.clone().unwrap_or_else(|| event.describe());
+                    // SMTP is blocking, keep it off the runtime
+                    let success = tokio::task::spawn_blocking(move || send_mail(receiver_email, title, body))
+                        .await
+                        .ok()
+                        .flatten()
+                        .is_some();
+                    results.push(RuleActionResult::Email { success });
+                }
+                _ => {}
+            }
+        }
+        results
+    }
+
+    // Sends the remote control actions of the rule the same way scenes are
+    // activated, without waiting for the gateway to acknowledge them.
+    async fn send_commands(&self, rule: &Rule) -> Vec<RuleActionResult> {
+        let (commands, missing_scenes) = match expand_rule_commands(&self.mongoc, rule).await {
+            Ok(expanded) => expanded,
+            Err(e) => {
+                return vec![RuleActionResult::Error {
+                    message: format!("Failed to resolve commands: {e}"),
+                }]
+            }
+        };
+        let mut results = missing_scenes
+            .into_iter()
+            .map(|scene_id| RuleActionResult::Error {
+                message: format!("Scene '{scene_id}' not found"),
+            })
+            .collect::<Vec<_>>();
+        if commands.is_empty() {
+            return results;
+        }
+
+        let client_id = match self
+            .mongoc
+            .default_database()
+            .unwrap()
+            .collection::<User>("users")
+            .find_one(doc! { "email": rule.owner_name.clone() }, None)
+            .await
+        {
+            Ok(Some(user)) => user.client_id,
+            Ok(None) => {
+                results.push(RuleActionResult::Error {
+                    message: String::from("Owner of the rule no longer exists"),
+                });
+                return results;
+            }
+            Err(e) => {
+                results.push(RuleActionResult::Error {
+                    message: format!("Failed to find owner of the rule: {e}"),
+                });
+                return results;
+            }
+        };
+
+        let sent = futures::future::join_all(commands.into_iter().map(|(device_id, component_id, command)| {
+            let notif = to_web_notification(
+                rule.owner_name.clone(),
+                client_id.clone(),
+                device_id,
+                component_id,
+                command,
+                None,
+                Some(self.config.command_expires_in_secs),
+            );
+            async move {
+                let response = serde_json::from_str::<IotNotification>(
+                    &self.remote_control.send_message_to_iot(serde_json::to_string(&notif).unwrap()).await,
+                )
+                .unwrap_or(IotNotification {
+                    status_code: 500,
+                    message: String::from("Internal server error"),
+                    command_id: None,
+                    command_status: None,
+                });
+                RuleActionResult::Command {
+                    device_id,
+                    component_id,
+                    command,
+                    status_code: response.status_code,
+                    message: response.message,
+                    command_id: response.command_id,
+                }
+            }
+        }))
+        .await;
+        results.extend(sent);
+        results
+    }
+}
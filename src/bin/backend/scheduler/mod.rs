@@ -0,0 +1,106 @@
+use std::time::{Duration, SystemTime};
+
+use mongodb::bson::doc;
+use tempusalert_be::backend_core::{
+    features::remote_control_feature::{
+        models::{Schedule, ScheduleRun, ScheduleRunOutcome},
+        schedules::{claim_due_schedules, is_missed, record_run, to_notification},
+        IotNotification,
+    },
+    models::User,
+};
+
+use crate::{config::SchedulerConfig, types::WebFeatureDyn, AppResult};
+
+pub struct SchedulerTask {
+    pub config: SchedulerConfig,
+    mongoc: mongodb::Client,
+    remote_control: Box<WebFeatureDyn>,
+}
+
+impl SchedulerTask {
+    pub async fn create(
+        config: SchedulerConfig,
+        mongoc: mongodb::Client,
+        remote_control: Box<WebFeatureDyn>,
+    ) -> AppResult<Self> {
+        Ok(Self {
+            config,
+            mongoc,
+            remote_control,
+        })
+    }
+
+    pub async fn run(self) -> AppResult {
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            interval.tick().await;
+            let now = SystemTime::now();
+            match claim_due_schedules(&self.mongoc, now).await {
+                Ok(schedules) => {
+                    for schedule in schedules {
+                        let run = self.execute(&schedule, now).await;
+                        if let Err(e) = record_run(&self.mongoc, run).await {
+                            eprintln!("Failed to record run of schedule '{}': {}", schedule.id, e);
+                        }
+                    }
+                }
+                Err(e) => eprintln!("Failed to claim due schedules: {}", e),
+            }
+        }
+    }
+
+    // Sends the command the same way the `/light` and `/buzzer` routes do,
+    // through the remote control web feature.
+    async fn execute(&self, schedule: &Schedule, now: SystemTime) -> ScheduleRun {
+        let mut run = ScheduleRun {
+            schedule_id: schedule.id.clone(),
+            owner_name: schedule.owner_name.clone(),
+            scheduled_for: schedule.next_run_at,
+            ran_at: now,
+            outcome: ScheduleRunOutcome::Failed,
+            command_id: None,
+            message: String::new(),
+        };
+
+        if is_missed(schedule, now, Duration::from_secs(self.config.missed_run_grace_secs)) {
+            run.outcome = ScheduleRunOutcome::Missed;
+            run.message = String::from("Skipped a run missed while the server was down");
+            return run;
+        }
+
+        let client_id = match self
+            .mongoc
+            .default_database()
+            .unwrap()
+            .collection::<User>("users")
+            .find_one(doc! { "email": schedule.owner_name.clone() }, None)
+            .await
+        {
+            Ok(Some(user)) => user.client_id,
+            Ok(None) => {
+                run.message = String::from("Owner of the schedule no longer exists");
+                return run;
+            }
+            Err(e) => {
+                run.message = format!("Failed to find owner of the schedule: {e}");
+                return run;
+            }
+        };
+
+        let notif = serde_json::to_string(&to_notification(schedule, client_id)).unwrap();
+        match serde_json::from_str::<IotNotification>(
+            &self.remote_control.send_message_to_iot(notif).await,
+        ) {
+            Ok(response) => {
+                if response.status_code < 300 {
+                    run.outcome = ScheduleRunOutcome::Dispatched;
+                }
+                run.command_id = response.command_id;
+                run.message = response.message;
+            }
+            Err(_) => run.message = String::from("Internal server error"),
+        }
+        run
+    }
+}
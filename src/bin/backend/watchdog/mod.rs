@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use tempusalert_be::backend_core::features::{
+    devices_status_feature::heartbeat::disconnect_silent_components,
+    remote_control_feature::commands::{expire_pending_commands, expire_stale_commands},
+};
+
+use crate::{config::WatchdogConfig, AppResult};
+
+pub struct WatchdogTask {
+    pub config: WatchdogConfig,
+    mongoc: mongodb::Client,
+}
+
+impl WatchdogTask {
+    pub async fn create(config: WatchdogConfig, mongoc: mongodb::Client) -> AppResult<Self> {
+        Ok(Self { config, mongoc })
+    }
+
+    pub async fn run(self) -> AppResult {
+        let silence = Duration::from_secs(self.config.offline_after_secs);
+        let ack_timeout = Duration::from_secs(self.config.command_ack_timeout_secs);
+        let mut interval = tokio::time::interval(Duration::from_secs(self.config.interval_secs));
+        loop {
+            interval.tick().await;
+            match disconnect_silent_components(&self.mongoc, silence).await {
+                Ok(0) => {}
+                Ok(count) => println!("Marked {count} silent components offline"),
+                Err(e) => eprintln!("Failed to check for silent components: {}", e),
+            }
+            match expire_pending_commands(&self.mongoc, ack_timeout).await {
+                Ok(0) => {}
+                Ok(count) => println!("Timed out {count} unacknowledged commands"),
+                Err(e) => eprintln!("Failed to time out unacknowledged commands: {}", e),
+            }
+            match expire_stale_commands(&self.mongoc).await {
+                Ok(0) => {}
+                Ok(count) => println!("Dropped {count} expired commands from the queue"),
+                Err(e) => eprintln!("Failed to expire queued commands: {}", e),
+            }
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use std::time::{Duration, SystemTime};
+
+use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use axum::{extract::Query, http::StatusCode, middleware::from_fn};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{
+        login_attempts::{clear_failures, find_lockouts, AttemptScope, LoginAttempts},
+        security_events::{count_security_events, SecurityEventCount},
+    },
+    json::Json,
+};
+
+use crate::database_client::{init_database, MONGOC};
+
+use super::middlewares::admin_middleware::{require_admin_key, ADMIN_SECURITY_SCHEME};
+
+const DEFAULT_SECURITY_EVENT_HOURS: u64 = 24;
+// Events are not kept much longer than that anyway
+const MAX_SECURITY_EVENT_HOURS: u64 = 365 * 24;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UnlockBody {
+    scope: AttemptScope,
+    // Email, gateway client id or IP, depending on the scope
+    key: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct SecurityEventsQuery {
+    // How far back to count, a day by default
+    hours: Option<u64>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SecurityEventsResponse {
+    message: String,
+    events: Option<Vec<SecurityEventCount>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct LockoutsResponse {
+    message: String,
+    lockouts: Option<Vec<LoginAttempts>>,
+}
+
+fn lockouts_response(
+    status_code: StatusCode,
+    message: &str,
+    lockouts: Option<Vec<LoginAttempts>>,
+) -> (StatusCode, Json<LockoutsResponse>) {
+    (
+        status_code,
+        Json(LockoutsResponse {
+            message: String::from(message),
+            lockouts,
+        }),
+    )
+}
+
+async fn get_lockouts_handler() -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match find_lockouts(mongoc).await {
+        Ok(lockouts) => lockouts_response(StatusCode::OK, "Successfully fetch lockouts", Some(lockouts)),
+        Err(_) => lockouts_response(StatusCode::INTERNAL_SERVER_ERROR, "Unexpected error while fetching lockouts", None),
+    }
+}
+
+// Also forgets the failures, so the next attempts start over
+async fn unlock_handler(Json(UnlockBody { scope, key }): Json<UnlockBody>) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match clear_failures(mongoc, scope, &key).await {
+        Ok(true) => lockouts_response(StatusCode::OK, "Unlocked successfully", None),
+        Ok(false) => lockouts_response(StatusCode::NOT_FOUND, "No failed logins for this key", None),
+        Err(_) => lockouts_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to unlock", None),
+    }
+}
+
+async fn get_security_events_handler(
+    Query(SecurityEventsQuery { hours }): Query<SecurityEventsQuery>,
+) -> impl IntoApiResponse {
+    let hours = hours.unwrap_or(DEFAULT_SECURITY_EVENT_HOURS).min(MAX_SECURITY_EVENT_HOURS);
+    let since = SystemTime::now() - Duration::from_secs(hours * 3600);
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match count_security_events(mongoc, since).await {
+        Ok(events) => (
+            StatusCode::OK,
+            Json(SecurityEventsResponse {
+                message: String::from("Successfully count security events"),
+                events: Some(events),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(SecurityEventsResponse {
+                message: String::from("Unexpected error while counting security events"),
+                events: None,
+            }),
+        ),
+    }
+}
+
+pub fn admin_routes() -> ApiRouter {
+    ApiRouter::new()
+        .api_route_with(
+            "/security-events",
+            get_with(get_security_events_handler, |op| {
+                op.description("Count the security events of the last hours, such as gateways publishing on the topic of another client")
+                    .tag("Administration")
+                    .response::<200, Json<SecurityEventsResponse>>()
+                    .response::<500, Json<SecurityEventsResponse>>()
+            })
+            .route_layer(from_fn(require_admin_key)),
+            |path_item| path_item.security_requirement(ADMIN_SECURITY_SCHEME),
+        )
+        .api_route_with(
+            "/login-lockouts",
+            get_with(get_lockouts_handler, |op| {
+                op.description("Get the accounts, gateways and IPs that are locked out after too many failed logins")
+                    .tag("Administration")
+                    .response::<200, Json<LockoutsResponse>>()
+                    .response::<500, Json<LockoutsResponse>>()
+            })
+            .delete_with(unlock_handler, |op| {
+                op.description("Lift the lockout of an account, gateway or IP and forget its failed logins")
+                    .tag("Administration")
+                    .response::<200, Json<LockoutsResponse>>()
+                    .response::<404, Json<LockoutsResponse>>()
+                    .response::<500, Json<LockoutsResponse>>()
+            })
+            .route_layer(from_fn(require_admin_key)),
+            |path_item| path_item.security_requirement(ADMIN_SECURITY_SCHEME),
+        )
+}
@@ -1,28 +1,47 @@
+use std::net::SocketAddr;
+
 use aide::axum::{routing::post_with, ApiRouter, IntoApiResponse};
 use axum::{
-    http::{HeaderName, StatusCode},
+    extract::ConnectInfo,
+    http::{header::RETRY_AFTER, HeaderMap, HeaderName, StatusCode},
     response::AppendHeaders,
 };
 use mongodb::{bson::doc, Collection};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use tempusalert_be::{
-    auth::{self, IotClientClaim, WebClientClaim},
+    auth::{
+        self,
+        sessions::{refresh_session, start_session, Session, SessionKind},
+        totp::SystemClock,
+        two_factor::{is_two_factor_enabled, verify_second_factor},
+        IotClientClaim, PreAuthClaim, WebClientClaim,
+    },
     backend_core::models::User,
     json::Json,
 };
 
 use crate::{
-    config::JWT_KEY,
+    config::{CONFIG, JWT_KEY, TOTP_KEY},
     database_client::{init_database, MONGOC},
 };
 
-use super::utils::verify_hashed_password;
+use super::{
+    throttle::{login_blocked_for, record_login_failure, record_login_success, LoginKind},
+    utils::{session_origin, verify_hashed_password},
+};
 
 #[derive(Deserialize, JsonSchema)]
-struct IotAuthBody {
-    client_id: String,
-    client_secret: String,
+#[serde(untagged)]
+enum IotAuthBody {
+    Credential {
+        client_id: String,
+        client_secret: String,
+    },
+    // Lets a gateway renew its access token unattended
+    Refresh {
+        refresh_token: String,
+    },
 }
 
 #[derive(Serialize, JsonSchema)]
@@ -31,31 +50,102 @@ enum Token {
     None,
 }
 
-async fn iot_auth_handler(Json(body): Json<IotAuthBody>) -> impl IntoApiResponse {
+#[derive(Serialize, JsonSchema)]
+struct IotAuthResponse {
+    token: Token,
+    refresh_token: Token,
+    expires_in: Option<u64>,
+}
+
+impl IotAuthResponse {
+    fn none() -> Self {
+        IotAuthResponse {
+            token: Token::None,
+            refresh_token: Token::None,
+            expires_in: None,
+        }
+    }
+}
+
+fn iot_tokens(session: Session, refresh_token: String) -> (StatusCode, Json<IotAuthResponse>) {
+    let ttl_secs = CONFIG.auth.iot_access_token_ttl_secs;
+    let client_claim = IotClientClaim::new(session.subject, session.id, ttl_secs);
+    match auth::sign_jwt(JWT_KEY.as_str(), &client_claim) {
+        Some(token) => (
+            StatusCode::OK,
+            Json(IotAuthResponse {
+                token: Token::Some(token),
+                refresh_token: Token::Some(refresh_token),
+                expires_in: Some(ttl_secs),
+            }),
+        ),
+        None => (StatusCode::INTERNAL_SERVER_ERROR, Json(IotAuthResponse::none())),
+    }
+}
+
+async fn iot_auth_handler(
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<IotAuthBody>,
+) -> impl IntoApiResponse {
     let mongoc = MONGOC.get_or_init(init_database).await;
-    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    let refresh_ttl_secs = CONFIG.auth.iot_refresh_token_ttl_secs;
+    let origin = session_origin(&headers, addr);
 
-    if let Some(_) = user_coll
-        .find_one(
-            doc! { "client_id": body.client_id.clone(), "client_secret": body.client_secret },
-            None,
-        )
-        .await
-        .ok()
-    {
-        let client_claim = IotClientClaim {
-            client_id: body.client_id,
-            nonce: uuid::Uuid::now_v7().into(),
-        };
-        let token = auth::sign_jwt(JWT_KEY.as_str(), &client_claim);
-
-        if let Some(token) = token {
-            (StatusCode::OK, Json(Token::Some(token)))
-        } else {
-            (StatusCode::INTERNAL_SERVER_ERROR, Json(Token::None))
+    let ip = origin.ip.clone();
+    // Refresh tokens are too long to guess, only the IP is limited for them
+    let account = match &body {
+        IotAuthBody::Credential { client_id, .. } => Some(client_id.clone()),
+        IotAuthBody::Refresh { .. } => None,
+    };
+    match login_blocked_for(mongoc, LoginKind::Iot, account.as_deref(), ip.as_deref()).await {
+        Ok(Some(_)) => return (StatusCode::TOO_MANY_REQUESTS, Json(IotAuthResponse::none())),
+        Ok(None) => {}
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(IotAuthResponse::none())),
+    }
+
+    let session = match body {
+        IotAuthBody::Credential {
+            client_id,
+            client_secret,
+        } => {
+            let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+            match user_coll
+                .find_one(doc! { "client_id": client_id.clone() }, None)
+                .await
+            {
+                Ok(Some(User { hashed_client_secret, client_secret_salt, .. }))
+                    if !verify_hashed_password(client_secret, hashed_client_secret, client_secret_salt) =>
+                {
+                    record_login_failure(mongoc, LoginKind::Iot, Some(&client_id), ip.as_deref()).await;
+                    return (StatusCode::BAD_REQUEST, Json(IotAuthResponse::none()));
+                }
+                Ok(Some(User { email_verified: false, .. })) => {
+                    return (StatusCode::FORBIDDEN, Json(IotAuthResponse::none()))
+                }
+                Ok(Some(_)) => {
+                    record_login_success(mongoc, LoginKind::Iot, &client_id).await;
+                    start_session(mongoc, SessionKind::Iot, client_id, origin, refresh_ttl_secs).await
+                }
+                Ok(None) => {
+                    record_login_failure(mongoc, LoginKind::Iot, Some(&client_id), ip.as_deref()).await;
+                    return (StatusCode::BAD_REQUEST, Json(IotAuthResponse::none()));
+                }
+                Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json(IotAuthResponse::none())),
+            }
         }
-    } else {
-        (StatusCode::BAD_REQUEST, Json(Token::None))
+        IotAuthBody::Refresh { refresh_token } => {
+            refresh_session(mongoc, SessionKind::Iot, &refresh_token, origin, refresh_ttl_secs).await
+        }
+    };
+
+    match session {
+        Ok(Some((session, refresh_token))) => iot_tokens(session, refresh_token),
+        Ok(None) => {
+            record_login_failure(mongoc, LoginKind::Iot, None, ip.as_deref()).await;
+            (StatusCode::BAD_REQUEST, Json(IotAuthResponse::none()))
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(IotAuthResponse::none())),
     }
 }
 
@@ -63,11 +153,13 @@ pub fn iot_auth_routes() -> ApiRouter {
     ApiRouter::new().api_route(
         "/",
         post_with(iot_auth_handler, |op| {
-            op.description("Iot authentication api")
+            op.description("Iot authentication api, with either the gateway credential or a refresh token")
                 .tag("Authentication")
-                .response::<200, Json<Token>>()
-                .response::<400, Json<Token>>()
-                .response::<500, Json<Token>>()
+                .response::<200, Json<IotAuthResponse>>()
+                .response::<400, Json<IotAuthResponse>>()
+                .response::<403, Json<IotAuthResponse>>()
+                .response::<429, Json<IotAuthResponse>>()
+                .response::<500, Json<IotAuthResponse>>()
         }),
     )
 }
@@ -78,15 +170,136 @@ struct WebAuthBody {
     password: String,
 }
 
+#[derive(Deserialize, JsonSchema)]
+struct WebTwoFactorBody {
+    pre_auth_token: String,
+    // Code of the authenticator app, or one of the recovery codes
+    code: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct RefreshBody {
+    refresh_token: String,
+}
+
 #[derive(Serialize, JsonSchema)]
 struct WebAuthResponse {
     token: Token,
+    refresh_token: Token,
+    expires_in: Option<u64>,
+    // Set instead of the tokens when the account requires a second factor,
+    // to be sent with the code to `/auth/web/two-factor`
+    pre_auth_token: Token,
     message: String,
 }
 
-async fn web_auth_handler(Json(body): Json<WebAuthBody>) -> impl IntoApiResponse {
+type WebAuthReply = (
+    StatusCode,
+    AppendHeaders<Vec<(HeaderName, String)>>,
+    Json<WebAuthResponse>,
+);
+
+fn web_auth_error(status_code: StatusCode, message: &str) -> WebAuthReply {
+    (
+        status_code,
+        AppendHeaders(vec![(HeaderName::from_static("jwt"), String::new())]),
+        Json(WebAuthResponse {
+            token: Token::None,
+            refresh_token: Token::None,
+            expires_in: None,
+            pre_auth_token: Token::None,
+            message: String::from(message),
+        }),
+    )
+}
+
+fn web_throttled(retry_after_secs: u64) -> WebAuthReply {
+    (
+        StatusCode::TOO_MANY_REQUESTS,
+        AppendHeaders(vec![
+            (HeaderName::from_static("jwt"), String::new()),
+            (RETRY_AFTER, retry_after_secs.to_string()),
+        ]),
+        Json(WebAuthResponse {
+            token: Token::None,
+            refresh_token: Token::None,
+            expires_in: None,
+            pre_auth_token: Token::None,
+            message: format!("Too many failed attempts. Please try again in {retry_after_secs} seconds!"),
+        }),
+    )
+}
+
+fn web_tokens(session: Session, refresh_token: String, message: &str) -> WebAuthReply {
+    let ttl_secs = CONFIG.auth.web_access_token_ttl_secs;
+    let client_claim = WebClientClaim::new(session.subject, session.id, ttl_secs);
+    match auth::sign_jwt(JWT_KEY.as_str(), &client_claim) {
+        Some(token) => (
+            StatusCode::OK,
+            AppendHeaders(vec![
+                (HeaderName::from_static("jwt"), token.clone()),
+                (HeaderName::from_static("loggedin"), String::from("true")),
+            ]),
+            Json(WebAuthResponse {
+                token: Token::Some(token),
+                refresh_token: Token::Some(refresh_token),
+                expires_in: Some(ttl_secs),
+                pre_auth_token: Token::None,
+                message: String::from(message),
+            }),
+        ),
+        None => web_auth_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error. Please try again later!",
+        ),
+    }
+}
+
+// The password alone is not enough, the client has to come back with a code
+// before the pre-auth token expires
+fn web_pre_auth(email: String) -> WebAuthReply {
+    let ttl_secs = CONFIG.auth.pre_auth_token_ttl_secs;
+    match auth::sign_jwt(JWT_KEY.as_str(), &PreAuthClaim::new(email, ttl_secs)) {
+        Some(pre_auth_token) => (
+            StatusCode::OK,
+            AppendHeaders(vec![(HeaderName::from_static("jwt"), String::new())]),
+            Json(WebAuthResponse {
+                token: Token::None,
+                refresh_token: Token::None,
+                expires_in: Some(ttl_secs),
+                pre_auth_token: Token::Some(pre_auth_token),
+                message: String::from("Two-factor authentication code required"),
+            }),
+        ),
+        None => web_auth_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error. Please try again later!",
+        ),
+    }
+}
+
+async fn web_auth_handler(
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(body): Json<WebAuthBody>,
+) -> impl IntoApiResponse {
     let mongoc = MONGOC.get_or_init(init_database).await;
     let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    let origin = session_origin(&headers, addr);
+    let ip = origin.ip.clone();
+
+    // Checked before the password, so that a locked account cannot be
+    // guessed either
+    match login_blocked_for(mongoc, LoginKind::Web, Some(&body.email), ip.as_deref()).await {
+        Ok(Some(retry_after_secs)) => return web_throttled(retry_after_secs),
+        Ok(None) => {}
+        Err(_) => {
+            return web_auth_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error. Please try again later!",
+            )
+        }
+    }
 
     if let Some(Some(User {
         hashed_password,
@@ -98,49 +311,124 @@ async fn web_auth_handler(Json(body): Json<WebAuthBody>) -> impl IntoApiResponse
         .ok()
     {
         if !verify_hashed_password(body.password, hashed_password, salt) {
-            (
-                StatusCode::BAD_REQUEST,
-                AppendHeaders(vec![(HeaderName::from_static("jwt"), String::new())]),
-                Json(WebAuthResponse{ token: Token::None, message: String::from("Wrong password or email") }),
-            )
+            record_login_failure(mongoc, LoginKind::Web, Some(&body.email), ip.as_deref()).await;
+            web_auth_error(StatusCode::BAD_REQUEST, "Wrong password or email")
+        } else if is_two_factor_enabled(mongoc, &body.email).await.unwrap_or(true) {
+            // When the lookup fails the login goes through the second step,
+            // which then fails too, rather than skipping it
+            web_pre_auth(body.email)
         } else {
-            let client_claim = WebClientClaim {
-                email: body.email,
-                nonce: uuid::Uuid::now_v7().into(),
-            };
-            let token = auth::sign_jwt(JWT_KEY.as_str(), &client_claim);
-
-            if let Some(token) = token {
-                (
-                    StatusCode::OK,
-                    AppendHeaders(vec![(HeaderName::from_static("jwt"), format!("{token}")), (HeaderName::from_static("loggedin"), String::from("true"))]),
-                    Json(WebAuthResponse{ token: Token::Some(token), message: String::from("Logged in successfuly") }),
-                )
-            } else {
-                (
+            record_login_success(mongoc, LoginKind::Web, &body.email).await;
+            match start_session(mongoc, SessionKind::Web, body.email, origin, CONFIG.auth.web_refresh_token_ttl_secs).await {
+                Ok(Some((session, refresh_token))) => web_tokens(session, refresh_token, "Logged in successfuly"),
+                _ => web_auth_error(
                     StatusCode::INTERNAL_SERVER_ERROR,
-                    AppendHeaders(vec![(HeaderName::from_static("jwt"), String::new())]),
-                    Json(WebAuthResponse { token: Token::None, message: String::from("Internal server error. Please try again later!") }),
-                )
+                    "Internal server error. Please try again later!",
+                ),
             }
         }
     } else {
-        (
-            StatusCode::BAD_REQUEST,
-            AppendHeaders(vec![(HeaderName::from_static("jwt"), String::new())]),
-            Json(WebAuthResponse{ token: Token::None, message: String::from("Wrong password or email") }),
-        )
+        record_login_failure(mongoc, LoginKind::Web, Some(&body.email), ip.as_deref()).await;
+        web_auth_error(StatusCode::BAD_REQUEST, "Wrong password or email")
+    }
+}
+
+async fn web_two_factor_handler(
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(WebTwoFactorBody { pre_auth_token, code }): Json<WebTwoFactorBody>,
+) -> impl IntoApiResponse {
+    let Some(email) = auth::get_email_from_pre_auth_token(JWT_KEY.as_str(), &pre_auth_token) else {
+        return web_auth_error(StatusCode::UNAUTHORIZED, "Invalid or expired pre-auth token");
+    };
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let origin = session_origin(&headers, addr);
+    let ip = origin.ip.clone();
+    // Codes are short, they are limited like passwords
+    match login_blocked_for(mongoc, LoginKind::Web, Some(&email), ip.as_deref()).await {
+        Ok(Some(retry_after_secs)) => return web_throttled(retry_after_secs),
+        Ok(None) => {}
+        Err(_) => {
+            return web_auth_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error. Please try again later!",
+            )
+        }
+    }
+    match verify_second_factor(mongoc, &TOTP_KEY, &SystemClock, &email, &code).await {
+        Ok(true) => record_login_success(mongoc, LoginKind::Web, &email).await,
+        Ok(false) => {
+            record_login_failure(mongoc, LoginKind::Web, Some(&email), ip.as_deref()).await;
+            return web_auth_error(StatusCode::BAD_REQUEST, "Invalid code");
+        }
+        Err(_) => {
+            return web_auth_error(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Internal server error. Please try again later!",
+            )
+        }
+    }
+    match start_session(mongoc, SessionKind::Web, email, origin, CONFIG.auth.web_refresh_token_ttl_secs).await {
+        Ok(Some((session, refresh_token))) => web_tokens(session, refresh_token, "Logged in successfuly"),
+        _ => web_auth_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error. Please try again later!",
+        ),
+    }
+}
+
+async fn refresh_handler(
+    headers: HeaderMap,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Json(RefreshBody { refresh_token }): Json<RefreshBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let origin = session_origin(&headers, addr);
+    match refresh_session(mongoc, SessionKind::Web, &refresh_token, origin, CONFIG.auth.web_refresh_token_ttl_secs).await {
+        Ok(Some((session, refresh_token))) => web_tokens(session, refresh_token, "Refreshed successfully"),
+        Ok(None) => web_auth_error(StatusCode::UNAUTHORIZED, "Invalid or expired refresh token"),
+        Err(_) => web_auth_error(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Internal server error. Please try again later!",
+        ),
     }
 }
 
 pub fn web_auth_routes() -> ApiRouter {
+    ApiRouter::new()
+        .api_route(
+            "/",
+            post_with(web_auth_handler, |op| {
+                op.description("Web authentication api, returns a pre-auth token instead of the tokens when the account has two-factor authentication")
+                    .tag("Authentication")
+                    .response::<200, Json<WebAuthResponse>>()
+                    .response::<400, Json<WebAuthResponse>>()
+                    .response::<429, Json<WebAuthResponse>>()
+                    .response::<500, Json<WebAuthResponse>>()
+            }),
+        )
+        .api_route(
+            "/two-factor",
+            post_with(web_two_factor_handler, |op| {
+                op.description("Second step of the web authentication, exchange the pre-auth token and a code of the authenticator app or a recovery code for the tokens")
+                    .tag("Authentication")
+                    .response::<200, Json<WebAuthResponse>>()
+                    .response::<400, Json<WebAuthResponse>>()
+                    .response::<401, Json<WebAuthResponse>>()
+                    .response::<429, Json<WebAuthResponse>>()
+                    .response::<500, Json<WebAuthResponse>>()
+            }),
+        )
+}
+
+pub fn refresh_routes() -> ApiRouter {
     ApiRouter::new().api_route(
         "/",
-        post_with(web_auth_handler, |op| {
-            op.description("Web authentication api")
+        post_with(refresh_handler, |op| {
+            op.description("Exchange a refresh token for a new access token and refresh token")
                 .tag("Authentication")
                 .response::<200, Json<WebAuthResponse>>()
-                .response::<400, Json<WebAuthResponse>>()
+                .response::<401, Json<WebAuthResponse>>()
                 .response::<500, Json<WebAuthResponse>>()
         }),
     )
@@ -1,7 +1,7 @@
-use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use aide::axum::{routing::{get_with, patch_with}, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
 };
 use mongodb::bson::doc;
 use schemars::JsonSchema;
@@ -12,6 +12,7 @@ use crate::{
     database_client::{init_database, MONGOC},
     TOGGABLE_FEATURES_NAMES,
 };
+use tempusalert_be::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Serialize, JsonSchema)]
 enum AllFeaturesResponse {
@@ -36,7 +37,6 @@ struct AllFeatureStatusQuery {
 }
 
 async fn get_all_features_status_handler(
-    headers: HeaderMap,
     Query(AllFeatureStatusQuery { email }): Query<AllFeatureStatusQuery>,
 ) -> impl IntoApiResponse {
     if email.is_none() {
@@ -47,19 +47,6 @@ async fn get_all_features_status_handler(
         });
     }
     let email = email.unwrap();
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(AllFeaturesResponse::FeatureStatusResponse {
-                message: String::from("Forbidden"),
-                feature_status: vec![],
-            }),
-        );
-    }
     let toggable_features = unsafe { TOGGABLE_FEATURES_NAMES.clone() };
     let mongoc = MONGOC.get_or_init(init_database).await;
     match mongoc
@@ -129,23 +116,9 @@ struct UpdateFeatureStatusBody {
 }
 
 async fn update_features_status_handler(
-    headers: HeaderMap,
     Query(UpdateFeatureStatusQuery { email }): Query<UpdateFeatureStatusQuery>,
     Json(UpdateFeatureStatusBody { new_feature_status }): Json<UpdateFeatureStatusBody>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(UpdateFeatureStatusResponse {
-                message: String::from("Forbidden"),
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
     let user_coll = mongoc
         .default_database()
@@ -194,23 +167,29 @@ async fn update_features_status_handler(
 }
 
 pub fn features_route() -> ApiRouter {
-    ApiRouter::new().api_route(
-        "/",
-        get_with(get_all_features_status_handler, |op| {
-            op.description("Get all feature status")
-                .tag("Features")
-                .response::<200, Json<AllFeaturesResponse>>()
-                .response::<400, Json<AllFeaturesResponse>>()
-                .response::<403, Json<AllFeaturesResponse>>()
-                .response::<500, Json<AllFeaturesResponse>>()
-        })
-        .patch_with(update_features_status_handler, |op| {
-            op.description("Update the feature status of a user by email if given")
-                .tag("Features")
-                .response::<200, Json<UpdateFeatureStatusResponse>>()
-                .response::<400, Json<UpdateFeatureStatusResponse>>()
-                .response::<403, Json<UpdateFeatureStatusResponse>>()
-                .response::<500, Json<UpdateFeatureStatusResponse>>()
-        }),
-    )
+    ApiRouter::new()
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_all_features_status_handler, |op| {
+                op.description("Get all feature status")
+                    .tag("Features")
+                    .response::<200, Json<AllFeaturesResponse>>()
+                    .response::<400, Json<AllFeaturesResponse>>()
+                    .response::<403, Json<AllFeaturesResponse>>()
+                    .response::<500, Json<AllFeaturesResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Member),
+            patch_with(update_features_status_handler, |op| {
+                op.description("Update the feature status of a user by email if given")
+                    .tag("Features")
+                    .response::<200, Json<UpdateFeatureStatusResponse>>()
+                    .response::<400, Json<UpdateFeatureStatusResponse>>()
+                    .response::<403, Json<UpdateFeatureStatusResponse>>()
+                    .response::<500, Json<UpdateFeatureStatusResponse>>()
+            }),
+        )
 }
@@ -0,0 +1,139 @@
+use aide::axum::{routing::post_with, ApiRouter, IntoApiResponse};
+use axum::http::StatusCode;
+use mongodb::{
+    bson::{doc, spec::BinarySubtype, Binary},
+    Collection,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tempusalert_be::{
+    auth::{
+        policy::{AuthorizedRouter, Policy},
+        principal::Principal,
+        sessions::{end_sessions, SessionKind},
+    },
+    backend_core::{
+        channels::{get_user_publisher, UserEvent, UserEventKind},
+        models::User,
+    },
+    json::Json,
+};
+
+use crate::{
+    database_client::{init_database, MONGOC},
+    mail::send_mail,
+};
+
+use super::utils::hash_password;
+
+#[derive(Serialize, JsonSchema)]
+pub struct RotateCredentialResponse {
+    message: String,
+}
+
+fn rotate_response(status_code: StatusCode, message: &str) -> (StatusCode, Json<RotateCredentialResponse>) {
+    (
+        status_code,
+        Json(RotateCredentialResponse {
+            message: String::from(message),
+        }),
+    )
+}
+
+fn binary(bytes: &[u8]) -> Binary {
+    Binary {
+        subtype: BinarySubtype::Generic,
+        bytes: bytes.to_vec(),
+    }
+}
+
+// The new credential is only ever sent by mail, like at registration. It is
+// stored first, so that the mail never carries a credential that does not work.
+async fn rotate_credential_handler(Principal { email, .. }: Principal) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    let old_client_id = match user_coll.find_one(doc! { "email": email.clone() }, None).await {
+        Ok(Some(user)) => user.client_id,
+        Ok(None) => return rotate_response(StatusCode::FORBIDDEN, "Forbidden"),
+        Err(_) => return rotate_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to rotate credential"),
+    };
+
+    let client_id = uuid::Uuid::new_v4().to_string();
+    let client_secret = uuid::Uuid::new_v4().to_string();
+    let Some((hashed_client_secret, client_secret_salt)) = hash_password(client_secret.clone()) else {
+        return rotate_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash client secret");
+    };
+    match user_coll
+        .update_one(
+            doc! { "email": email.clone(), "client_id": old_client_id.clone() },
+            doc! { "$set": {
+                "client_id": client_id.clone(),
+                "hashed_client_secret": binary(&hashed_client_secret),
+                "client_secret_salt": binary(&client_secret_salt),
+            } },
+            None,
+        )
+        .await
+    {
+        Ok(result) if result.matched_count > 0 => {}
+        // Rotated by another request in the meantime
+        Ok(_) => return rotate_response(StatusCode::CONFLICT, "Credential changed concurrently, please try again"),
+        Err(_) => return rotate_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to rotate credential"),
+    }
+
+    // Sent before anything else can fail, the old credential no longer works
+    let mail_sent = send_mail(
+        email.clone(),
+        String::from("Your new gateway credential"),
+        format!(
+            "
+            <p> Your gateway credential has been changed, please config the new credential at your gateway:
+                <ul>
+                    <li> id: {client_id} </li>
+                    <li> secret: {client_secret} </li>
+                </ul>
+            </p>
+            <p> The previous credential no longer works. </p>
+
+            <footer>
+                <p>Best wishes,<p>
+                <p>Tempusalert team<p>
+            </footer>
+        "
+        ),
+    )
+    .is_some();
+
+    // Tokens issued to the old credential stop working right away
+    if end_sessions(mongoc, SessionKind::Iot, &old_client_id, None).await.is_err() {
+        return rotate_response(StatusCode::INTERNAL_SERVER_ERROR, "Credential rotated but failed to revoke gateway tokens");
+    }
+    let user_publisher = get_user_publisher().await;
+    let _ = user_publisher.send(UserEvent {
+        kind: UserEventKind::CANCEL,
+        client_id: old_client_id,
+    });
+    let _ = user_publisher.send(UserEvent {
+        kind: UserEventKind::JOIN,
+        client_id,
+    });
+    if !mail_sent {
+        return rotate_response(StatusCode::INTERNAL_SERVER_ERROR, "Credential rotated but failed to send mail, please rotate again");
+    }
+    rotate_response(StatusCode::OK, "Credential rotated successfully, check your mail")
+}
+
+pub fn gateway_routes() -> ApiRouter {
+    ApiRouter::new().authorized_route(
+        "/rotate",
+        Policy::Authenticated,
+        post_with(rotate_credential_handler, |op| {
+            op.description("Replace the gateway client id and secret, mail the new credential and revoke the gateway tokens")
+                .tag("Authentication")
+                .response::<200, Json<RotateCredentialResponse>>()
+                .response::<403, Json<RotateCredentialResponse>>()
+                .response::<409, Json<RotateCredentialResponse>>()
+                .response::<500, Json<RotateCredentialResponse>>()
+        }),
+    )
+}
@@ -0,0 +1,338 @@
+use aide::axum::{
+    routing::{delete_with, get_with, patch_with, post_with},
+    ApiRouter, IntoApiResponse,
+};
+use axum::{extract::Query, http::StatusCode};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{
+        policy::{AuthorizedRouter, Policy},
+        principal::Principal,
+    },
+    backend_core::households::{
+        memberships::{
+            accept_invitation, create_household, create_invitation, find_household, find_households,
+            remove_member, set_member_role,
+        },
+        models::{Household, HouseholdRole},
+    },
+    json::Json,
+};
+
+use crate::{
+    config::CONFIG,
+    database_client::{init_database, MONGOC},
+    mail::send_mail,
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetHouseholdsQuery {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateHouseholdBody {
+    email: String,
+    name: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct InviteMemberBody {
+    // Owner of the household
+    email: String,
+    member_email: String,
+    role: HouseholdRole,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct AcceptInvitationBody {
+    email: String,
+    token: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpdateMemberBody {
+    // Owner of the household
+    email: String,
+    member_email: String,
+    role: HouseholdRole,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RemoveMemberBody {
+    // Owner of the household
+    email: String,
+    member_email: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct HouseholdsResponse {
+    message: String,
+    households: Option<Vec<Household>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct HouseholdResponse {
+    message: String,
+    household: Option<Household>,
+}
+
+fn household_response(
+    status_code: StatusCode,
+    message: &str,
+    household: Option<Household>,
+) -> (StatusCode, Json<HouseholdResponse>) {
+    (
+        status_code,
+        Json(HouseholdResponse {
+            message: String::from(message),
+            household,
+        }),
+    )
+}
+
+// Admins can only hand out roles up to their own, and nobody but the owner
+// can be the owner
+fn can_grant(principal: &Principal, owner_name: &str, role: HouseholdRole) -> bool {
+    role < HouseholdRole::Owner
+        && principal
+            .role_in(owner_name)
+            .is_some_and(|own_role| own_role >= HouseholdRole::Admin && own_role >= role)
+}
+
+async fn get_households_handler(
+    Query(GetHouseholdsQuery { email }): Query<GetHouseholdsQuery>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match find_households(mongoc, &email).await {
+        Ok(households) => (
+            StatusCode::OK,
+            Json(HouseholdsResponse {
+                message: String::from("Successfully fetch households"),
+                households: Some(households),
+            }),
+        ),
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(HouseholdsResponse {
+                message: String::from("Unexpected error while fetching households"),
+                households: None,
+            }),
+        ),
+    }
+}
+
+async fn create_household_handler(
+    Json(CreateHouseholdBody { email, name }): Json<CreateHouseholdBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match create_household(mongoc, email, name).await {
+        Ok(Some(household)) => household_response(StatusCode::OK, "Household created successfully", Some(household)),
+        Ok(None) => household_response(StatusCode::CONFLICT, "The user already owns a household", None),
+        Err(_) => household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create household", None),
+    }
+}
+
+// The invitation is sent to the invited email and can only be accepted by
+// that account
+async fn invite_member_handler(
+    principal: Principal,
+    Json(InviteMemberBody {
+        email,
+        member_email,
+        role,
+    }): Json<InviteMemberBody>,
+) -> impl IntoApiResponse {
+    if !can_grant(&principal, &email, role) {
+        return household_response(StatusCode::FORBIDDEN, "Forbidden", None);
+    }
+    let invited_by = principal.email;
+
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let household = match find_household(mongoc, &email).await {
+        Ok(Some(household)) => household,
+        Ok(None) => return household_response(StatusCode::NOT_FOUND, "Household not found", None),
+        Err(_) => return household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to invite member", None),
+    };
+    let token = match create_invitation(
+        mongoc,
+        &household,
+        member_email.clone(),
+        role,
+        invited_by.clone(),
+        CONFIG.auth.household_invitation_ttl_secs,
+    )
+    .await
+    {
+        Ok(Some((_, token))) => token,
+        _ => return household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to invite member", None),
+    };
+    let link = format!("{}/households/join?token={}", CONFIG.auth.frontend_url, token);
+    let household_name = household.name.clone();
+    if send_mail(
+        member_email,
+        String::from("You have been invited to a household"),
+        format!(
+            "
+            <p> {invited_by} invited you to join the household {household_name}. </p>
+            <p> Please <a href=\"{link}\">accept the invitation</a> after logging in with this email. The link can only be used once. </p>
+
+            <footer>
+                <p>Best wishes,<p>
+                <p>Tempusalert team<p>
+            </footer>
+        "
+        ),
+    )
+    .is_none()
+    {
+        return household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send mail", None);
+    }
+    household_response(StatusCode::OK, "Invitation sent successfully", Some(household))
+}
+
+async fn accept_invitation_handler(
+    Json(AcceptInvitationBody { email, token }): Json<AcceptInvitationBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match accept_invitation(mongoc, &email, &token).await {
+        Ok(Some(household)) => household_response(StatusCode::OK, "Joined household successfully", Some(household)),
+        Ok(None) => household_response(StatusCode::BAD_REQUEST, "Invalid or expired invitation", None),
+        Err(_) => household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to accept invitation", None),
+    }
+}
+
+async fn update_member_handler(
+    principal: Principal,
+    Json(UpdateMemberBody {
+        email,
+        member_email,
+        role,
+    }): Json<UpdateMemberBody>,
+) -> impl IntoApiResponse {
+    if !can_grant(&principal, &email, role) {
+        return household_response(StatusCode::FORBIDDEN, "Forbidden", None);
+    }
+
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let household = match find_household(mongoc, &email).await {
+        Ok(Some(household)) => household,
+        Ok(None) => return household_response(StatusCode::NOT_FOUND, "Household not found", None),
+        Err(_) => return household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update member", None),
+    };
+    // Admins cannot demote those above them
+    let Some(member) = household.members.iter().find(|member| member.email == member_email) else {
+        return household_response(StatusCode::NOT_FOUND, "Member not found", None);
+    };
+    if !can_grant(&principal, &email, member.role) {
+        return household_response(StatusCode::FORBIDDEN, "Forbidden", None);
+    }
+    match set_member_role(mongoc, &email, &member_email, role).await {
+        Ok(true) => household_response(StatusCode::OK, "Member updated successfully", None),
+        Ok(false) => household_response(StatusCode::NOT_FOUND, "Member not found", None),
+        Err(_) => household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update member", None),
+    }
+}
+
+// Members can always leave, admins can also remove those not above them
+async fn remove_member_handler(
+    principal: Principal,
+    Json(RemoveMemberBody { email, member_email }): Json<RemoveMemberBody>,
+) -> impl IntoApiResponse {
+    let leaving = principal.email == member_email;
+    if !leaving && !principal.has_role(&email, HouseholdRole::Admin) {
+        return household_response(StatusCode::FORBIDDEN, "Forbidden", None);
+    }
+
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    if !leaving {
+        let household = match find_household(mongoc, &email).await {
+            Ok(Some(household)) => household,
+            Ok(None) => return household_response(StatusCode::NOT_FOUND, "Household not found", None),
+            Err(_) => return household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove member", None),
+        };
+        let Some(member) = household.members.iter().find(|member| member.email == member_email) else {
+            return household_response(StatusCode::NOT_FOUND, "Member not found", None);
+        };
+        if !can_grant(&principal, &email, member.role) {
+            return household_response(StatusCode::FORBIDDEN, "Forbidden", None);
+        }
+    }
+    match remove_member(mongoc, &email, &member_email).await {
+        Ok(true) => household_response(StatusCode::OK, "Member removed successfully", None),
+        Ok(false) => household_response(StatusCode::NOT_FOUND, "Member not found", None),
+        Err(_) => household_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to remove member", None),
+    }
+}
+
+pub fn household_routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Owner),
+            get_with(get_households_handler, |op| {
+                op.description("Get the households the user is a member of")
+                    .tag("Households")
+                    .response::<200, Json<HouseholdsResponse>>()
+                    .response::<403, Json<HouseholdsResponse>>()
+                    .response::<500, Json<HouseholdsResponse>>()
+            })
+            .post_with(create_household_handler, |op| {
+                op.description("Create a household owned by the user, sharing their gateway, rooms and devices")
+                    .tag("Households")
+                    .response::<200, Json<HouseholdResponse>>()
+                    .response::<403, Json<HouseholdResponse>>()
+                    .response::<409, Json<HouseholdResponse>>()
+                    .response::<500, Json<HouseholdResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/invitations",
+            Policy::Role(HouseholdRole::Admin),
+            post_with(invite_member_handler, |op| {
+                op.description("Invite someone by email to the household with a role, requires the admin role")
+                    .tag("Households")
+                    .response::<200, Json<HouseholdResponse>>()
+                    .response::<403, Json<HouseholdResponse>>()
+                    .response::<404, Json<HouseholdResponse>>()
+                    .response::<500, Json<HouseholdResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/invitations/accept",
+            Policy::Role(HouseholdRole::Owner),
+            post_with(accept_invitation_handler, |op| {
+                op.description("Join a household with the token of an invitation sent to the user")
+                    .tag("Households")
+                    .response::<200, Json<HouseholdResponse>>()
+                    .response::<400, Json<HouseholdResponse>>()
+                    .response::<403, Json<HouseholdResponse>>()
+                    .response::<500, Json<HouseholdResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/members",
+            Policy::Role(HouseholdRole::Admin),
+            patch_with(update_member_handler, |op| {
+                op.description("Change the role of a member, requires the admin role")
+                    .tag("Households")
+                    .response::<200, Json<HouseholdResponse>>()
+                    .response::<403, Json<HouseholdResponse>>()
+                    .response::<404, Json<HouseholdResponse>>()
+                    .response::<500, Json<HouseholdResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/members",
+            Policy::Authenticated,
+            delete_with(remove_member_handler, |op| {
+                op.description("Remove a member, requires the admin role unless members remove themselves")
+                    .tag("Households")
+                    .response::<200, Json<HouseholdResponse>>()
+                    .response::<403, Json<HouseholdResponse>>()
+                    .response::<404, Json<HouseholdResponse>>()
+                    .response::<500, Json<HouseholdResponse>>()
+            }),
+        )
+}
@@ -1,15 +1,39 @@
 use aide::axum::{routing::post_with, ApiRouter, IntoApiResponse};
-use axum::{http::{HeaderName, StatusCode}, response::AppendHeaders};
+use axum::{http::{HeaderMap, HeaderName, StatusCode}, response::AppendHeaders};
 use schemars::JsonSchema;
 use serde::Serialize;
-use tempusalert_be::json::Json;
+use tempusalert_be::{
+    auth::{decrypt_jwt, sessions::end_session, WebClientClaim},
+    json::Json,
+};
+
+use crate::{
+    config::JWT_KEY,
+    database_client::{init_database, MONGOC},
+};
 
 #[derive(Serialize, JsonSchema)]
 struct LogoutResponse {
     message: String,
 }
 
-async fn logout_handler() -> impl IntoApiResponse {
+async fn logout_handler(headers: HeaderMap) -> impl IntoApiResponse {
+    // Ending the session revokes its refresh token and every access token
+    // issued from it, even if the access token has already expired
+    let claim = headers
+        .get("jwt")
+        .and_then(|jwt| jwt.to_str().ok())
+        .and_then(|jwt| decrypt_jwt::<WebClientClaim>(JWT_KEY.as_str(), jwt));
+    if let Some(claim) = claim {
+        let mongoc = MONGOC.get_or_init(init_database).await;
+        if end_session(mongoc, &claim.session_id).await.is_err() {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                AppendHeaders([(HeaderName::from_static("jwt"), String::new()), (HeaderName::from_static("loggedin"), String::new())]),
+                Json(LogoutResponse { message: String::from("Failed to end session"), }),
+            );
+        }
+    }
     (
         StatusCode::OK,
         AppendHeaders([(HeaderName::from_static("jwt"), String::new()), (HeaderName::from_static("loggedin"), String::new())]),
@@ -21,9 +45,10 @@ pub fn logout_routes() -> ApiRouter {
     ApiRouter::new().api_route(
         "/",
         post_with(logout_handler, |op| {
-            op.description("Logout API")
+            op.description("Logout API, revokes the session of the token")
                 .tag("Authentication")
                 .response::<200, Json<LogoutResponse>>()
+                .response::<500, Json<LogoutResponse>>()
         }),
     )
 }
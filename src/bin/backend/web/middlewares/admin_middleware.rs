@@ -0,0 +1,28 @@
+use axum::{
+    extract::Request,
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use ring::constant_time::verify_slices_are_equal;
+
+use crate::config::ADMIN_KEY;
+
+// Name of the OpenAPI security scheme of the `x-admin-key` header
+pub const ADMIN_SECURITY_SCHEME: &str = "admin";
+
+// Operator routes are not tied to any account, they take the key from the
+// environment instead of a session
+pub async fn require_admin_key(headers: HeaderMap, request: Request, next: Next) -> Result<Response, StatusCode> {
+    let Some(admin_key) = ADMIN_KEY.as_deref() else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let given = headers
+        .get("x-admin-key")
+        .map(|value| value.as_bytes())
+        .unwrap_or_default();
+    if verify_slices_are_equal(given, admin_key.as_bytes()).is_err() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(next.run(request).await)
+}
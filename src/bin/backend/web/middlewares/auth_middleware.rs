@@ -1,30 +1,42 @@
+use std::collections::HashMap;
+
 use axum::{
     extract::Request,
-    http::{HeaderMap, HeaderValue, StatusCode},
+    http::{HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
-use tempusalert_be::auth::get_email_from_web_token;
+use tempusalert_be::{
+    auth::{get_email_from_web_session, principal::Principal},
+    backend_core::households::memberships::find_household_roles,
+};
 
-use crate::config::JWT_KEY;
+use crate::{
+    config::JWT_KEY,
+    database_client::{init_database, MONGOC},
+};
 
-pub async fn set_username_from_token_in_request_middleware(
+// Handlers and route policies read the `Principal` extension, which unlike a
+// header cannot be set by the client
+pub async fn set_principal_from_token_in_request_middleware(
     headers: HeaderMap,
     mut request: Request,
     next: Next,
 ) -> Result<Response, StatusCode> {
     let value: Option<&str> = headers.get("jwt").and_then(|value| value.to_str().ok());
-    request.headers_mut().remove("email");
     if let Some(jwt) = value {
-        request.headers_mut().append(
-            "email",
-            HeaderValue::from_str(
-                get_email_from_web_token(JWT_KEY.as_str(), jwt.to_string())
-                    .unwrap_or("".to_string())
-                    .as_str(),
-            )
-            .unwrap(),
-        );
+        let mongoc = MONGOC.get_or_init(init_database).await;
+        if let Some(email) = get_email_from_web_session(JWT_KEY.as_str(), jwt.to_string(), mongoc).await {
+            // A failed lookup only costs the user their access to the
+            // households of others
+            let household_roles = find_household_roles(mongoc, &email)
+                .await
+                .unwrap_or_else(|_| HashMap::new());
+            request.extensions_mut().insert(Principal {
+                email,
+                household_roles,
+            });
+        }
     }
     let response = next.run(request).await;
     Ok(response)
@@ -1 +1,2 @@
+pub mod admin_middleware;
 pub mod auth_middleware;
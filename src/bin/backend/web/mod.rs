@@ -1,20 +1,31 @@
+mod admin_apis;
 mod auth_apis;
 mod doc;
 mod feature_apis;
+mod gateway_api;
+mod household_apis;
 mod logout_api;
 mod middlewares;
+mod password_api;
 mod push_apis;
 mod register_api;
 mod room_apis;
+mod rule_apis;
+mod session_apis;
+mod stream_apis;
+mod throttle;
+mod two_factor_apis;
 mod utils;
+mod verification_api;
 
-use std::{str::FromStr, sync::Arc};
+use std::{net::SocketAddr, str::FromStr, sync::Arc};
 
 use aide::{
     axum::ApiRouter,
     openapi::{OpenApi, Tag},
     transform::TransformOpenApi,
 };
+use tempusalert_be::auth::principal::SECURITY_SCHEME;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
 use crate::{
@@ -26,7 +37,10 @@ use axum::{
 };
 
 use self::{
-    doc::docs_routes, middlewares::auth_middleware::set_username_from_token_in_request_middleware,
+    doc::docs_routes,
+    middlewares::{
+        admin_middleware::ADMIN_SECURITY_SCHEME, auth_middleware::set_principal_from_token_in_request_middleware,
+    },
 };
 
 pub struct WebTask {
@@ -70,13 +84,23 @@ impl WebTask {
             .fallback(|uri: Uri| async move {
                 (StatusCode::NOT_FOUND, Json(format!("No route for {uri}")))
             })
+            .nest_api_service("/admin", admin_apis::admin_routes())
             .nest_api_service("/auth/iot", auth_apis::iot_auth_routes())
             .nest_api_service("/auth/web", auth_apis::web_auth_routes())
+            .nest_api_service("/auth/refresh", auth_apis::refresh_routes())
             .nest_api_service("/auth/logout", logout_api::logout_routes())
             .nest_api_service("/auth/register", register_api::register_routes())
+            .nest_api_service("/auth/verify-email", verification_api::verification_routes())
+            .nest_api_service("/auth/password", password_api::password_routes())
             .nest_api_service("/api/push-credential", push_apis::push_routes())
             .nest_api_service("/api/features", feature_apis::features_route())
-            .nest_api_service("/api/rooms", room_apis::room_routes());
+            .nest_api_service("/api/gateway", gateway_api::gateway_routes())
+            .nest_api_service("/api/households", household_apis::household_routes())
+            .nest_api_service("/api/rooms", room_apis::room_routes())
+            .nest_api_service("/api/rules", rule_apis::rule_routes())
+            .nest_api_service("/api/sessions", session_apis::session_routes())
+            .nest_api_service("/api/stream", stream_apis::stream_routes())
+            .nest_api_service("/api/two-factor", two_factor_apis::two_factor_routes());
 
         for feat in &mut self.features {
             self.router = self.router.nest_api_service(
@@ -91,7 +115,7 @@ impl WebTask {
             .finish_api_with(&mut api, WebTask::api_docs)
             .layer(Extension(Arc::new(api)))
             .layer(axum::middleware::from_fn(
-                set_username_from_token_in_request_middleware,
+                set_principal_from_token_in_request_middleware,
             ))
             .layer(TraceLayer::new_for_http())
             .layer(
@@ -101,7 +125,7 @@ impl WebTask {
                     .allow_headers([CONTENT_TYPE, HeaderName::from_str("jwt").unwrap()])
                     .allow_origin("http://localhost:3000".parse::<HeaderValue>().unwrap()),
             ) // TODO: Whitelist additional origins
-            .into_make_service();
+            .into_make_service_with_connect_info::<SocketAddr>();
         tokio::spawn(async move {
             println!(
                 "Web server ready to server on {}://{}:{}",
@@ -134,5 +158,23 @@ impl WebTask {
                     extensions: Default::default(),
                 },
             )
+            .security_scheme(
+                SECURITY_SCHEME,
+                aide::openapi::SecurityScheme::ApiKey {
+                    location: aide::openapi::ApiKeyLocation::Header,
+                    name: "jwt".into(),
+                    description: Some("Access token of a web session, from `/auth/web` or `/auth/refresh`.".into()),
+                    extensions: Default::default(),
+                },
+            )
+            .security_scheme(
+                ADMIN_SECURITY_SCHEME,
+                aide::openapi::SecurityScheme::ApiKey {
+                    location: aide::openapi::ApiKeyLocation::Header,
+                    name: "x-admin-key".into(),
+                    description: Some("The `ADMIN_KEY` of the server environment.".into()),
+                    extensions: Default::default(),
+                },
+            )
     }
 }
@@ -0,0 +1,164 @@
+use aide::axum::{routing::post_with, ApiRouter, IntoApiResponse};
+use axum::http::StatusCode;
+use mongodb::{
+    bson::{doc, spec::BinarySubtype, Binary},
+    Collection,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{
+        login_attempts::{clear_failures, AttemptScope},
+        password_resets::{consume_password_reset, create_password_reset},
+        sessions::{end_sessions, SessionKind},
+    },
+    backend_core::models::User,
+    json::Json,
+};
+
+use crate::{
+    config::CONFIG,
+    database_client::{init_database, MONGOC},
+    mail::send_mail,
+};
+
+use super::utils::hash_password;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ForgotPasswordBody {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct ResetPasswordBody {
+    token: String,
+    password: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct PasswordResponse {
+    message: String,
+}
+
+fn password_response(status_code: StatusCode, message: &str) -> (StatusCode, Json<PasswordResponse>) {
+    (
+        status_code,
+        Json(PasswordResponse {
+            message: String::from(message),
+        }),
+    )
+}
+
+fn binary(bytes: &[u8]) -> Binary {
+    Binary {
+        subtype: BinarySubtype::Generic,
+        bytes: bytes.to_vec(),
+    }
+}
+
+// Answers the same whether the account exists or not, so that the endpoint
+// cannot be used to find registered emails
+async fn forgot_password_handler(Json(ForgotPasswordBody { email }): Json<ForgotPasswordBody>) -> impl IntoApiResponse {
+    let sent = password_response(StatusCode::OK, "A reset link has been sent if the account exists");
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    match user_coll.find_one(doc! { "email": email.clone() }, None).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return sent,
+        Err(_) => return password_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send reset link"),
+    }
+
+    let token = match create_password_reset(mongoc, email.clone(), CONFIG.auth.password_reset_ttl_secs).await {
+        Ok(Some(token)) => token,
+        _ => return password_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send reset link"),
+    };
+    let link = format!("{}/reset-password?token={}", CONFIG.auth.frontend_url, token);
+    if send_mail(
+        email.clone(),
+        String::from("Reset your password"),
+        format!(
+            "
+            <p> Please <a href=\"{link}\">reset your password</a>. The link can only be used once. </p>
+            <p> You can ignore this mail if you did not ask to reset your password. </p>
+
+            <footer>
+                <p>Best wishes,<p>
+                <p>Tempusalert team<p>
+            </footer>
+        "
+        ),
+    )
+    .is_none()
+    {
+        eprintln!("Failed to send reset link to '{}'", email);
+    }
+    sent
+}
+
+async fn reset_password_handler(
+    Json(ResetPasswordBody { token, password }): Json<ResetPasswordBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let email = match consume_password_reset(mongoc, &token).await {
+        Ok(Some(email)) => email,
+        Ok(None) => return password_response(StatusCode::BAD_REQUEST, "Invalid or expired reset token"),
+        Err(_) => return password_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to reset password"),
+    };
+    let Some((hashed_password, salt)) = hash_password(password) else {
+        return password_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to hash password");
+    };
+
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    // Opening the link also proves the email is owned
+    let user = match user_coll
+        .find_one_and_update(
+            doc! { "email": email.clone() },
+            doc! { "$set": {
+                "hashed_password": binary(&hashed_password),
+                "salt": binary(&salt),
+                "email_verified": true,
+            } },
+            None,
+        )
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => return password_response(StatusCode::BAD_REQUEST, "Invalid or expired reset token"),
+        Err(_) => return password_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to reset password"),
+    };
+
+    // Whoever knew the old password is logged out everywhere
+    if end_sessions(mongoc, SessionKind::Web, &email, None).await.is_err()
+        || end_sessions(mongoc, SessionKind::Iot, &user.client_id, None).await.is_err()
+    {
+        return password_response(StatusCode::INTERNAL_SERVER_ERROR, "Password reset but failed to revoke sessions");
+    }
+    // Proving the email is enough to lift a lockout of the account
+    if clear_failures(mongoc, AttemptScope::WebAccount, &email).await.is_err() {
+        eprintln!("Failed to unlock '{}' after a password reset", email);
+    }
+    password_response(StatusCode::OK, "Password reset successfully")
+}
+
+pub fn password_routes() -> ApiRouter {
+    ApiRouter::new()
+        .api_route(
+            "/forgot",
+            post_with(forgot_password_handler, |op| {
+                op.description("Send a single-use password reset link by mail")
+                    .tag("Authentication")
+                    .response::<200, Json<PasswordResponse>>()
+                    .response::<500, Json<PasswordResponse>>()
+            }),
+        )
+        .api_route(
+            "/reset",
+            post_with(reset_password_handler, |op| {
+                op.description("Set a new password with a reset token, revoke every session of the account and lift its lockout")
+                    .tag("Authentication")
+                    .response::<200, Json<PasswordResponse>>()
+                    .response::<400, Json<PasswordResponse>>()
+                    .response::<500, Json<PasswordResponse>>()
+            }),
+        )
+}
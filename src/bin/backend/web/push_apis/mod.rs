@@ -4,7 +4,7 @@ use aide::{
 };
 use axum::{
     extract::Path,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
 };
 use mongodb::{
     bson::{doc, to_bson, Document},
@@ -12,7 +12,12 @@ use mongodb::{
 };
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tempusalert_be::{backend_core::models::PushCredential, json::Json, push_notification::PUBLIC_KEY};
+use tempusalert_be::{
+    auth::policy::{AuthorizedRouter, Policy},
+    backend_core::{households::models::HouseholdRole, models::PushCredential},
+    json::Json,
+    push_notification::PUBLIC_KEY,
+};
 
 use crate::database_client::{init_database, MONGOC};
 
@@ -32,19 +37,16 @@ struct PushCredentialResponse {
 }
 
 async fn register_push_credential_handler(
-    headers: HeaderMap,
     Path(Params { email }): Path<Params>,
     Json(body): Json<PushCredentialBody>,
 ) -> impl IntoApiResponse {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
+    // The route policy vouches for the email of the path, the credential is
+    // stored under its own email
+    if body.credential.email != email {
         return (
-            StatusCode::UNAUTHORIZED,
+            StatusCode::FORBIDDEN,
             Json(PushCredentialResponse {
-                message: String::from("Unauthorized"),
+                message: String::from("Forbidden"),
             }),
         );
     }
@@ -66,8 +68,9 @@ async fn get_public_key_handler() -> impl IntoApiResponse {
 }
 
 pub fn push_routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/:email",
+        Policy::Role(HouseholdRole::Owner),
         post_with(register_push_credential_handler, |op| {
             op.description("Add subscription for push notification")
                 .tag("Push notification")
@@ -75,6 +78,7 @@ pub fn push_routes() -> ApiRouter {
                     op.description("The registered user's email")
                 })
                 .response::<200, Json<PushCredentialResponse>>()
+                .response::<403, Json<PushCredentialResponse>>()
                 .response::<500, Json<PushCredentialResponse>>()
         })
     ).api_route(
@@ -3,15 +3,20 @@ use axum::http::StatusCode;
 use mongodb::{bson::doc, Collection};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use tempusalert_be::{backend_core::models::User, json::Json};
+use tempusalert_be::{
+    backend_core::{
+        channels::{get_user_publisher, UserEvent, UserEventKind},
+        models::User,
+    },
+    json::Json,
+};
 
 use crate::{
     database_client::{init_database, MONGOC},
-    globals::channels::{get_user_publisher, UserEvent, UserEventKind},
     mail::send_mail,
 };
 
-use super::utils::hash_password;
+use super::{utils::hash_password, verification_api::verification_link};
 
 #[derive(Deserialize, JsonSchema)]
 struct RegisterBody {
@@ -50,13 +55,30 @@ async fn register_handler(Json(body): Json<RegisterBody>) -> impl IntoApiRespons
                 )
             }
         };
+        let Some(link) = verification_link(body.email.clone()) else {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(RegisterResponse {
+                    message: String::from("Failed to create verification link"),
+                }),
+            );
+        };
         let client_id = uuid::Uuid::new_v4().to_string();
         let client_secret = uuid::Uuid::new_v4().to_string();
+        let Some((hashed_client_secret, client_secret_salt)) = hash_password(client_secret.clone()) else {
+            return (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(RegisterResponse {
+                    message: String::from("Failed to hash client secret"),
+                }),
+            );
+        };
         if let None = send_mail(
             body.email.clone(),
-            String::from("Your gateway credential"),
+            String::from("Verify your email and set up your gateway"),
             format!(
                 "
+                <p> Please <a href=\"{link}\">verify your email</a>. Your gateway can connect once it is verified. </p>
                 <p> Please config the given credential at your gateway:
                     <ul>
                         <li> id: {client_id} </li>
@@ -83,9 +105,11 @@ async fn register_handler(Json(body): Json<RegisterBody>) -> impl IntoApiRespons
                 email: body.email,
                 hashed_password,
                 client_id: client_id.clone(),
-                client_secret,
+                hashed_client_secret,
+                client_secret_salt,
                 salt,
                 enabled_features: vec![],
+                email_verified: false,
             };
             if let Err(_) = user_coll.insert_one(user, None).await {
                 (
@@ -103,7 +127,7 @@ async fn register_handler(Json(body): Json<RegisterBody>) -> impl IntoApiRespons
                 (
                     StatusCode::OK,
                     Json(RegisterResponse {
-                        message: String::from("Registered successfully"),
+                        message: String::from("Registered successfully, please verify your email"),
                     }),
                 )
             }
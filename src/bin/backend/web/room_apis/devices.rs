@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use aide::axum::{routing::post_with, ApiRouter};
-use axum::http::{HeaderMap, StatusCode};
+use axum::http::StatusCode;
 use mongodb::{bson::doc, Collection};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
@@ -10,6 +10,7 @@ use tempusalert_be::{backend_core::models::Room, json::Json};
 use crate::database_client::{init_database, MONGOC};
 
 use super::utils::{check_device_exist, DeviceCheckExistResult};
+use tempusalert_be::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct DeviceIdentifiersBody {
@@ -24,26 +25,12 @@ pub struct NotificationMessageResponse {
 }
 
 async fn add_device_handler(
-    headers: HeaderMap,
     Json(DeviceIdentifiersBody {
         email,
         room_name,
         device_ids,
     }): Json<DeviceIdentifiersBody>,
 ) -> (StatusCode, Json<NotificationMessageResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(NotificationMessageResponse {
-                message: String::from("Forbidden"),
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -139,26 +126,12 @@ async fn add_device_handler(
 }
 
 async fn remove_device_from_room_handler(
-    headers: HeaderMap,
     Json(DeviceIdentifiersBody {
         email,
         room_name,
         device_ids,
     }): Json<DeviceIdentifiersBody>,
 ) -> (StatusCode, Json<NotificationMessageResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(NotificationMessageResponse {
-                message: String::from("Forbidden"),
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -238,8 +211,9 @@ async fn remove_device_from_room_handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
+    ApiRouter::new().authorized_route(
         "/devices",
+        Policy::Role(HouseholdRole::Member),
         post_with(add_device_handler, |op| {
             op.description("Add devices to room for specific user")
                 .tag("Room")
@@ -1,7 +1,7 @@
-use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use aide::axum::{routing::{get_with, post_with}, ApiRouter, IntoApiResponse};
 use axum::{
     extract::Query,
-    http::{HeaderMap, StatusCode},
+    http::StatusCode,
 };
 use mongodb::{bson::doc, Collection};
 use schemars::JsonSchema;
@@ -15,6 +15,7 @@ use tempusalert_be::{
 };
 
 use crate::database_client::{init_database, MONGOC};
+use tempusalert_be::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
 
 #[derive(Deserialize, JsonSchema)]
 pub struct GetRoomsQuery {
@@ -53,36 +54,20 @@ pub enum GetRoomsOfUserResponse {
 }
 
 async fn get_rooms_handler(
-    headers: HeaderMap,
     Query(GetRoomsQuery { email, room_name, name_only }): Query<GetRoomsQuery>,
 ) -> impl IntoApiResponse {
     if let Some(_) = name_only {
-        get_name_only_handler(headers, email).await
+        get_name_only_handler(email).await
     } else if let Some(id) = room_name {
-        get_one_handler(headers, email, id).await
+        get_one_handler(email, id).await
     } else {
-        get_all_handler(headers, email).await
+        get_all_handler(email).await
     }
 }
 
 async fn get_name_only_handler(
-    headers: HeaderMap,
     email: String,
 ) -> (StatusCode, Json<GetRoomsOfUserResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetRoomsOfUserResponse::GetRoomNames {
-                message: String::from("Forbidden"),
-                value: None,
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -116,23 +101,8 @@ async fn get_name_only_handler(
 }
 
 async fn get_all_handler(
-    headers: HeaderMap,
     email: String,
 ) -> (StatusCode, Json<GetRoomsOfUserResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetRoomsOfUserResponse::GetAllRooms {
-                message: String::from("Forbidden"),
-                value: None,
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -194,24 +164,9 @@ async fn get_all_handler(
 }
 
 async fn get_one_handler(
-    headers: HeaderMap,
     email: String,
     room_name: String,
 ) -> (StatusCode, Json<GetRoomsOfUserResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(GetRoomsOfUserResponse::GetOneRoom {
-                message: String::from("Forbidden"),
-                value: None,
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -292,22 +247,8 @@ pub struct NotificationMessageResponse {
 }
 
 async fn create_room_handler(
-    headers: HeaderMap,
     Json(RoomIdentifier { email, room_name }): Json<RoomIdentifier>,
 ) -> (StatusCode, Json<NotificationMessageResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(NotificationMessageResponse {
-                message: String::from("Forbidden"),
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -360,22 +301,8 @@ async fn create_room_handler(
 }
 
 async fn delete_room_handler(
-    headers: HeaderMap,
     Query(RoomIdentifier { email, room_name }): Query<RoomIdentifier>,
 ) -> (StatusCode, Json<NotificationMessageResponse>) {
-    if headers.get("email").is_none()
-        || headers
-            .get("email")
-            .is_some_and(|value| value != email.as_str())
-    {
-        return (
-            StatusCode::FORBIDDEN,
-            Json(NotificationMessageResponse {
-                message: String::from("Forbidden"),
-            }),
-        );
-    }
-
     let mongoc = MONGOC.get_or_init(init_database).await;
 
     let room_coll: Collection<Room> = mongoc.default_database().unwrap().collection("rooms");
@@ -416,28 +343,34 @@ async fn delete_room_handler(
 }
 
 pub fn routes() -> ApiRouter {
-    ApiRouter::new().api_route(
-        "/",
-        get_with(get_rooms_handler, |op| {
-            op.description("Get room by user email")
-                .tag("Room")
-                .response::<200, Json<GetRoomsOfUserResponse>>()
-                .response::<403, Json<GetRoomsOfUserResponse>>()
-                .response::<500, Json<GetRoomsOfUserResponse>>()
-        })
-        .post_with(create_room_handler, |op| {
-            op.description("Create new room for specific user")
-                .tag("Room")
-                .response::<200, Json<NotificationMessageResponse>>()
-                .response::<403, Json<NotificationMessageResponse>>()
-                .response::<500, Json<NotificationMessageResponse>>()
-        })
-        .delete_with(delete_room_handler, |op| {
-            op.description("Delete new room for specific user")
-                .tag("Room")
-                .response::<200, Json<NotificationMessageResponse>>()
-                .response::<403, Json<NotificationMessageResponse>>()
-                .response::<500, Json<NotificationMessageResponse>>()
-        }),
-    )
+    ApiRouter::new()
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_rooms_handler, |op| {
+                op.description("Get room by user email")
+                    .tag("Room")
+                    .response::<200, Json<GetRoomsOfUserResponse>>()
+                    .response::<403, Json<GetRoomsOfUserResponse>>()
+                    .response::<500, Json<GetRoomsOfUserResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Member),
+            post_with(create_room_handler, |op| {
+                op.description("Create new room for specific user")
+                    .tag("Room")
+                    .response::<200, Json<NotificationMessageResponse>>()
+                    .response::<403, Json<NotificationMessageResponse>>()
+                    .response::<500, Json<NotificationMessageResponse>>()
+            })
+            .delete_with(delete_room_handler, |op| {
+                op.description("Delete new room for specific user")
+                    .tag("Room")
+                    .response::<200, Json<NotificationMessageResponse>>()
+                    .response::<403, Json<NotificationMessageResponse>>()
+                    .response::<500, Json<NotificationMessageResponse>>()
+            }),
+        )
 }
@@ -0,0 +1,320 @@
+use std::time::SystemTime;
+
+use aide::axum::{routing::{get_with, post_with}, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, options::FindOptions};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    backend_core::automation::{
+        models::{Rule, RuleAction, RuleCondition, RuleRun, RuleTrigger, DEFAULT_RULE_COOLDOWN_SECS},
+        rules::{get_rule_coll, get_rule_run_coll},
+    },
+    json::Json,
+};
+
+use crate::database_client::{init_database, MONGOC};
+use tempusalert_be::{auth::policy::{AuthorizedRouter, Policy}, backend_core::households::models::HouseholdRole};
+
+const MAX_RUNS_PER_REQUEST: i64 = 100;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetRulesQuery {
+    email: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct GetRuleRunsQuery {
+    email: String,
+    rule_id: Option<String>,
+    offset: Option<u64>,
+    limit: Option<i64>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct CreateRuleBody {
+    email: String,
+    name: String,
+    trigger: RuleTrigger,
+    #[serde(default)]
+    conditions: Vec<RuleCondition>,
+    actions: Vec<RuleAction>,
+    cooldown_secs: Option<u64>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct UpdateRuleBody {
+    email: String,
+    rule_id: String,
+    name: Option<String>,
+    trigger: Option<RuleTrigger>,
+    conditions: Option<Vec<RuleCondition>>,
+    actions: Option<Vec<RuleAction>>,
+    cooldown_secs: Option<u64>,
+    enabled: Option<bool>,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct DeleteRuleBody {
+    email: String,
+    rule_id: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetRulesResponse {
+    message: String,
+    rules: Option<Vec<Rule>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetRuleRunsResponse {
+    message: String,
+    runs: Option<Vec<RuleRun>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct RuleResponse {
+    message: String,
+    rule: Option<Rule>,
+}
+
+fn rule_response(status_code: StatusCode, message: &str, rule: Option<Rule>) -> (StatusCode, Json<RuleResponse>) {
+    (
+        status_code,
+        Json(RuleResponse {
+            message: String::from(message),
+            rule,
+        }),
+    )
+}
+
+async fn get_rules_handler(
+    Query(GetRulesQuery { email }): Query<GetRulesQuery>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match get_rule_coll(mongoc)
+        .find(doc! { "owner_name": email }, None)
+        .await
+    {
+        Ok(mut cursor) => {
+            let mut rules = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(rule) => rules.push(rule),
+                    Err(e) => eprintln!("Error deserializing rule: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetRulesResponse {
+                    message: String::from("Successfully fetch rules"),
+                    rules: Some(rules),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetRulesResponse {
+                message: String::from("Unexpected error while fetching rules"),
+                rules: None,
+            }),
+        ),
+    }
+}
+
+async fn create_rule_handler(
+    Json(CreateRuleBody {
+        email,
+        name,
+        trigger,
+        conditions,
+        actions,
+        cooldown_secs,
+    }): Json<CreateRuleBody>,
+) -> impl IntoApiResponse {
+    if actions.is_empty() {
+        return rule_response(StatusCode::BAD_REQUEST, "A rule needs at least one action", None);
+    }
+
+    let rule = Rule {
+        id: uuid::Uuid::now_v7().into(),
+        owner_name: email,
+        name,
+        enabled: true,
+        trigger,
+        conditions,
+        actions,
+        cooldown_secs: cooldown_secs.unwrap_or(DEFAULT_RULE_COOLDOWN_SECS),
+        last_fired_at: None,
+        created_at: SystemTime::now(),
+    };
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match get_rule_coll(mongoc).insert_one(&rule, None).await {
+        Ok(_) => rule_response(StatusCode::OK, "Rule created successfully", Some(rule)),
+        Err(_) => rule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to create rule", None),
+    }
+}
+
+async fn update_rule_handler(
+    Json(UpdateRuleBody {
+        email,
+        rule_id,
+        name,
+        trigger,
+        conditions,
+        actions,
+        cooldown_secs,
+        enabled,
+    }): Json<UpdateRuleBody>,
+) -> impl IntoApiResponse {
+    let rule_coll = get_rule_coll(MONGOC.get_or_init(init_database).await);
+    let filter = doc! { "id": rule_id, "owner_name": email };
+    let mut rule = match rule_coll.find_one(filter.clone(), None).await {
+        Ok(Some(rule)) => rule,
+        Ok(None) => return rule_response(StatusCode::NOT_FOUND, "Rule not found", None),
+        Err(_) => return rule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update rule", None),
+    };
+    if let Some(name) = name {
+        rule.name = name;
+    }
+    if let Some(trigger) = trigger {
+        rule.trigger = trigger;
+    }
+    if let Some(conditions) = conditions {
+        rule.conditions = conditions;
+    }
+    if let Some(actions) = actions {
+        if actions.is_empty() {
+            return rule_response(StatusCode::BAD_REQUEST, "A rule needs at least one action", None);
+        }
+        rule.actions = actions;
+    }
+    if let Some(cooldown_secs) = cooldown_secs {
+        rule.cooldown_secs = cooldown_secs;
+    }
+    if let Some(enabled) = enabled {
+        rule.enabled = enabled;
+    }
+
+    match rule_coll.replace_one(filter, &rule, None).await {
+        Ok(_) => rule_response(StatusCode::OK, "Rule updated successfully", Some(rule)),
+        Err(_) => rule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to update rule", None),
+    }
+}
+
+async fn delete_rule_handler(
+    Json(DeleteRuleBody { email, rule_id }): Json<DeleteRuleBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match get_rule_coll(mongoc)
+        .find_one_and_delete(doc! { "id": rule_id, "owner_name": email }, None)
+        .await
+    {
+        Ok(Some(rule)) => rule_response(StatusCode::OK, "Rule deleted successfully", Some(rule)),
+        Ok(None) => rule_response(StatusCode::NOT_FOUND, "Rule not found", None),
+        Err(_) => rule_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete rule", None),
+    }
+}
+
+async fn get_rule_runs_handler(
+    Query(GetRuleRunsQuery {
+        email,
+        rule_id,
+        offset,
+        limit,
+    }): Query<GetRuleRunsQuery>,
+) -> impl IntoApiResponse {
+    let mut filter = doc! { "owner_name": email };
+    if let Some(rule_id) = rule_id {
+        filter.insert("rule_id", rule_id);
+    }
+    let find_options = FindOptions::builder()
+        .sort(doc! { "fired_at.secs_since_epoch": -1, "fired_at.nanos_since_epoch": -1 })
+        .skip(offset)
+        .limit(limit.unwrap_or(MAX_RUNS_PER_REQUEST).min(MAX_RUNS_PER_REQUEST))
+        .build();
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match get_rule_run_coll(mongoc).find(filter, find_options).await {
+        Ok(mut cursor) => {
+            let mut runs = vec![];
+            while let Ok(true) = cursor.advance().await {
+                match cursor.deserialize_current() {
+                    Ok(run) => runs.push(run),
+                    Err(e) => eprintln!("Error deserializing rule run: {}", e),
+                }
+            }
+            (
+                StatusCode::OK,
+                Json(GetRuleRunsResponse {
+                    message: String::from("Successfully fetch rule runs"),
+                    runs: Some(runs),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetRuleRunsResponse {
+                message: String::from("Unexpected error while fetching rule runs"),
+                runs: None,
+            }),
+        ),
+    }
+}
+
+pub fn rule_routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_rules_handler, |op| {
+                op.description("Get automation rules by user email")
+                    .tag("Automation")
+                    .response::<200, Json<GetRulesResponse>>()
+                    .response::<403, Json<GetRulesResponse>>()
+                    .response::<500, Json<GetRulesResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/",
+            Policy::Role(HouseholdRole::Member),
+            post_with(create_rule_handler, |op| {
+                op.description("Create a rule that runs actions when an event matches its conditions")
+                    .tag("Automation")
+                    .response::<200, Json<RuleResponse>>()
+                    .response::<400, Json<RuleResponse>>()
+                    .response::<403, Json<RuleResponse>>()
+                    .response::<500, Json<RuleResponse>>()
+            })
+            .patch_with(update_rule_handler, |op| {
+                op.description("Update, enable or disable a rule")
+                    .tag("Automation")
+                    .response::<200, Json<RuleResponse>>()
+                    .response::<400, Json<RuleResponse>>()
+                    .response::<403, Json<RuleResponse>>()
+                    .response::<404, Json<RuleResponse>>()
+                    .response::<500, Json<RuleResponse>>()
+            })
+            .delete_with(delete_rule_handler, |op| {
+                op.description("Delete a rule")
+                    .tag("Automation")
+                    .response::<200, Json<RuleResponse>>()
+                    .response::<403, Json<RuleResponse>>()
+                    .response::<404, Json<RuleResponse>>()
+                    .response::<500, Json<RuleResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/runs",
+            Policy::Role(HouseholdRole::Viewer),
+            get_with(get_rule_runs_handler, |op| {
+                op.description("Get the history of fired rules and the outcome of their actions, newest first")
+                    .tag("Automation")
+                    .response::<200, Json<GetRuleRunsResponse>>()
+                    .response::<403, Json<GetRuleRunsResponse>>()
+                    .response::<500, Json<GetRuleRunsResponse>>()
+            }),
+        )
+}
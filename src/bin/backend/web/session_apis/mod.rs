@@ -0,0 +1,215 @@
+use std::time::SystemTime;
+
+use aide::axum::{
+    routing::{delete_with, get_with},
+    ApiRouter, IntoApiResponse,
+};
+use axum::http::{HeaderMap, StatusCode};
+use mongodb::{bson::doc, Collection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{
+        decrypt_jwt,
+        policy::{AuthorizedRouter, Policy},
+        principal::Principal,
+        sessions::{end_session, end_sessions, find_sessions, Session, SessionKind},
+        WebClientClaim,
+    },
+    backend_core::models::User,
+    json::Json,
+};
+
+use crate::{
+    config::JWT_KEY,
+    database_client::{init_database, MONGOC},
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RevokeSessionBody {
+    session_id: String,
+}
+
+#[derive(Deserialize, JsonSchema)]
+pub struct RevokeSessionsBody {
+    // Both web and IoT sessions are revoked when omitted
+    kind: Option<SessionKind>,
+    // Keeps the session of the request logged in
+    #[serde(default)]
+    keep_current: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct SessionInfo {
+    id: String,
+    kind: SessionKind,
+    user_agent: Option<String>,
+    ip: Option<String>,
+    created_at: SystemTime,
+    last_used_at: Option<SystemTime>,
+    expires_at: SystemTime,
+    // Whether this is the session of the request
+    current: bool,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct GetSessionsResponse {
+    message: String,
+    sessions: Option<Vec<SessionInfo>>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct RevokeSessionsResponse {
+    message: String,
+    revoked: Option<u64>,
+}
+
+fn current_session_id(headers: &HeaderMap) -> Option<String> {
+    let jwt = headers.get("jwt")?.to_str().ok()?;
+    decrypt_jwt::<WebClientClaim>(JWT_KEY.as_str(), jwt).map(|claim| claim.session_id)
+}
+
+fn revoke_response(status_code: StatusCode, message: &str, revoked: Option<u64>) -> (StatusCode, Json<RevokeSessionsResponse>) {
+    (
+        status_code,
+        Json(RevokeSessionsResponse {
+            message: String::from(message),
+            revoked,
+        }),
+    )
+}
+
+// Gateway sessions belong to the client id of the user
+async fn find_client_id(mongoc: &mongodb::Client, email: &str) -> mongodb::error::Result<Option<String>> {
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    Ok(user_coll
+        .find_one(doc! { "email": email }, None)
+        .await?
+        .map(|user| user.client_id))
+}
+
+async fn find_user_sessions(mongoc: &mongodb::Client, email: &str) -> mongodb::error::Result<Vec<Session>> {
+    let mut sessions = find_sessions(mongoc, SessionKind::Web, email).await?;
+    if let Some(client_id) = find_client_id(mongoc, email).await? {
+        sessions.extend(find_sessions(mongoc, SessionKind::Iot, &client_id).await?);
+    }
+    Ok(sessions)
+}
+
+async fn get_sessions_handler(headers: HeaderMap, Principal { email, .. }: Principal) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let current_session_id = current_session_id(&headers);
+    match find_user_sessions(mongoc, &email).await {
+        Ok(sessions) => {
+            let sessions = sessions
+                .into_iter()
+                .map(|session| SessionInfo {
+                    current: current_session_id.as_ref() == Some(&session.id),
+                    id: session.id,
+                    kind: session.kind,
+                    user_agent: session.user_agent,
+                    ip: session.ip,
+                    created_at: session.created_at,
+                    last_used_at: session.last_used_at,
+                    expires_at: session.expires_at,
+                })
+                .collect();
+            (
+                StatusCode::OK,
+                Json(GetSessionsResponse {
+                    message: String::from("Successfully fetch sessions"),
+                    sessions: Some(sessions),
+                }),
+            )
+        }
+        Err(_) => (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(GetSessionsResponse {
+                message: String::from("Unexpected error while fetching sessions"),
+                sessions: None,
+            }),
+        ),
+    }
+}
+
+async fn revoke_session_handler(
+    Principal { email, .. }: Principal,
+    Json(RevokeSessionBody { session_id }): Json<RevokeSessionBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let owned = match find_user_sessions(mongoc, &email).await {
+        Ok(sessions) => sessions.iter().any(|session| session.id == session_id),
+        Err(_) => return revoke_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to revoke session", None),
+    };
+    if !owned {
+        return revoke_response(StatusCode::NOT_FOUND, "Session not found", None);
+    }
+    match end_session(mongoc, &session_id).await {
+        Ok(true) => revoke_response(StatusCode::OK, "Session revoked successfully", Some(1)),
+        Ok(false) => revoke_response(StatusCode::NOT_FOUND, "Session not found", None),
+        Err(_) => revoke_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to revoke session", None),
+    }
+}
+
+async fn revoke_sessions_handler(
+    headers: HeaderMap,
+    Principal { email, .. }: Principal,
+    Json(RevokeSessionsBody {
+        kind,
+        keep_current,
+    }): Json<RevokeSessionsBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let current_session_id = current_session_id(&headers).filter(|_| keep_current);
+    let mut revoked = 0;
+    if kind.is_none_or(|kind| kind == SessionKind::Web) {
+        match end_sessions(mongoc, SessionKind::Web, &email, current_session_id.as_deref()).await {
+            Ok(count) => revoked += count,
+            Err(_) => return revoke_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to revoke sessions", None),
+        }
+    }
+    if kind.is_none_or(|kind| kind == SessionKind::Iot) {
+        let revoked_iot = match find_client_id(mongoc, &email).await {
+            Ok(Some(client_id)) => end_sessions(mongoc, SessionKind::Iot, &client_id, None).await,
+            Ok(None) => Ok(0),
+            Err(e) => Err(e),
+        };
+        match revoked_iot {
+            Ok(count) => revoked += count,
+            Err(_) => return revoke_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to revoke sessions", None),
+        }
+    }
+    revoke_response(StatusCode::OK, "Sessions revoked successfully", Some(revoked))
+}
+
+// Only ever about the sessions of the logged in user, never taken from the request
+pub fn session_routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/",
+            Policy::Authenticated,
+            get_with(get_sessions_handler, |op| {
+                op.description("Get the active web and gateway sessions of the user")
+                    .tag("Authentication")
+                    .response::<200, Json<GetSessionsResponse>>()
+                    .response::<500, Json<GetSessionsResponse>>()
+            })
+            .delete_with(revoke_session_handler, |op| {
+                op.description("Revoke a session along with its refresh token and access tokens")
+                    .tag("Authentication")
+                    .response::<200, Json<RevokeSessionsResponse>>()
+                    .response::<404, Json<RevokeSessionsResponse>>()
+                    .response::<500, Json<RevokeSessionsResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/all",
+            Policy::Authenticated,
+            delete_with(revoke_sessions_handler, |op| {
+                op.description("Revoke every web session, every gateway token of the user, or both")
+                    .tag("Authentication")
+                    .response::<200, Json<RevokeSessionsResponse>>()
+                    .response::<500, Json<RevokeSessionsResponse>>()
+            }),
+        )
+}
@@ -0,0 +1,185 @@
+use std::{convert::Infallible, time::Duration};
+
+use aide::axum::{routing::get_with, ApiRouter, IntoApiResponse};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query,
+    },
+    http::StatusCode,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
+};
+use futures::stream::{self, Stream};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{get_email_from_web_session, principal::Principal},
+    backend_core::{channels::subscribe_events, events::FeatureEvent, households::memberships::find_household_role},
+    json::Json,
+};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+use crate::{
+    config::JWT_KEY,
+    database_client::{init_database, MONGOC},
+};
+
+const KEEP_ALIVE_SECS: u64 = 15;
+
+#[derive(Deserialize, JsonSchema)]
+pub struct StreamQuery {
+    // Browsers cannot set headers on `EventSource` and `WebSocket`, so the
+    // token may also be passed here
+    jwt: Option<String>,
+    // Comma separated event kinds, e.g. `sensor_reading,command_acked`.
+    // Every kind is streamed when omitted.
+    kinds: Option<String>,
+    // Owner of the household to follow, the user's own data when omitted
+    email: Option<String>,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct StreamErrorResponse {
+    message: String,
+}
+
+// Only forwards the events of one household, optionally of some kinds only.
+struct EventFilter {
+    owner_name: String,
+    kinds: Option<Vec<String>>,
+}
+
+impl EventFilter {
+    fn accepts(&self, event: &FeatureEvent) -> bool {
+        event.owner_name() == self.owner_name
+            && self
+                .kinds
+                .as_ref()
+                .is_none_or(|kinds| kinds.iter().any(|kind| kind == event.kind()))
+    }
+
+    // Waits for the next event of the user. Events missed because the client
+    // was too slow are skipped. Returns `None` once the bus is gone.
+    async fn next(&self, events: &mut Receiver<FeatureEvent>) -> Option<FeatureEvent> {
+        loop {
+            match events.recv().await {
+                Ok(event) if self.accepts(&event) => return Some(event),
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    eprintln!("Stream of household '{}' fell behind, skipped {skipped} events", self.owner_name)
+                }
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
+}
+
+// Browsers cannot send the `jwt` header, so the principal is only there when
+// the token came in a header
+async fn authenticate(
+    principal: Option<Principal>,
+    StreamQuery { jwt, kinds, email: owner_name }: StreamQuery,
+) -> Option<EventFilter> {
+    let owner_name = match principal {
+        Some(principal) => {
+            let owner_name = owner_name.unwrap_or_else(|| principal.email.clone());
+            principal.role_in(&owner_name)?;
+            owner_name
+        }
+        None => {
+            let mongoc = MONGOC.get_or_init(init_database).await;
+            let email = get_email_from_web_session(JWT_KEY.as_str(), jwt?, mongoc).await?;
+            let owner_name = owner_name.unwrap_or_else(|| email.clone());
+            find_household_role(mongoc, &email, &owner_name).await.ok()??;
+            owner_name
+        }
+    };
+    let kinds = kinds.map(|kinds| kinds.split(',').map(|kind| kind.trim().to_string()).collect());
+    Some(EventFilter { owner_name, kinds })
+}
+
+fn forbidden() -> Response {
+    (
+        StatusCode::FORBIDDEN,
+        Json(StreamErrorResponse {
+            message: String::from("Forbidden"),
+        }),
+    )
+        .into_response()
+}
+
+fn event_stream(filter: EventFilter) -> impl Stream<Item = Result<Event, Infallible>> {
+    stream::unfold((filter, subscribe_events()), |(filter, mut events)| async move {
+        let event = filter.next(&mut events).await?;
+        let sse_event = Event::default()
+            .event(event.kind())
+            .json_data(&event)
+            .unwrap_or_else(|_| Event::default().comment("unserializable event"));
+        Some((Ok(sse_event), (filter, events)))
+    })
+}
+
+async fn sse_handler(principal: Option<Principal>, Query(query): Query<StreamQuery>) -> impl IntoApiResponse {
+    match authenticate(principal, query).await {
+        Some(filter) => Sse::new(event_stream(filter))
+            .keep_alive(KeepAlive::new().interval(Duration::from_secs(KEEP_ALIVE_SECS)))
+            .into_response(),
+        None => forbidden(),
+    }
+}
+
+async fn forward_events(mut socket: WebSocket, filter: EventFilter) {
+    let mut events = subscribe_events();
+    loop {
+        tokio::select! {
+            event = filter.next(&mut events) => {
+                let Some(event) = event else { break };
+                let Ok(text) = serde_json::to_string(&event) else { continue };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+            message = socket.recv() => match message {
+                // Clients have nothing to say, but pings are answered by axum
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+async fn ws_handler(
+    principal: Option<Principal>,
+    Query(query): Query<StreamQuery>,
+    ws: WebSocketUpgrade,
+) -> impl IntoApiResponse {
+    match authenticate(principal, query).await {
+        Some(filter) => ws.on_upgrade(|socket| forward_events(socket, filter)),
+        None => forbidden(),
+    }
+}
+
+pub fn stream_routes() -> ApiRouter {
+    ApiRouter::new()
+        .api_route(
+            "/",
+            get_with(sse_handler, |op| {
+                op.description("Server-sent events of the user's readings, incidents, connections and command acknowledgements as they are ingested")
+                    .tag("Stream")
+                    .response::<200, ()>()
+                    .response::<403, Json<StreamErrorResponse>>()
+            }),
+        )
+        .api_route(
+            "/ws",
+            get_with(ws_handler, |op| {
+                op.description("The same events as `/api/stream`, as JSON text messages over a WebSocket")
+                    .tag("Stream")
+                    .response::<101, ()>()
+                    .response::<403, Json<StreamErrorResponse>>()
+            }),
+        )
+}
@@ -0,0 +1,144 @@
+use mongodb::{bson::doc, Collection};
+use tempusalert_be::{
+    auth::{
+        login_attempts::{blocked_for, clear_failures, record_failure, AttemptScope, ThrottlePolicy},
+        security_events::{record_security_event, SecurityEventKind},
+    },
+    backend_core::models::User,
+};
+
+use crate::{config::CONFIG, mail::send_mail};
+
+fn throttle_policy(scope: AttemptScope) -> &'static ThrottlePolicy {
+    match scope {
+        AttemptScope::WebAccount => &CONFIG.login_throttle.web_account,
+        AttemptScope::WebIp => &CONFIG.login_throttle.web_ip,
+        AttemptScope::IotClient => &CONFIG.login_throttle.iot_client,
+        AttemptScope::IotIp => &CONFIG.login_throttle.iot_ip,
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum LoginKind {
+    Web,
+    Iot,
+}
+
+impl LoginKind {
+    fn account_scope(self) -> AttemptScope {
+        match self {
+            LoginKind::Web => AttemptScope::WebAccount,
+            LoginKind::Iot => AttemptScope::IotClient,
+        }
+    }
+
+    fn ip_scope(self) -> AttemptScope {
+        match self {
+            LoginKind::Web => AttemptScope::WebIp,
+            LoginKind::Iot => AttemptScope::IotIp,
+        }
+    }
+
+    fn keys<'a>(self, account: Option<&'a str>, ip: Option<&'a str>) -> Vec<(AttemptScope, &'a str)> {
+        [(self.account_scope(), account), (self.ip_scope(), ip)]
+            .into_iter()
+            .filter_map(|(scope, key)| Some((scope, key?)))
+            .collect()
+    }
+}
+
+// Seconds to wait before the account, or anything from the IP, may be tried
+// again. The account is the email of web logins and the client id of gateways.
+pub async fn login_blocked_for(
+    mongoc: &mongodb::Client,
+    kind: LoginKind,
+    account: Option<&str>,
+    ip: Option<&str>,
+) -> mongodb::error::Result<Option<u64>> {
+    let mut longest = None;
+    for (scope, key) in kind.keys(account, ip) {
+        longest = longest.max(blocked_for(mongoc, scope, key).await?);
+    }
+    Ok(longest)
+}
+
+// Failing to count an attempt only loosens the limits, so it does not fail
+// the request
+pub async fn record_login_failure(mongoc: &mongodb::Client, kind: LoginKind, account: Option<&str>, ip: Option<&str>) {
+    for (scope, key) in kind.keys(account, ip) {
+        match record_failure(mongoc, throttle_policy(scope), scope, key).await {
+            Ok(outcome) if outcome.locked_out => {
+                record_security_event(
+                    mongoc,
+                    SecurityEventKind::LoginLockout {
+                        scope,
+                        key: key.to_string(),
+                    },
+                )
+                .await;
+                notify_lockout(mongoc, scope, key, outcome.delay_secs).await;
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("Failed to record failed login of '{}': {}", key, e),
+        }
+    }
+}
+
+// Only the account is forgiven, failures from the IP still count
+pub async fn record_login_success(mongoc: &mongodb::Client, kind: LoginKind, account: &str) {
+    if let Err(e) = clear_failures(mongoc, kind.account_scope(), account).await {
+        eprintln!("Failed to clear failed logins of '{}': {}", account, e);
+    }
+}
+
+async fn notify_lockout(mongoc: &mongodb::Client, scope: AttemptScope, key: &str, lockout_secs: u64) {
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    let filter = match scope {
+        AttemptScope::WebAccount => doc! { "email": key },
+        AttemptScope::IotClient => doc! { "client_id": key },
+        AttemptScope::WebIp | AttemptScope::IotIp => return,
+    };
+    // Nobody to tell when the guesses were for an unknown account
+    let Ok(Some(user)) = user_coll.find_one(filter, None).await else {
+        return;
+    };
+    let minutes = lockout_secs.div_ceil(60);
+    let (title, message) = match scope {
+        AttemptScope::IotClient => (
+            "Your gateway has been locked out",
+            format!(
+                "
+                <p> Your gateway {key} failed to log in too many times, so its logins are refused for {minutes} minutes. </p>
+                <p> Logging in on the web is not affected. If the gateway credential was not changed by you, please rotate it. </p>
+                "
+            ),
+        ),
+        _ => (
+            "Your account has been locked",
+            format!(
+                "
+                <p> There were too many failed attempts to log in to your account, so logging in is locked for {minutes} minutes. </p>
+                <p> If it was not you, please reset your password, which also unlocks your account. </p>
+                "
+            ),
+        ),
+    };
+    if send_mail(
+        user.email.clone(),
+        String::from(title),
+        format!(
+            "
+            {message}
+
+            <footer>
+                <p>Best wishes,<p>
+                <p>Tempusalert team<p>
+            </footer>
+        "
+        ),
+    )
+    .is_none()
+    {
+        eprintln!("Failed to send lockout notification to '{}'", user.email);
+    }
+}
@@ -0,0 +1,248 @@
+use aide::axum::{
+    routing::{get_with, post_with},
+    ApiRouter, IntoApiResponse,
+};
+use axum::http::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{
+        policy::{AuthorizedRouter, Policy},
+        principal::Principal,
+        totp::{base32_encode, provisioning_uri, SystemClock},
+        two_factor::{
+            confirm_enrollment, disable_two_factor, find_two_factor, regenerate_recovery_codes, start_enrollment,
+            verify_second_factor,
+        },
+    },
+    json::Json,
+};
+
+use crate::{
+    config::TOTP_KEY,
+    database_client::{init_database, MONGOC},
+};
+
+// Shown by authenticator apps next to the account
+const TOTP_ISSUER: &str = "Tempusalert";
+
+#[derive(Deserialize, JsonSchema)]
+pub struct TwoFactorCodeBody {
+    // Code of the authenticator app, recovery codes are also accepted except
+    // when confirming the enrollment
+    code: String,
+}
+
+#[derive(Serialize, JsonSchema, Default)]
+pub struct TwoFactorResponse {
+    message: String,
+    enabled: Option<bool>,
+    recovery_codes_left: Option<usize>,
+    // To be rendered as a QR code for the authenticator app
+    provisioning_uri: Option<String>,
+    // Base32 secret, for apps that cannot scan the QR code
+    secret: Option<String>,
+    // Only ever returned once, when they are generated
+    recovery_codes: Option<Vec<String>>,
+}
+
+fn two_factor_response(status_code: StatusCode, message: &str) -> (StatusCode, Json<TwoFactorResponse>) {
+    (
+        status_code,
+        Json(TwoFactorResponse {
+            message: String::from(message),
+            ..Default::default()
+        }),
+    )
+}
+
+async fn get_two_factor_handler(Principal { email, .. }: Principal) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match find_two_factor(mongoc, &email).await {
+        Ok(two_factor) => (
+            StatusCode::OK,
+            Json(TwoFactorResponse {
+                message: String::from("Successfully fetch two-factor authentication"),
+                enabled: Some(two_factor.as_ref().is_some_and(|two_factor| two_factor.enabled)),
+                recovery_codes_left: two_factor
+                    .filter(|two_factor| two_factor.enabled)
+                    .map(|two_factor| two_factor.recovery_code_hashes.len()),
+                ..Default::default()
+            }),
+        ),
+        Err(_) => two_factor_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Unexpected error while fetching two-factor authentication",
+        ),
+    }
+}
+
+// Starting over replaces the secret of an enrollment that was not confirmed
+async fn enroll_two_factor_handler(Principal { email, .. }: Principal) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match find_two_factor(mongoc, &email).await {
+        Ok(Some(two_factor)) if two_factor.enabled => {
+            return two_factor_response(StatusCode::CONFLICT, "Two-factor authentication is already enabled")
+        }
+        Ok(_) => {}
+        Err(_) => {
+            return two_factor_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to enroll two-factor authentication",
+            )
+        }
+    }
+    match start_enrollment(mongoc, &TOTP_KEY, &email).await {
+        Ok(Some(secret)) => (
+            StatusCode::OK,
+            Json(TwoFactorResponse {
+                message: String::from("Scan the QR code and confirm with a code of the authenticator app"),
+                enabled: Some(false),
+                provisioning_uri: Some(provisioning_uri(TOTP_ISSUER, &email, &secret)),
+                secret: Some(base32_encode(&secret)),
+                ..Default::default()
+            }),
+        ),
+        _ => two_factor_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to enroll two-factor authentication",
+        ),
+    }
+}
+
+async fn confirm_two_factor_handler(
+    Principal { email, .. }: Principal,
+    Json(TwoFactorCodeBody { code }): Json<TwoFactorCodeBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match confirm_enrollment(mongoc, &TOTP_KEY, &SystemClock, &email, &code).await {
+        Ok(Some(recovery_codes)) => (
+            StatusCode::OK,
+            Json(TwoFactorResponse {
+                message: String::from("Two-factor authentication enabled, store the recovery codes safely"),
+                enabled: Some(true),
+                recovery_codes_left: Some(recovery_codes.len()),
+                recovery_codes: Some(recovery_codes),
+                ..Default::default()
+            }),
+        ),
+        Ok(None) => two_factor_response(StatusCode::BAD_REQUEST, "Invalid code or no pending enrollment"),
+        Err(_) => two_factor_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to confirm two-factor authentication",
+        ),
+    }
+}
+
+async fn regenerate_recovery_codes_handler(
+    Principal { email, .. }: Principal,
+    Json(TwoFactorCodeBody { code }): Json<TwoFactorCodeBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match verify_second_factor(mongoc, &TOTP_KEY, &SystemClock, &email, &code).await {
+        Ok(true) => {}
+        Ok(false) => return two_factor_response(StatusCode::BAD_REQUEST, "Invalid code"),
+        Err(_) => {
+            return two_factor_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to regenerate recovery codes",
+            )
+        }
+    }
+    match regenerate_recovery_codes(mongoc, &email).await {
+        Ok(Some(recovery_codes)) => (
+            StatusCode::OK,
+            Json(TwoFactorResponse {
+                message: String::from("Recovery codes regenerated, the previous ones no longer work"),
+                enabled: Some(true),
+                recovery_codes_left: Some(recovery_codes.len()),
+                recovery_codes: Some(recovery_codes),
+                ..Default::default()
+            }),
+        ),
+        _ => two_factor_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to regenerate recovery codes",
+        ),
+    }
+}
+
+// A stolen access token alone is not enough to turn it off
+async fn disable_two_factor_handler(
+    Principal { email, .. }: Principal,
+    Json(TwoFactorCodeBody { code }): Json<TwoFactorCodeBody>,
+) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    match verify_second_factor(mongoc, &TOTP_KEY, &SystemClock, &email, &code).await {
+        Ok(true) => {}
+        Ok(false) => return two_factor_response(StatusCode::BAD_REQUEST, "Invalid code"),
+        Err(_) => {
+            return two_factor_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to disable two-factor authentication",
+            )
+        }
+    }
+    match disable_two_factor(mongoc, &email).await {
+        Ok(_) => two_factor_response(StatusCode::OK, "Two-factor authentication disabled"),
+        Err(_) => two_factor_response(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Failed to disable two-factor authentication",
+        ),
+    }
+}
+
+// Only ever about the account of the logged in user, never taken from the request
+pub fn two_factor_routes() -> ApiRouter {
+    ApiRouter::new()
+        .authorized_route(
+            "/",
+            Policy::Authenticated,
+            get_with(get_two_factor_handler, |op| {
+                op.description("Get whether two-factor authentication is enabled and how many recovery codes are left")
+                    .tag("Two-factor authentication")
+                    .response::<200, Json<TwoFactorResponse>>()
+                    .response::<500, Json<TwoFactorResponse>>()
+            })
+            .delete_with(disable_two_factor_handler, |op| {
+                op.description("Disable two-factor authentication, requires a code")
+                    .tag("Two-factor authentication")
+                    .response::<200, Json<TwoFactorResponse>>()
+                    .response::<400, Json<TwoFactorResponse>>()
+                    .response::<500, Json<TwoFactorResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/enroll",
+            Policy::Authenticated,
+            post_with(enroll_two_factor_handler, |op| {
+                op.description("Start enrolling an authenticator app, returns the provisioning uri for the QR code")
+                    .tag("Two-factor authentication")
+                    .response::<200, Json<TwoFactorResponse>>()
+                    .response::<409, Json<TwoFactorResponse>>()
+                    .response::<500, Json<TwoFactorResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/confirm",
+            Policy::Authenticated,
+            post_with(confirm_two_factor_handler, |op| {
+                op.description("Enable two-factor authentication with a first code of the authenticator app, returns the recovery codes")
+                    .tag("Two-factor authentication")
+                    .response::<200, Json<TwoFactorResponse>>()
+                    .response::<400, Json<TwoFactorResponse>>()
+                    .response::<500, Json<TwoFactorResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/recovery-codes",
+            Policy::Authenticated,
+            post_with(regenerate_recovery_codes_handler, |op| {
+                op.description("Replace the recovery codes, requires a code")
+                    .tag("Two-factor authentication")
+                    .response::<200, Json<TwoFactorResponse>>()
+                    .response::<400, Json<TwoFactorResponse>>()
+                    .response::<500, Json<TwoFactorResponse>>()
+            }),
+        )
+}
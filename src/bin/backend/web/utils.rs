@@ -1,48 +1,25 @@
-use std::num::NonZeroU32;
+use std::net::SocketAddr;
 
-use once_cell::sync::Lazy;
-use ring::{
-    digest, pbkdf2,
-    rand::{self, SecureRandom},
-};
+use axum::http::{header::USER_AGENT, HeaderMap};
+use tempusalert_be::auth::sessions::{client_ip, SessionOrigin};
 
-const N_ITER: Lazy<NonZeroU32> = Lazy::new(|| NonZeroU32::new(100_000).unwrap());
+use crate::config::CONFIG;
 
-pub fn hash_password(
-    password: String,
-) -> Option<(
-    [u8; digest::SHA512_OUTPUT_LEN],
-    [u8; digest::SHA512_OUTPUT_LEN],
-)> {
-    const CREDENTIAL_LEN: usize = digest::SHA512_OUTPUT_LEN;
-    let rng = rand::SystemRandom::new();
+pub use tempusalert_be::auth::hashing::{hash_password, verify_hashed_password};
 
-    let mut salt = [0u8; CREDENTIAL_LEN];
-    rng.fill(&mut salt).ok()?;
-
-    let mut pbkdf2_hash = [0u8; CREDENTIAL_LEN];
-    pbkdf2::derive(
-        pbkdf2::PBKDF2_HMAC_SHA512,
-        N_ITER.to_owned(),
-        &salt,
-        password.as_bytes(),
-        &mut pbkdf2_hash,
-    );
-
-    Some((pbkdf2_hash, salt))
-}
-
-pub fn verify_hashed_password(
-    sent_password: String,
-    hashed_password: [u8; digest::SHA512_OUTPUT_LEN],
-    salt: [u8; digest::SHA512_OUTPUT_LEN],
-) -> bool {
-    pbkdf2::verify(
-        pbkdf2::PBKDF2_HMAC_SHA512,
-        N_ITER.to_owned(),
-        &salt,
-        sent_password.as_bytes(),
-        &hashed_password,
-    )
-    .is_ok()
+// Takes the client address forwarded by a reverse proxy only from the proxies
+// of `server.trusted_proxies`, anyone else could make it up
+pub fn session_origin(headers: &HeaderMap, addr: SocketAddr) -> SessionOrigin {
+    let header = |name| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(String::from)
+    };
+    let forwarded_for = header("x-forwarded-for");
+    let ip = client_ip(addr.ip(), forwarded_for.as_deref(), &CONFIG.server.trusted_proxies);
+    SessionOrigin {
+        user_agent: header(USER_AGENT.as_str()),
+        ip: Some(ip.to_string()),
+    }
 }
@@ -0,0 +1,130 @@
+use aide::axum::{
+    routing::{get_with, post_with},
+    ApiRouter, IntoApiResponse,
+};
+use axum::{
+    extract::Query,
+    http::StatusCode,
+};
+use mongodb::{bson::doc, Collection};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tempusalert_be::{
+    auth::{
+        get_email_from_verification_token,
+        policy::{AuthorizedRouter, Policy},
+        principal::Principal,
+        sign_jwt, EmailVerificationClaim,
+    },
+    backend_core::models::User,
+    json::Json,
+};
+
+use crate::{
+    config::{CONFIG, JWT_KEY},
+    database_client::{init_database, MONGOC},
+    mail::send_mail,
+};
+
+#[derive(Deserialize, JsonSchema)]
+pub struct VerifyEmailQuery {
+    token: String,
+}
+
+#[derive(Serialize, JsonSchema)]
+pub struct VerificationResponse {
+    message: String,
+}
+
+// A signed link that expires, nothing needs to be stored until it is opened
+pub fn verification_link(email: String) -> Option<String> {
+    let claim = EmailVerificationClaim::new(email, CONFIG.auth.email_verification_ttl_secs);
+    let token = sign_jwt(JWT_KEY.as_str(), &claim)?;
+    Some(format!("{}/auth/verify-email?token={}", CONFIG.auth.api_url, token))
+}
+
+fn verification_response(status_code: StatusCode, message: &str) -> (StatusCode, Json<VerificationResponse>) {
+    (
+        status_code,
+        Json(VerificationResponse {
+            message: String::from(message),
+        }),
+    )
+}
+
+async fn verify_email_handler(Query(VerifyEmailQuery { token }): Query<VerifyEmailQuery>) -> impl IntoApiResponse {
+    let Some(email) = get_email_from_verification_token(JWT_KEY.as_str(), &token) else {
+        return verification_response(StatusCode::BAD_REQUEST, "Invalid or expired verification link");
+    };
+
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    match user_coll
+        .update_one(doc! { "email": email }, doc! { "$set": { "email_verified": true } }, None)
+        .await
+    {
+        Ok(result) if result.matched_count > 0 => verification_response(StatusCode::OK, "Email verified successfully"),
+        Ok(_) => verification_response(StatusCode::BAD_REQUEST, "Invalid or expired verification link"),
+        Err(_) => verification_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to verify email"),
+    }
+}
+
+// To the email of the logged in user only
+async fn resend_verification_handler(Principal { email, .. }: Principal) -> impl IntoApiResponse {
+    let mongoc = MONGOC.get_or_init(init_database).await;
+    let user_coll: Collection<User> = mongoc.default_database().unwrap().collection("users");
+    match user_coll.find_one(doc! { "email": email.clone() }, None).await {
+        Ok(Some(User { email_verified: true, .. })) => {
+            return verification_response(StatusCode::BAD_REQUEST, "Email already verified")
+        }
+        Ok(Some(_)) => {}
+        Ok(None) => return verification_response(StatusCode::FORBIDDEN, "Forbidden"),
+        Err(_) => return verification_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send verification mail"),
+    }
+    let Some(link) = verification_link(email.clone()) else {
+        return verification_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send verification mail");
+    };
+    match send_mail(
+        email,
+        String::from("Verify your email"),
+        format!(
+            "
+            <p> Please <a href=\"{link}\">verify your email</a>. Your gateway can connect once it is verified. </p>
+
+            <footer>
+                <p>Best wishes,<p>
+                <p>Tempusalert team<p>
+            </footer>
+        "
+        ),
+    ) {
+        Some(_) => verification_response(StatusCode::OK, "Verification mail sent"),
+        None => verification_response(StatusCode::INTERNAL_SERVER_ERROR, "Failed to send verification mail"),
+    }
+}
+
+pub fn verification_routes() -> ApiRouter {
+    ApiRouter::new()
+        .api_route(
+            "/",
+            get_with(verify_email_handler, |op| {
+                op.description("Verify the email of an account with the link sent by mail")
+                    .tag("Authentication")
+                    .response::<200, Json<VerificationResponse>>()
+                    .response::<400, Json<VerificationResponse>>()
+                    .response::<500, Json<VerificationResponse>>()
+            }),
+        )
+        .authorized_route(
+            "/resend",
+            Policy::Authenticated,
+            post_with(resend_verification_handler, |op| {
+                op.description("Send the verification mail again")
+                    .tag("Authentication")
+                    .response::<200, Json<VerificationResponse>>()
+                    .response::<400, Json<VerificationResponse>>()
+                    .response::<403, Json<VerificationResponse>>()
+                    .response::<500, Json<VerificationResponse>>()
+            }),
+        )
+}